@@ -1,7 +1,16 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::{hash, hashv};
 
 declare_id!("2ZE5N8rTU2S2GUuQGX8ZsBAraByUqD37hYP8pz1hYLLJ");
 
+const MAX_KEY_LEN: usize = 64;
+const MAX_WRITERS: usize = 10;
+const BUFFER_HEADER_LEN: usize = 8 + BufferPDA::INIT_SPACE;
+// Accounts are capped at 10 MiB of data
+const MAX_BUFFER_LEN: u32 = (10 * 1024 * 1024 - BUFFER_HEADER_LEN) as u32;
+// The bytes of a zero-copy account follow its header, up to the end of the account
+const LARGE_BYTES_HEADER_LEN: usize = 8 + std::mem::size_of::<LargeBytesAccount>();
+
 #[program]
 pub mod storage {
     use super::*;
@@ -13,15 +22,248 @@ pub mod storage {
 
     pub fn store_string(ctx: Context<StoreStringCtx>, data_to_store: String) -> Result<()> {
         let string_storage_pda = &mut ctx.accounts.string_storage_pda;
+        resize(
+            &string_storage_pda.to_account_info(),
+            &ctx.accounts.user,
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.system_program,
+            8 + 4 + data_to_store.len(),
+        )?;
         string_storage_pda.my_string = data_to_store;
         Ok(())
     }
 
     pub fn store_bytes(ctx: Context<StoreBytesCtx>, data_to_store: Vec<u8>) -> Result<()> {
         let bytes_storage_pda = &mut ctx.accounts.bytes_storage_pda;
+        resize(
+            &bytes_storage_pda.to_account_info(),
+            &ctx.accounts.user,
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.system_program,
+            8 + 4 + data_to_store.len(),
+        )?;
         bytes_storage_pda.my_bytes = data_to_store;
         Ok(())
     }
+
+    pub fn initialize_large_bytes(ctx: Context<InitializeLargeBytesCtx>) -> Result<()> {
+        msg!("Initializing a zero-copy bytes account");
+        let mut large_bytes = ctx.accounts.large_bytes.load_init()?;
+        large_bytes.owner = ctx.accounts.user.key();
+        large_bytes.len = 0;
+        Ok(())
+    }
+
+    // Writes at the given offset and truncates the contents right after the written bytes
+    pub fn store_large_bytes(
+        ctx: Context<StoreLargeBytesCtx>,
+        offset: u32,
+        bytes: Vec<u8>,
+    ) -> Result<()> {
+        let large_bytes = &ctx.accounts.large_bytes;
+        let old_len = large_bytes.load()?.len as usize;
+        let start = offset as usize;
+        require!(start <= old_len, CustomError::InvalidOffset);
+        let capacity = large_bytes.as_ref().data_len() - LARGE_BYTES_HEADER_LEN;
+        let end = start
+            .checked_add(bytes.len())
+            .filter(|end| *end <= capacity)
+            .ok_or(CustomError::InvalidBufferLength)?;
+
+        {
+            let mut data = large_bytes.as_ref().try_borrow_mut_data()?;
+            let contents = &mut data[LARGE_BYTES_HEADER_LEN..];
+            contents[start..end].copy_from_slice(&bytes);
+            if end < old_len {
+                contents[end..old_len].fill(0);
+            }
+        }
+        large_bytes.load_mut()?.len = end as u64;
+        Ok(())
+    }
+
+    pub fn store(ctx: Context<StoreCtx>, key: String, value: Vec<u8>) -> Result<()> {
+        require!(!key.is_empty(), CustomError::InvalidKey);
+        require!(key.len() <= MAX_KEY_LEN, CustomError::InvalidKey);
+
+        let user = ctx.accounts.user.key();
+        let owner = ctx.accounts.owner.key();
+        let entry_pda = &mut ctx.accounts.entry_pda;
+        // Only the owner can create new entries, the writers can update existing ones
+        if entry_pda.owner == Pubkey::default() {
+            require!(user == owner, CustomError::Unauthorized);
+            entry_pda.owner = owner;
+        }
+        require!(!entry_pda.read_only, CustomError::ReadOnly);
+        require!(
+            user == owner || entry_pda.writers.contains(&user),
+            CustomError::Unauthorized
+        );
+
+        // The signer pays for any growth, while refunds on shrink always go to the owner:
+        // rent paid by a writer is not returned to it
+        entry_pda.key = key.clone();
+        entry_pda.value = value;
+        let new_len = entry_pda.space();
+        resize(
+            &entry_pda.to_account_info(),
+            &ctx.accounts.user,
+            &ctx.accounts.owner.to_account_info(),
+            &ctx.accounts.system_program,
+            new_len,
+        )?;
+
+        let storage_index = &mut ctx.accounts.storage_index;
+        if !storage_index.keys.contains(&key) {
+            msg!("Adding key {} to the index", key);
+            storage_index.keys.push(key);
+            let new_len = storage_index.space();
+            resize(
+                &storage_index.to_account_info(),
+                &ctx.accounts.user,
+                &ctx.accounts.owner.to_account_info(),
+                &ctx.accounts.system_program,
+                new_len,
+            )?;
+        }
+        Ok(())
+    }
+
+    // The entry is closed by the `close` constraint, which refunds its rent to the user
+    pub fn delete(ctx: Context<DeleteCtx>, key: String) -> Result<()> {
+        require!(!ctx.accounts.entry_pda.read_only, CustomError::ReadOnly);
+
+        let storage_index = &mut ctx.accounts.storage_index;
+        msg!("Removing key {} from the index", key);
+        storage_index.keys.retain(|k| *k != key);
+        let new_len = storage_index.space();
+        resize(
+            &storage_index.to_account_info(),
+            &ctx.accounts.user,
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.system_program,
+            new_len,
+        )?;
+        Ok(())
+    }
+
+    pub fn grant_writer(ctx: Context<ManageEntryCtx>, _key: String, writer: Pubkey) -> Result<()> {
+        let entry_pda = &mut ctx.accounts.entry_pda;
+        require!(!entry_pda.read_only, CustomError::ReadOnly);
+        require!(
+            entry_pda.writers.len() < MAX_WRITERS,
+            CustomError::TooManyWriters
+        );
+
+        if !entry_pda.writers.contains(&writer) {
+            msg!("Granting write access to {}", writer);
+            entry_pda.writers.push(writer);
+            let new_len = entry_pda.space();
+            resize(
+                &entry_pda.to_account_info(),
+                &ctx.accounts.user,
+                &ctx.accounts.user.to_account_info(),
+                &ctx.accounts.system_program,
+                new_len,
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn revoke_writer(ctx: Context<ManageEntryCtx>, _key: String, writer: Pubkey) -> Result<()> {
+        let entry_pda = &mut ctx.accounts.entry_pda;
+        require!(!entry_pda.read_only, CustomError::ReadOnly);
+        msg!("Revoking write access from {}", writer);
+        entry_pda.writers.retain(|w| *w != writer);
+        let new_len = entry_pda.space();
+        resize(
+            &entry_pda.to_account_info(),
+            &ctx.accounts.user,
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.system_program,
+            new_len,
+        )?;
+        Ok(())
+    }
+
+    // Freezing cannot be undone: the entry can no longer be written nor deleted
+    pub fn freeze(ctx: Context<ManageEntryCtx>, key: String) -> Result<()> {
+        msg!("Freezing key {}", key);
+        ctx.accounts.entry_pda.read_only = true;
+        Ok(())
+    }
+
+    pub fn init_buffer(ctx: Context<InitBufferCtx>, _key: String, total_len: u32) -> Result<()> {
+        require!(
+            total_len > 0 && total_len <= MAX_BUFFER_LEN,
+            CustomError::InvalidBufferLength
+        );
+
+        let buffer_pda = &mut ctx.accounts.buffer_pda;
+        buffer_pda.total_len = total_len;
+        buffer_pda.written = 0;
+        buffer_pda.running_hash = [0; 32];
+        buffer_pda.finalized = false;
+        Ok(())
+    }
+
+    // Chunks are appended in order, the offset guards against replayed or reordered chunks
+    pub fn write_chunk(
+        ctx: Context<WriteChunkCtx>,
+        _key: String,
+        offset: u32,
+        bytes: Vec<u8>,
+    ) -> Result<()> {
+        let buffer_pda = &mut ctx.accounts.buffer_pda;
+        require!(!buffer_pda.finalized, CustomError::BufferFinalized);
+        require!(offset == buffer_pda.written, CustomError::InvalidOffset);
+        let written = u32::try_from(bytes.len())
+            .ok()
+            .and_then(|len| offset.checked_add(len))
+            .filter(|written| *written <= buffer_pda.total_len)
+            .ok_or(CustomError::InvalidBufferLength)?;
+
+        let start = BUFFER_HEADER_LEN + offset as usize;
+        let end = BUFFER_HEADER_LEN + written as usize;
+        let buffer_info = buffer_pda.to_account_info();
+        resize(
+            &buffer_info,
+            &ctx.accounts.user,
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.system_program,
+            end,
+        )?;
+        buffer_info.try_borrow_mut_data()?[start..end].copy_from_slice(&bytes);
+
+        buffer_pda.running_hash = hashv(&[&buffer_pda.running_hash, &bytes]).to_bytes();
+        buffer_pda.written = written;
+        Ok(())
+    }
+
+    // The content hash is the chained hash of the chunks (see BufferPDA), not the sha256 of the
+    // payload: it depends on how the payload was split into chunks
+    pub fn finalize(ctx: Context<FinalizeCtx>, _key: String, content_hash: [u8; 32]) -> Result<()> {
+        let buffer_pda = &mut ctx.accounts.buffer_pda;
+        require!(!buffer_pda.finalized, CustomError::BufferFinalized);
+        require!(
+            buffer_pda.written == buffer_pda.total_len,
+            CustomError::IncompleteBuffer
+        );
+        require!(
+            buffer_pda.running_hash == content_hash,
+            CustomError::InvalidContentHash
+        );
+
+        msg!("Finalizing a buffer of {} bytes", buffer_pda.total_len);
+        buffer_pda.finalized = true;
+        Ok(())
+    }
+
+    // The buffer is closed by the `close` constraint, which refunds its rent to the user
+    pub fn close_buffer(_ctx: Context<CloseBufferCtx>, key: String) -> Result<()> {
+        msg!("Closing the buffer of key {}", key);
+        Ok(())
+    }
 }
 
 #[account]
@@ -34,6 +276,54 @@ pub struct MemoryBytesPDA {
     pub my_bytes: Vec<u8>,
 }
 
+#[account]
+pub struct MemoryEntryPDA {
+    pub key: String,          // 4 + key length bytes
+    pub value: Vec<u8>,       // 4 + value length bytes
+    pub owner: Pubkey,        // 32 bytes
+    pub writers: Vec<Pubkey>, // 4 + 32 bytes per writer, at most MAX_WRITERS
+    pub read_only: bool,      // 1 byte, set by freeze and never cleared
+}
+
+impl MemoryEntryPDA {
+    fn space(&self) -> usize {
+        8 + 4 + self.key.len() + 4 + self.value.len() + 32 + 4 + 32 * self.writers.len() + 1
+    }
+}
+
+#[account]
+pub struct StorageIndex {
+    pub keys: Vec<String>, // 4 + (4 + key length) bytes per key
+}
+
+// Header of a zero-copy bytes account, mapped in place instead of being (de)serialized
+// with borsh; the bytes follow it up to the end of the account. zero_copy relies on the
+// bytemuck traits: the program depends on bytemuck with the "derive" and
+// "min_const_generics" features
+#[account(zero_copy)]
+pub struct LargeBytesAccount {
+    pub owner: Pubkey, // 32 bytes
+    pub len: u64,      // 8 bytes
+}
+
+impl StorageIndex {
+    fn space(&self) -> usize {
+        8 + 4 + self.keys.iter().map(|k| 4 + k.len()).sum::<usize>()
+    }
+}
+
+// Header of a chunked buffer, the payload bytes follow it in the account data.
+// The content hash is chained over the chunks: h_0 = [0; 32], h_i = sha256(h_{i-1} || chunk_i),
+// hashing the whole payload at once would not fit in the compute budget for large buffers.
+#[account]
+#[derive(InitSpace)]
+pub struct BufferPDA {
+    pub total_len: u32,         // 4 bytes
+    pub written: u32,           // 4 bytes
+    pub running_hash: [u8; 32], // 32 bytes
+    pub finalized: bool,        // 1 byte
+}
+
 #[derive(Accounts)]
 pub struct InitializeCtx<'info> {
     #[account(mut)]
@@ -57,8 +347,8 @@ pub struct InitializeCtx<'info> {
     pub bytes_storage_pda: Account<'info, MemoryBytesPDA>,
 }
 
+// The storage accounts are resized to fit in the instruction, refunding the rent when they shrink
 #[derive(Accounts)]
-#[instruction(data_to_store: String)]
 pub struct StoreStringCtx<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
@@ -66,16 +356,12 @@ pub struct StoreStringCtx<'info> {
     #[account(
         mut,
         seeds = [b"storage_string", user.key.as_ref()],
-        bump,
-        realloc = 8 + 4 + data_to_store.len(),
-        realloc::payer = user,
-        realloc::zero = false,
+        bump
     )]
     pub string_storage_pda: Account<'info, MemoryStringPDA>,
 }
 
 #[derive(Accounts)]
-#[instruction(data_to_store: Vec<u8>)]
 pub struct StoreBytesCtx<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
@@ -83,10 +369,215 @@ pub struct StoreBytesCtx<'info> {
     #[account(
         mut,
         seeds = [b"storage_bytes", user.key.as_ref()],
-        bump,
-        realloc = 8 + 4 + data_to_store.len(),
-        realloc::payer = user,
-        realloc::zero = false,
+        bump
     )]
     pub bytes_storage_pda: Account<'info, MemoryBytesPDA>,
 }
+
+// The account is created by the client in the same transaction, owned by the program:
+// accounts created by a program through CPI are capped at 10 KiB, while the client can
+// allocate up to 10 MiB
+#[derive(Accounts)]
+pub struct InitializeLargeBytesCtx<'info> {
+    pub user: Signer<'info>,
+    #[account(zero)]
+    pub large_bytes: AccountLoader<'info, LargeBytesAccount>,
+}
+
+#[derive(Accounts)]
+pub struct StoreLargeBytesCtx<'info> {
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        constraint = large_bytes.load()?.owner == user.key() @ CustomError::Unauthorized
+    )]
+    pub large_bytes: AccountLoader<'info, LargeBytesAccount>,
+}
+
+// Entries are seeded by the hash of their key, so keys of any length fit in the seeds.
+// The user is either the owner of the entry or one of its writers.
+#[derive(Accounts)]
+#[instruction(key: String)]
+pub struct StoreCtx<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut)] // mutable to receive the rent refunds
+    pub owner: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"storage_entry", owner.key.as_ref(), hash(key.as_bytes()).as_ref()],
+        bump,
+        space = 8 + 4 + 4 + 32 + 4 + 1 // resized to the key and value in the instruction
+    )]
+    pub entry_pda: Account<'info, MemoryEntryPDA>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"storage_index", owner.key.as_ref()],
+        bump,
+        space = 8 + 4 // resized when a new key is added
+    )]
+    pub storage_index: Account<'info, StorageIndex>,
+}
+
+#[derive(Accounts)]
+#[instruction(key: String)]
+pub struct DeleteCtx<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    #[account(
+        mut,
+        seeds = [b"storage_entry", user.key.as_ref(), hash(key.as_bytes()).as_ref()],
+        bump,
+        close = user
+    )]
+    pub entry_pda: Account<'info, MemoryEntryPDA>,
+    #[account(
+        mut,
+        seeds = [b"storage_index", user.key.as_ref()],
+        bump
+    )]
+    pub storage_index: Account<'info, StorageIndex>,
+}
+
+// Only the owner can manage the writers of an entry and freeze it
+#[derive(Accounts)]
+#[instruction(key: String)]
+pub struct ManageEntryCtx<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    #[account(
+        mut,
+        seeds = [b"storage_entry", user.key.as_ref(), hash(key.as_bytes()).as_ref()],
+        bump
+    )]
+    pub entry_pda: Account<'info, MemoryEntryPDA>,
+}
+
+#[derive(Accounts)]
+#[instruction(key: String)]
+pub struct InitBufferCtx<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    #[account(
+        init,
+        payer = user,
+        seeds = [b"storage_buffer", user.key.as_ref(), hash(key.as_bytes()).as_ref()],
+        bump,
+        space = BUFFER_HEADER_LEN // grown chunk by chunk
+    )]
+    pub buffer_pda: Account<'info, BufferPDA>,
+}
+
+#[derive(Accounts)]
+#[instruction(key: String)]
+pub struct WriteChunkCtx<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    #[account(
+        mut,
+        seeds = [b"storage_buffer", user.key.as_ref(), hash(key.as_bytes()).as_ref()],
+        bump
+    )]
+    pub buffer_pda: Account<'info, BufferPDA>,
+}
+
+#[derive(Accounts)]
+#[instruction(key: String)]
+pub struct FinalizeCtx<'info> {
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"storage_buffer", user.key.as_ref(), hash(key.as_bytes()).as_ref()],
+        bump
+    )]
+    pub buffer_pda: Account<'info, BufferPDA>,
+}
+
+#[derive(Accounts)]
+#[instruction(key: String)]
+pub struct CloseBufferCtx<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"storage_buffer", user.key.as_ref(), hash(key.as_bytes()).as_ref()],
+        bump,
+        close = user
+    )]
+    pub buffer_pda: Account<'info, BufferPDA>,
+}
+
+// Reallocates the account, charging the payer for extra rent and refunding any excess to the owner.
+// Freed bytes are zeroed, so that stale data does not reappear if the account grows again.
+fn resize<'info>(
+    account: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    owner: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    new_len: usize,
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(new_len);
+    let lamports = account.lamports();
+
+    if rent > lamports {
+        let transfer_instruction = anchor_lang::solana_program::system_instruction::transfer(
+            &payer.key(),
+            &account.key(),
+            rent - lamports,
+        );
+        anchor_lang::solana_program::program::invoke(
+            &transfer_instruction,
+            &[
+                payer.to_account_info(),
+                account.clone(),
+                system_program.to_account_info(),
+            ],
+        )?;
+    } else {
+        **account.try_borrow_mut_lamports()? -= lamports - rent;
+        **owner.try_borrow_mut_lamports()? += lamports - rent;
+    }
+
+    if new_len < account.data_len() {
+        account.try_borrow_mut_data()?[new_len..].fill(0);
+    }
+    account.realloc(new_len, false)?;
+    Ok(())
+}
+
+#[error_code]
+pub enum CustomError {
+    #[msg("Invalid key, must be non-empty and at most 64 bytes long")]
+    InvalidKey,
+
+    #[msg("Invalid buffer length")]
+    InvalidBufferLength,
+
+    #[msg("Chunks must be written in order")]
+    InvalidOffset,
+
+    #[msg("The buffer is not completely written")]
+    IncompleteBuffer,
+
+    #[msg("The content hash does not match the written chunks")]
+    InvalidContentHash,
+
+    #[msg("The buffer is finalized")]
+    BufferFinalized,
+
+    #[msg("The signer is not allowed to write this entry")]
+    Unauthorized,
+
+    #[msg("The entry is read-only")]
+    ReadOnly,
+
+    #[msg("Too many writers")]
+    TooManyWriters,
+}
@@ -2,6 +2,7 @@ import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { PublicKey, SystemProgram, Keypair } from "@solana/web3.js";
 import { expect } from "chai";
+import { createHash } from "crypto";
 
 // Generic interface for any storage program
 interface StorageProgram {
@@ -11,6 +12,17 @@ interface StorageProgram {
     store_string?(data: string): any;
     storeBytes?(data: Buffer): any;
     store_bytes?(data: Buffer): any;
+    store(key: string, value: Buffer): any;
+    delete(key: string): any;
+    grantWriter(key: string, writer: PublicKey): any;
+    revokeWriter(key: string, writer: PublicKey): any;
+    freeze(key: string): any;
+    initBuffer(key: string, totalLen: number): any;
+    writeChunk(key: string, offset: number, bytes: Buffer): any;
+    finalize(key: string, contentHash: number[]): any;
+    closeBuffer(key: string): any;
+    initializeLargeBytes(): any;
+    storeLargeBytes(offset: number, bytes: Buffer): any;
   };
   account: {
     memoryStringPda?: {
@@ -25,6 +37,24 @@ interface StorageProgram {
     memoryBytesPDA?: {
       fetch(address: PublicKey): Promise<{ myBytes: Buffer }>;
     };
+    memoryEntryPda: {
+      fetch(address: PublicKey): Promise<{
+        key: string;
+        value: Buffer;
+        owner: PublicKey;
+        writers: PublicKey[];
+        readOnly: boolean;
+      }>;
+    };
+    storageIndex: {
+      fetch(address: PublicKey): Promise<{ keys: string[] }>;
+    };
+    largeBytesAccount: {
+      fetch(address: PublicKey): Promise<{ owner: PublicKey; len: anchor.BN }>;
+    };
+    bufferPda: {
+      fetch(address: PublicKey): Promise<{ totalLen: number; written: number; finalized: boolean }>;
+    };
   };
 }
 
@@ -320,6 +350,22 @@ describe("Storage Program", () => {
       expect(accountData.myString).to.equal(TEST_STRINGS.unicode);
       expect(accountData.myString).to.not.equal(TEST_STRINGS.short);
     });
+
+    it("shrinks the account and refunds the rent for a shorter string", async () => {
+      await executeTransaction(callStoreString(TEST_STRINGS.long));
+      const balanceBefore = await getAccountBalance(user.publicKey);
+
+      const result = await executeTransaction(callStoreString(TEST_STRINGS.short));
+      expect(result.success).to.be.true;
+
+      // The refund exceeds the transaction fee
+      expect(await getAccountBalance(user.publicKey)).to.be.greaterThan(balanceBefore);
+
+      const accountInfo = await provider.connection.getAccountInfo(stringStoragePDA);
+      expect(accountInfo.data.length).to.equal(8 + 4 + Buffer.byteLength(TEST_STRINGS.short));
+      const rent = await provider.connection.getMinimumBalanceForRentExemption(accountInfo.data.length);
+      expect(accountInfo.lamports).to.equal(rent);
+    });
   });
 
   describe("store_bytes()", () => {
@@ -388,6 +434,23 @@ describe("Storage Program", () => {
       expect(Buffer.from(accountData.myBytes)).to.deep.equal(TEST_BYTES.medium);
       expect(Buffer.from(accountData.myBytes)).to.not.deep.equal(TEST_BYTES.small);
     });
+
+    it("shrinks the account and refunds the rent for shorter bytes", async () => {
+      await executeTransaction(callStoreBytes(TEST_BYTES.large));
+      const balanceBefore = await getAccountBalance(user.publicKey);
+
+      const result = await executeTransaction(callStoreBytes(TEST_BYTES.small));
+      expect(result.success).to.be.true;
+      expect(await getAccountBalance(user.publicKey)).to.be.greaterThan(balanceBefore);
+
+      const accountInfo = await provider.connection.getAccountInfo(bytesStoragePDA);
+      expect(accountInfo.data.length).to.equal(8 + 4 + TEST_BYTES.small.length);
+
+      // Growing again does not bring back the old contents
+      await executeTransaction(callStoreBytes(TEST_BYTES.medium));
+      const accountData = await fetchBytesAccount(bytesStoragePDA);
+      expect(Buffer.from(accountData.myBytes)).to.deep.equal(TEST_BYTES.medium);
+    });
   });
 
   describe("data persistence", () => {
@@ -465,4 +528,471 @@ describe("Storage Program", () => {
       expect(Buffer.from(bytesData.myBytes)).to.deep.equal(testBytes);
     });
   });
+
+  describe("keyed storage", () => {
+    let indexPDA: PublicKey;
+
+    beforeEach(() => {
+      [indexPDA] = PublicKey.findProgramAddressSync(
+        [Buffer.from("storage_index"), user.publicKey.toBuffer()],
+        program.programId
+      );
+    });
+
+    function deriveEntry(key: string): PublicKey {
+      const keyHash = createHash("sha256").update(key).digest();
+      return PublicKey.findProgramAddressSync(
+        [Buffer.from("storage_entry"), user.publicKey.toBuffer(), keyHash],
+        program.programId
+      )[0];
+    }
+
+    async function callStore(key: string, value: Buffer, writer: Keypair = user) {
+      return await program.methods
+        .store(key, value)
+        .accounts({
+          user: writer.publicKey,
+          owner: user.publicKey,
+          entryPda: deriveEntry(key),
+          storageIndex: indexPDA,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([writer])
+        .rpc();
+    }
+
+    // grantWriter, revokeWriter and freeze share the same accounts
+    function manageEntry(methodBuilder: any, key: string) {
+      return methodBuilder
+        .accounts({
+          user: user.publicKey,
+          entryPda: deriveEntry(key),
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+    }
+
+    async function callDelete(key: string) {
+      return await program.methods
+        .delete(key)
+        .accounts({
+          user: user.publicKey,
+          entryPda: deriveEntry(key),
+          storageIndex: indexPDA,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+    }
+
+    it("stores values under different keys", async () => {
+      expect((await executeTransaction(callStore("config", TEST_BYTES.small))).success).to.be.true;
+      expect((await executeTransaction(callStore("a much longer label for the second value", TEST_BYTES.medium))).success).to.be.true;
+
+      const config = await program.account.memoryEntryPda.fetch(deriveEntry("config"));
+      expect(config.key).to.equal("config");
+      expect(Buffer.from(config.value)).to.deep.equal(TEST_BYTES.small);
+
+      const second = await program.account.memoryEntryPda.fetch(deriveEntry("a much longer label for the second value"));
+      expect(Buffer.from(second.value)).to.deep.equal(TEST_BYTES.medium);
+
+      const index = await program.account.storageIndex.fetch(indexPDA);
+      expect(index.keys).to.deep.equal(["config", "a much longer label for the second value"]);
+    });
+
+    it("overwrites an existing key without duplicating it in the index", async () => {
+      await executeTransaction(callStore("config", TEST_BYTES.large));
+      const result = await executeTransaction(callStore("config", TEST_BYTES.small));
+      expect(result.success).to.be.true;
+
+      const config = await program.account.memoryEntryPda.fetch(deriveEntry("config"));
+      expect(Buffer.from(config.value)).to.deep.equal(TEST_BYTES.small);
+
+      const index = await program.account.storageIndex.fetch(indexPDA);
+      expect(index.keys).to.deep.equal(["config"]);
+    });
+
+    it("deletes a key and refunds its rent", async () => {
+      await executeTransaction(callStore("config", TEST_BYTES.large));
+      await executeTransaction(callStore("other", TEST_BYTES.small));
+
+      const balanceBefore = await getAccountBalance(user.publicKey);
+      const result = await executeTransaction(callDelete("config"));
+      expect(result.success).to.be.true;
+
+      expect(await accountExists(deriveEntry("config"))).to.be.false;
+      expect(await getAccountBalance(user.publicKey)).to.be.greaterThan(balanceBefore);
+
+      const index = await program.account.storageIndex.fetch(indexPDA);
+      expect(index.keys).to.deep.equal(["other"]);
+    });
+
+    it("rejects deleting a missing key", async () => {
+      const result = await executeTransaction(callDelete("missing"));
+      expect(result.success).to.be.false;
+    });
+
+    it("rejects keys longer than 64 bytes", async () => {
+      const result = await executeTransaction(callStore("k".repeat(65), TEST_BYTES.small));
+      expect(result.success).to.be.false;
+    });
+
+    describe("access control", () => {
+      let writer: Keypair;
+
+      beforeEach(async () => {
+        writer = Keypair.generate();
+        const signature = await provider.connection.requestAirdrop(
+          writer.publicKey,
+          anchor.web3.LAMPORTS_PER_SOL
+        );
+        await provider.connection.confirmTransaction(signature);
+
+        await callStore("config", TEST_BYTES.small);
+      });
+
+      it("rejects writes from users that are not writers", async () => {
+        const result = await executeTransaction(callStore("config", TEST_BYTES.medium, writer));
+        expect(result.success).to.be.false;
+      });
+
+      it("allows granted writers to update an entry", async () => {
+        await manageEntry(program.methods.grantWriter("config", writer.publicKey), "config");
+
+        const result = await executeTransaction(callStore("config", TEST_BYTES.medium, writer));
+        expect(result.success).to.be.true;
+
+        const entry = await program.account.memoryEntryPda.fetch(deriveEntry("config"));
+        expect(Buffer.from(entry.value)).to.deep.equal(TEST_BYTES.medium);
+        expect(entry.owner.toString()).to.equal(user.publicKey.toString());
+        expect(entry.writers.map((w) => w.toString())).to.deep.equal([writer.publicKey.toString()]);
+      });
+
+      it("does not let writers create new keys", async () => {
+        await manageEntry(program.methods.grantWriter("config", writer.publicKey), "config");
+
+        const result = await executeTransaction(callStore("other", TEST_BYTES.small, writer));
+        expect(result.success).to.be.false;
+      });
+
+      it("rejects writes after the writer is revoked", async () => {
+        await manageEntry(program.methods.grantWriter("config", writer.publicKey), "config");
+        await manageEntry(program.methods.revokeWriter("config", writer.publicKey), "config");
+
+        const result = await executeTransaction(callStore("config", TEST_BYTES.medium, writer));
+        expect(result.success).to.be.false;
+
+        const entry = await program.account.memoryEntryPda.fetch(deriveEntry("config"));
+        expect(entry.writers).to.be.empty;
+      });
+
+      it("only lets the owner grant writers", async () => {
+        const result = await executeTransaction(
+          program.methods
+            .grantWriter("config", writer.publicKey)
+            .accounts({
+              user: writer.publicKey,
+              entryPda: deriveEntry("config"),
+              systemProgram: SystemProgram.programId,
+            })
+            .signers([writer])
+            .rpc()
+        );
+        expect(result.success).to.be.false;
+      });
+
+      it("makes a frozen entry permanently immutable", async () => {
+        await manageEntry(program.methods.grantWriter("config", writer.publicKey), "config");
+        await manageEntry(program.methods.freeze("config"), "config");
+
+        const entry = await program.account.memoryEntryPda.fetch(deriveEntry("config"));
+        expect(entry.readOnly).to.be.true;
+
+        expect((await executeTransaction(callStore("config", TEST_BYTES.medium))).success).to.be.false;
+        expect((await executeTransaction(callStore("config", TEST_BYTES.medium, writer))).success).to.be.false;
+        expect((await executeTransaction(callDelete("config"))).success).to.be.false;
+        expect(
+          (await executeTransaction(manageEntry(program.methods.revokeWriter("config", writer.publicKey), "config")))
+            .success
+        ).to.be.false;
+
+        const frozen = await program.account.memoryEntryPda.fetch(deriveEntry("config"));
+        expect(Buffer.from(frozen.value)).to.deep.equal(TEST_BYTES.small);
+      });
+    });
+  });
+
+  describe("chunked writes", () => {
+    const BUFFER_KEY = "blob";
+    const BUFFER_HEADER_LEN = 8 + 4 + 4 + 32 + 1;
+    const CHUNK_SIZE = 800;
+    const PAYLOAD = Buffer.from(Array(3000).fill(0).map((_, i) => (i * 7) % 256));
+
+    let bufferPDA: PublicKey;
+
+    beforeEach(() => {
+      const keyHash = createHash("sha256").update(BUFFER_KEY).digest();
+      [bufferPDA] = PublicKey.findProgramAddressSync(
+        [Buffer.from("storage_buffer"), user.publicKey.toBuffer(), keyHash],
+        program.programId
+      );
+    });
+
+    function chunksOf(payload: Buffer): Buffer[] {
+      const chunks: Buffer[] = [];
+      for (let offset = 0; offset < payload.length; offset += CHUNK_SIZE) {
+        chunks.push(payload.subarray(offset, offset + CHUNK_SIZE));
+      }
+      return chunks;
+    }
+
+    // h_0 = 0, h_i = sha256(h_{i-1} || chunk_i)
+    function chainedHash(chunks: Buffer[]): number[] {
+      let runningHash = Buffer.alloc(32);
+      for (const chunk of chunks) {
+        runningHash = createHash("sha256").update(Buffer.concat([runningHash, chunk])).digest();
+      }
+      return Array.from(runningHash);
+    }
+
+    async function callInitBuffer(totalLen: number) {
+      return await program.methods
+        .initBuffer(BUFFER_KEY, totalLen)
+        .accounts({
+          user: user.publicKey,
+          bufferPda: bufferPDA,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+    }
+
+    async function callWriteChunk(offset: number, bytes: Buffer) {
+      return await program.methods
+        .writeChunk(BUFFER_KEY, offset, bytes)
+        .accounts({
+          user: user.publicKey,
+          bufferPda: bufferPDA,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+    }
+
+    async function callFinalize(contentHash: number[]) {
+      return await program.methods
+        .finalize(BUFFER_KEY, contentHash)
+        .accounts({
+          user: user.publicKey,
+          bufferPda: bufferPDA,
+        })
+        .signers([user])
+        .rpc();
+    }
+
+    async function writeAll(chunks: Buffer[]) {
+      let offset = 0;
+      for (const chunk of chunks) {
+        await callWriteChunk(offset, chunk);
+        offset += chunk.length;
+      }
+    }
+
+    it("writes a payload larger than a transaction in chunks", async () => {
+      const chunks = chunksOf(PAYLOAD);
+      await callInitBuffer(PAYLOAD.length);
+      await writeAll(chunks);
+
+      const result = await executeTransaction(callFinalize(chainedHash(chunks)));
+      expect(result.success).to.be.true;
+
+      const buffer = await program.account.bufferPda.fetch(bufferPDA);
+      expect(buffer.finalized).to.be.true;
+      expect(buffer.written).to.equal(PAYLOAD.length);
+
+      const accountInfo = await provider.connection.getAccountInfo(bufferPDA);
+      expect(accountInfo.data.subarray(BUFFER_HEADER_LEN)).to.deep.equal(PAYLOAD);
+    });
+
+    it("rejects a content hash that does not match the chunks", async () => {
+      const chunks = chunksOf(PAYLOAD);
+      await callInitBuffer(PAYLOAD.length);
+      await writeAll(chunks);
+
+      const wrongHash = chainedHash(chunksOf(Buffer.alloc(PAYLOAD.length)));
+      const result = await executeTransaction(callFinalize(wrongHash));
+      expect(result.success).to.be.false;
+    });
+
+    it("rejects finalizing an incomplete buffer", async () => {
+      const chunks = chunksOf(PAYLOAD);
+      await callInitBuffer(PAYLOAD.length);
+      await callWriteChunk(0, chunks[0]);
+
+      const result = await executeTransaction(callFinalize(chainedHash([chunks[0]])));
+      expect(result.success).to.be.false;
+    });
+
+    it("rejects out of order chunks", async () => {
+      const chunks = chunksOf(PAYLOAD);
+      await callInitBuffer(PAYLOAD.length);
+
+      const result = await executeTransaction(callWriteChunk(CHUNK_SIZE, chunks[1]));
+      expect(result.success).to.be.false;
+    });
+
+    it("rejects chunks past the announced length", async () => {
+      await callInitBuffer(10);
+
+      const result = await executeTransaction(callWriteChunk(0, TEST_BYTES.medium));
+      expect(result.success).to.be.false;
+    });
+
+    it("rejects writes after finalization", async () => {
+      const chunks = chunksOf(TEST_BYTES.small);
+      await callInitBuffer(TEST_BYTES.small.length);
+      await writeAll(chunks);
+      await callFinalize(chainedHash(chunks));
+
+      const result = await executeTransaction(callWriteChunk(TEST_BYTES.small.length, TEST_BYTES.small));
+      expect(result.success).to.be.false;
+    });
+
+    it("closes a finalized buffer and refunds its rent", async () => {
+      const chunks = chunksOf(PAYLOAD);
+      await callInitBuffer(PAYLOAD.length);
+      await writeAll(chunks);
+      await callFinalize(chainedHash(chunks));
+
+      const balanceBefore = await getAccountBalance(user.publicKey);
+      const result = await executeTransaction(
+        program.methods
+          .closeBuffer(BUFFER_KEY)
+          .accounts({
+            user: user.publicKey,
+            bufferPda: bufferPDA,
+          })
+          .signers([user])
+          .rpc()
+      );
+      expect(result.success).to.be.true;
+
+      expect(await accountExists(bufferPDA)).to.be.false;
+      expect(await getAccountBalance(user.publicKey)).to.be.greaterThan(balanceBefore);
+
+      // The key can be reused for a new buffer
+      expect((await executeTransaction(callInitBuffer(PAYLOAD.length))).success).to.be.true;
+    });
+  });
+
+  describe("zero-copy bytes", () => {
+    // Discriminator, owner and length
+    const LARGE_BYTES_HEADER_LEN = 8 + 32 + 8;
+    // Larger than the 10 KiB a program can allocate itself
+    const LARGE_BYTES_CAPACITY = 64 * 1024;
+
+    let largeBytes: Keypair;
+
+    // The account is allocated by the client, then initialized by the program in the same transaction
+    async function createLargeBytes(account: Keypair, capacity: number) {
+      const space = LARGE_BYTES_HEADER_LEN + capacity;
+      return await program.methods
+        .initializeLargeBytes()
+        .accounts({
+          user: user.publicKey,
+          largeBytes: account.publicKey,
+        })
+        .preInstructions([
+          SystemProgram.createAccount({
+            fromPubkey: user.publicKey,
+            newAccountPubkey: account.publicKey,
+            space,
+            lamports: await provider.connection.getMinimumBalanceForRentExemption(space),
+            programId: program.programId,
+          }),
+        ])
+        .signers([user, account])
+        .rpc();
+    }
+
+    async function callStoreLargeBytes(offset: number, bytes: Buffer, account: Keypair = largeBytes, signer: Keypair = user) {
+      return await program.methods
+        .storeLargeBytes(offset, bytes)
+        .accounts({
+          user: signer.publicKey,
+          largeBytes: account.publicKey,
+        })
+        .signers([signer])
+        .rpc();
+    }
+
+    async function fetchContents(account: Keypair = largeBytes): Promise<Buffer> {
+      const accountInfo = await provider.connection.getAccountInfo(account.publicKey);
+      return accountInfo!.data.subarray(LARGE_BYTES_HEADER_LEN);
+    }
+
+    beforeEach(async () => {
+      largeBytes = Keypair.generate();
+      await createLargeBytes(largeBytes, LARGE_BYTES_CAPACITY);
+    });
+
+    it("stores bytes in place", async () => {
+      await callStoreLargeBytes(0, TEST_BYTES.large);
+      await callStoreLargeBytes(TEST_BYTES.large.length, TEST_BYTES.medium);
+
+      const account = await program.account.largeBytesAccount.fetch(largeBytes.publicKey);
+      const len = account.len.toNumber();
+      expect(account.owner.toString()).to.equal(user.publicKey.toString());
+      expect(len).to.equal(TEST_BYTES.large.length + TEST_BYTES.medium.length);
+      expect((await fetchContents()).subarray(0, len)).to.deep.equal(Buffer.concat([TEST_BYTES.large, TEST_BYTES.medium]));
+    });
+
+    it("stores bytes past the first 10 KiB", async () => {
+      const chunk = Buffer.alloc(900, 7);
+      let offset = 0;
+      while (offset < 11 * 1024) {
+        await callStoreLargeBytes(offset, chunk);
+        offset += chunk.length;
+      }
+
+      const account = await program.account.largeBytesAccount.fetch(largeBytes.publicKey);
+      expect(account.len.toNumber()).to.equal(offset);
+      expect((await fetchContents()).subarray(0, offset).every((b) => b === 7)).to.be.true;
+    });
+
+    it("zeroes the bytes freed by a shorter write", async () => {
+      await callStoreLargeBytes(0, TEST_BYTES.large);
+      await callStoreLargeBytes(0, TEST_BYTES.small);
+
+      const account = await program.account.largeBytesAccount.fetch(largeBytes.publicKey);
+      expect(account.len.toNumber()).to.equal(TEST_BYTES.small.length);
+      expect((await fetchContents()).subarray(TEST_BYTES.small.length, TEST_BYTES.large.length).every((b) => b === 0)).to.be.true;
+    });
+
+    it("rejects writes past the capacity", async () => {
+      const smallBytes = Keypair.generate();
+      await createLargeBytes(smallBytes, 16);
+
+      const result = await executeTransaction(callStoreLargeBytes(0, Buffer.alloc(14), smallBytes));
+      expect(result.success).to.be.true;
+
+      const overflow = await executeTransaction(callStoreLargeBytes(14, TEST_BYTES.small, smallBytes));
+      expect(overflow.success).to.be.false;
+    });
+
+    it("rejects writes leaving a gap", async () => {
+      const result = await executeTransaction(callStoreLargeBytes(10, TEST_BYTES.small));
+      expect(result.success).to.be.false;
+    });
+
+    it("rejects writes by another user", async () => {
+      const other = Keypair.generate();
+      const signature = await provider.connection.requestAirdrop(other.publicKey, anchor.web3.LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(signature);
+
+      const result = await executeTransaction(callStoreLargeBytes(0, TEST_BYTES.small, largeBytes, other));
+      expect(result.success).to.be.false;
+    });
+  });
 });
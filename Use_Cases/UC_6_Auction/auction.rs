@@ -2,36 +2,79 @@ use anchor_lang::prelude::*;
 
 declare_id!("ArpHisvZS1EECpwisEM32EXMz2hoqmdhaQwY2TxWLiD7");
 
+const MAX_OBJECT_LEN: usize = 100;
+
 #[program]
 pub mod auction {
     use super::*;
 
+    #[allow(clippy::too_many_arguments)]
     pub fn start(
         ctx: Context<StartCtx>,
+        auction_id: u64,
         auctioned_object: String,
         duration_slots: u64,
         starting_bid: u64,
+        reserve_price: u64,
+        min_increment: BidIncrement,
+        anti_sniping_window: u64,
+        extension_slots: u64,
+        mode: AuctionMode,
     ) -> Result<()> {
+        msg!("Auction id: {}", auction_id);
+        require!(
+            auctioned_object.len() <= MAX_OBJECT_LEN,
+            CustomError::ObjectTooLong
+        );
+        require!(
+            anti_sniping_window == 0 || extension_slots > 0,
+            CustomError::InvalidExtension
+        );
+        // In a Dutch auction the starting bid is the start price, decreasing down to the floor
+        if let AuctionMode::Dutch {
+            floor_price,
+            decay_per_slot,
+        } = mode
+        {
+            require!(
+                floor_price <= starting_bid && decay_per_slot > 0,
+                CustomError::InvalidDutchPrice
+            );
+        }
+
+        let current_slot = Clock::get()?.slot;
+
         let auction_info = &mut ctx.accounts.auction_info;
         auction_info.seller = *ctx.accounts.seller.key;
         auction_info.highest_bidder = *ctx.accounts.seller.key; // The seller is the first bidder at the beginning
-        auction_info.end_time = Clock::get()?.slot + duration_slots;
+        auction_info.start_time = current_slot;
+        auction_info.end_time = current_slot + duration_slots;
         auction_info.highest_bid = starting_bid;
+        auction_info.second_bid = starting_bid;
+        auction_info.bid_count = 0;
+        auction_info.reserve_price = reserve_price;
+        auction_info.min_increment = min_increment;
+        auction_info.anti_sniping_window = anti_sniping_window;
+        auction_info.extension_slots = extension_slots;
+        auction_info.mode = mode;
         auction_info.object = auctioned_object;
         emit!(Start {});
         Ok(())
     }
 
-    pub fn bid(
-        ctx: Context<BidCtx>,
-        auctioned_object: String,
-        amount_to_deposit: u64,
-    ) -> Result<()> {
+    pub fn bid(ctx: Context<BidCtx>, auction_id: u64, amount_to_deposit: u64) -> Result<()> {
+        msg!("Auction id: {}", auction_id);
         let auction_info = &mut ctx.accounts.auction_info;
         let bidder = &ctx.accounts.bidder;
-        let current_highest_bidder = &ctx.accounts.current_highest_bidder;
+        let pending_returns = &mut ctx.accounts.pending_returns;
 
-        if Clock::get()?.slot > auction_info.end_time {
+        require!(
+            auction_info.mode == AuctionMode::English,
+            CustomError::InvalidAuctionMode
+        );
+
+        let current_slot = Clock::get()?.slot;
+        if current_slot > auction_info.end_time {
             return err!(CustomError::AuctionEnded);
         }
 
@@ -39,28 +82,46 @@ pub mod auction {
             return err!(CustomError::InvalidBidAmount);
         }
 
+        if amount_to_deposit < min_next_bid(auction_info) {
+            return err!(CustomError::BidIncrementNotMet);
+        }
+
+        // A deposit left from a previous auction with the same id must be withdrawn first
+        if pending_returns.amount == 0 {
+            pending_returns.auction_start = auction_info.start_time;
+        }
+        require!(
+            pending_returns.auction_start == auction_info.start_time,
+            CustomError::StaleDeposit
+        );
+
+        // The deposit is kept in the bidder's pending-returns PDA: the previous highest bid
+        // becomes withdrawable by its bidder through withdraw_refund as soon as it is outbid
         let transfer_instruction = anchor_lang::solana_program::system_instruction::transfer(
             &bidder.key(),
-            &auction_info.key(),
+            &pending_returns.key(),
             amount_to_deposit,
         );
 
         anchor_lang::solana_program::program::invoke(
             &transfer_instruction,
-            &[bidder.to_account_info(), auction_info.to_account_info()],
+            &[bidder.to_account_info(), pending_returns.to_account_info()],
         )
         .unwrap();
 
-        // Return founds to the previous bidder if it's not the first attempt (the first bidder is the seller)
-        if auction_info.highest_bidder != auction_info.seller {
-            **current_highest_bidder
-                .to_account_info()
-                .try_borrow_mut_lamports()? += auction_info.highest_bid;
-            **auction_info.to_account_info().try_borrow_mut_lamports()? -= auction_info.highest_bid;
-        }
+        pending_returns.amount += amount_to_deposit;
 
         auction_info.highest_bid = amount_to_deposit;
         auction_info.highest_bidder = *bidder.key;
+        auction_info.bid_count += 1;
+
+        // Anti-sniping: a bid close to the end gives the other bidders time to answer
+        if auction_info.end_time - current_slot < auction_info.anti_sniping_window {
+            auction_info.end_time += auction_info.extension_slots;
+            emit!(Extend {
+                end_time: auction_info.end_time
+            });
+        }
 
         emit!(Bid {
             sender: *bidder.key,
@@ -70,50 +131,398 @@ pub mod auction {
         Ok(())
     }
 
-    pub fn end(ctx: Context<EndCtx>, auctioned_object: String) -> Result<()> {
-        msg!("Auction name: {}", auctioned_object);
+    pub fn buy(ctx: Context<BuyCtx>, auction_id: u64, max_price: u64) -> Result<()> {
+        msg!("Auction id: {}", auction_id);
         let auction_info = &mut ctx.accounts.auction_info;
+        let buyer = &ctx.accounts.buyer;
+
+        let current_slot = Clock::get()?.slot;
+        if current_slot > auction_info.end_time {
+            return err!(CustomError::AuctionEnded);
+        }
+
+        let price = dutch_price(auction_info, current_slot)?;
+        require!(max_price >= price, CustomError::InvalidBidAmount);
+
+        // The first buyer wins immediately, the price goes straight to the seller
+        let transfer_instruction = anchor_lang::solana_program::system_instruction::transfer(
+            &buyer.key(),
+            &ctx.accounts.seller.key(),
+            price,
+        );
+
+        anchor_lang::solana_program::program::invoke(
+            &transfer_instruction,
+            &[
+                buyer.to_account_info(),
+                ctx.accounts.seller.to_account_info(),
+            ],
+        )
+        .unwrap();
+
+        emit!(End {
+            winner: *buyer.key,
+            amount: price
+        });
+
+        // The auction_info account is closed by Anchor, returning the rent to the seller
+        Ok(())
+    }
+
+    pub fn commit_bid(
+        ctx: Context<CommitBidCtx>,
+        auction_id: u64,
+        hashed_bid: [u8; 32],
+        deposit: u64,
+    ) -> Result<()> {
+        msg!("Auction id: {}", auction_id);
+        let auction_info = &mut ctx.accounts.auction_info;
+        let bidder = &ctx.accounts.bidder;
+        let sealed_bid = &mut ctx.accounts.sealed_bid;
+
+        let penalty = match auction_info.mode {
+            AuctionMode::Sealed { penalty, .. } => penalty,
+            _ => return err!(CustomError::InvalidAuctionMode),
+        };
+        if Clock::get()?.slot > auction_info.end_time {
+            return err!(CustomError::AuctionEnded);
+        }
+        require!(deposit > 0, CustomError::InvalidBidAmount);
+
+        // The deposit can be higher than the bid to hide its amount
+        let transfer_instruction = anchor_lang::solana_program::system_instruction::transfer(
+            &bidder.key(),
+            &sealed_bid.key(),
+            deposit,
+        );
+
+        anchor_lang::solana_program::program::invoke(
+            &transfer_instruction,
+            &[bidder.to_account_info(), sealed_bid.to_account_info()],
+        )
+        .unwrap();
+
+        // The seller and the penalty are copied because the auction is closed before the withdrawal
+        sealed_bid.seller = auction_info.seller;
+        sealed_bid.auction_start = auction_info.start_time;
+        sealed_bid.hashed_bid = hashed_bid;
+        sealed_bid.deposit = deposit;
+        sealed_bid.penalty = penalty;
+        sealed_bid.revealed = false;
+        auction_info.bid_count += 1;
+
+        Ok(())
+    }
+
+    pub fn reveal_bid(
+        ctx: Context<RevealBidCtx>,
+        auction_id: u64,
+        amount: u64,
+        salt: [u8; 32],
+    ) -> Result<()> {
+        msg!("Auction id: {}", auction_id);
+        let auction_info = &mut ctx.accounts.auction_info;
+        let bidder = &ctx.accounts.bidder;
+        let sealed_bid = &mut ctx.accounts.sealed_bid;
+
+        let reveal_slots = match auction_info.mode {
+            AuctionMode::Sealed { reveal_slots, .. } => reveal_slots,
+            _ => return err!(CustomError::InvalidAuctionMode),
+        };
+        let current_slot = Clock::get()?.slot;
+        if current_slot <= auction_info.end_time {
+            return err!(CustomError::RevealNotStarted);
+        }
+        if current_slot > auction_info.end_time + reveal_slots {
+            return err!(CustomError::AuctionEnded);
+        }
+        require!(
+            sealed_bid.auction_start == auction_info.start_time,
+            CustomError::StaleDeposit
+        );
+        require!(!sealed_bid.revealed, CustomError::AlreadyRevealed);
+
+        let hash =
+            anchor_lang::solana_program::keccak::hashv(&[&amount.to_le_bytes(), &salt]).to_bytes();
+        require!(hash == sealed_bid.hashed_bid, CustomError::InvalidReveal);
+        require!(amount <= sealed_bid.deposit, CustomError::InvalidReveal);
+
+        sealed_bid.revealed = true;
+        if amount > auction_info.highest_bid {
+            if auction_info.highest_bidder != auction_info.seller {
+                auction_info.second_bid = auction_info.highest_bid;
+            }
+            auction_info.highest_bid = amount;
+            auction_info.highest_bidder = *bidder.key;
+        } else if amount > auction_info.second_bid {
+            auction_info.second_bid = amount;
+        }
+
+        emit!(Bid {
+            sender: *bidder.key,
+            amount
+        });
+
+        Ok(())
+    }
+
+    pub fn withdraw_sealed_bid(ctx: Context<WithdrawSealedBidCtx>, auction_id: u64) -> Result<()> {
+        msg!("Auction id: {}", auction_id);
+        let bidder = &ctx.accounts.bidder;
         let seller = &ctx.accounts.seller;
+        let sealed_bid = &ctx.accounts.sealed_bid;
+
+        // The auction account is closed by end, after the reveal phase; the seller may
+        // have started a new auction with the same id since then
+        require!(
+            running_auction(&ctx.accounts.auction_info, sealed_bid.auction_start)?.is_none(),
+            CustomError::AuctionNotEnded
+        );
+
+        // Non-revealers lose the penalty, paid to the seller. The price of the winner
+        // was already taken from its deposit by end
+        let penalty = if sealed_bid.revealed {
+            0
+        } else {
+            sealed_bid.penalty.min(sealed_bid.deposit)
+        };
+        **seller.to_account_info().try_borrow_mut_lamports()? += penalty;
+        **sealed_bid.to_account_info().try_borrow_mut_lamports()? -= penalty;
+
+        emit!(Withdraw {
+            sender: *bidder.key,
+            amount: sealed_bid.deposit - penalty
+        });
+
+        // The sealed_bid account is closed by Anchor, returning the deposit and the rent to the bidder
+        Ok(())
+    }
+
+    pub fn withdraw_refund(ctx: Context<WithdrawRefundCtx>, auction_id: u64) -> Result<()> {
+        msg!("Auction id: {}", auction_id);
+        let bidder = &ctx.accounts.bidder;
+        let pending_returns = &mut ctx.accounts.pending_returns;
+
+        // The current highest bid is locked until the end of the auction, when the
+        // auction account is closed
+        let locked =
+            match running_auction(&ctx.accounts.auction_info, pending_returns.auction_start)? {
+                Some(info) if info.highest_bidder == *bidder.key => info.highest_bid,
+                _ => 0,
+            };
+        let amount = pending_returns.amount - locked;
+        // Once the auction is closed the winner can still get back the rent
+        require!(amount > 0 || locked == 0, CustomError::NothingToWithdraw);
+
+        if locked == 0 {
+            // Close the pending_returns account and return the rent to the bidder
+            **bidder.to_account_info().try_borrow_mut_lamports()? += **pending_returns
+                .to_account_info()
+                .try_borrow_mut_lamports()?;
+            **pending_returns
+                .to_account_info()
+                .try_borrow_mut_lamports()? = 0;
+        } else {
+            **bidder.to_account_info().try_borrow_mut_lamports()? += amount;
+            **pending_returns
+                .to_account_info()
+                .try_borrow_mut_lamports()? -= amount;
+        }
+        pending_returns.amount -= amount;
 
-        if Clock::get()?.slot <= auction_info.end_time {
+        emit!(Withdraw {
+            sender: *bidder.key,
+            amount
+        });
+
+        Ok(())
+    }
+
+    pub fn cancel(ctx: Context<CancelCtx>, auction_id: u64) -> Result<()> {
+        msg!("Auction id: {}", auction_id);
+        require!(
+            ctx.accounts.auction_info.bid_count == 0,
+            CustomError::AuctionHasBids
+        );
+
+        emit!(Cancel {});
+
+        // The auction_info account is closed by Anchor, returning the rent to the seller
+        Ok(())
+    }
+
+    pub fn end(ctx: Context<EndCtx>, auction_id: u64) -> Result<()> {
+        msg!("Auction id: {}", auction_id);
+        let auction_info = &ctx.accounts.auction_info;
+        let seller = &ctx.accounts.seller;
+
+        // Sealed-bid auctions are settled after the reveal phase
+        let closing_slot = match auction_info.mode {
+            AuctionMode::Sealed { reveal_slots, .. } => auction_info.end_time + reveal_slots,
+            _ => auction_info.end_time,
+        };
+        if Clock::get()?.slot <= closing_slot {
             return err!(CustomError::AuctionNotEnded);
         }
 
+        if auction_info.highest_bidder != auction_info.seller
+            && auction_info.highest_bid < auction_info.reserve_price
+        {
+            // Reserve not met: the object goes back to the seller and, once the auction
+            // is closed, the top bidder can withdraw its bid
+            emit!(End {
+                winner: auction_info.seller,
+                amount: 0
+            });
+            return Ok(());
+        }
+
         // send the auction_info.object to the highest bidder
 
-        **seller.to_account_info().try_borrow_mut_lamports()? +=
-            **auction_info.to_account_info().try_borrow_mut_lamports()?;
-        **auction_info.to_account_info().try_borrow_mut_lamports()? = 0;
+        // Only the winning price goes to the seller, taken from the winner's deposit;
+        // the other deposits stay in their PDAs until their bidders withdraw them
+        let price = winning_price(auction_info);
+        if auction_info.highest_bidder != auction_info.seller {
+            if let Some(winner_returns) = &mut ctx.accounts.winner_pending_returns {
+                winner_returns.amount -= price;
+                **winner_returns.to_account_info().try_borrow_mut_lamports()? -= price;
+            } else if let Some(winner_sealed_bid) = &mut ctx.accounts.winner_sealed_bid {
+                winner_sealed_bid.deposit -= price;
+                **winner_sealed_bid
+                    .to_account_info()
+                    .try_borrow_mut_lamports()? -= price;
+            } else {
+                return err!(CustomError::MissingWinnerDeposit);
+            }
+            **seller.to_account_info().try_borrow_mut_lamports()? += price;
+        }
 
         emit!(End {
             winner: auction_info.highest_bidder,
-            amount: auction_info.highest_bid
+            amount: price
         });
 
+        // The auction_info account is closed by Anchor, returning the rent to the seller
         Ok(())
     }
 }
 
+// Returns the auction the deposits were made in, unless it was closed by end. The seller
+// can start a new auction with the same id, recognized by its different start slot
+fn running_auction(auction_info: &AccountInfo, auction_start: u64) -> Result<Option<AuctionInfo>> {
+    if auction_info.data_is_empty() {
+        return Ok(None);
+    }
+    let data = auction_info.try_borrow_data()?;
+    let info = AuctionInfo::try_deserialize(&mut &data[..])?;
+    if info.start_time != auction_start {
+        return Ok(None);
+    }
+    Ok(Some(info))
+}
+
+fn min_next_bid(auction_info: &AuctionInfo) -> u64 {
+    let increment = match auction_info.min_increment {
+        BidIncrement::Absolute(amount) => amount,
+        BidIncrement::BasisPoints(bps) => {
+            (auction_info.highest_bid as u128 * bps as u128 / 10_000) as u64
+        }
+    };
+    auction_info.highest_bid + increment
+}
+
+fn winning_price(auction_info: &AuctionInfo) -> u64 {
+    match auction_info.mode {
+        // Vickrey auction: the winner pays the second highest bid, at least the reserve price
+        AuctionMode::Sealed {
+            second_price: true, ..
+        } => auction_info.second_bid.max(auction_info.reserve_price),
+        _ => auction_info.highest_bid,
+    }
+}
+
+fn dutch_price(auction_info: &AuctionInfo, current_slot: u64) -> Result<u64> {
+    match auction_info.mode {
+        AuctionMode::Dutch {
+            floor_price,
+            decay_per_slot,
+        } => {
+            let decay = (current_slot - auction_info.start_time).saturating_mul(decay_per_slot);
+            Ok(auction_info
+                .highest_bid
+                .saturating_sub(decay)
+                .max(floor_price))
+        }
+        _ => err!(CustomError::InvalidAuctionMode),
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Clone, InitSpace)]
+pub enum AuctionMode {
+    English,
+    Dutch {
+        floor_price: u64,    // lamports
+        decay_per_slot: u64, // lamports
+    },
+    Sealed {
+        reveal_slots: u64, // length of the reveal phase after the end time
+        second_price: bool,
+        penalty: u64, // lamports kept from the deposit of non-revealers
+    },
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Clone, InitSpace)]
+pub enum BidIncrement {
+    Absolute(u64),    // lamports
+    BasisPoints(u16), // of the current highest bid
+}
+
 #[account]
 #[derive(InitSpace)]
 pub struct AuctionInfo {
-    pub seller: Pubkey,         // 32 bytes
-    pub highest_bidder: Pubkey, // 32 bytes
-    pub end_time: u64,          // 8 bytes
-    pub highest_bid: u64,       // 8 bytes
-    #[max_len(30)]
+    pub seller: Pubkey,              // 32 bytes
+    pub highest_bidder: Pubkey,      // 32 bytes
+    pub start_time: u64,             // 8 bytes
+    pub end_time: u64,               // 8 bytes
+    pub highest_bid: u64,            // 8 bytes
+    pub second_bid: u64,             // 8 bytes
+    pub bid_count: u64,              // 8 bytes
+    pub reserve_price: u64,          // 8 bytes
+    pub min_increment: BidIncrement, // 1 + 8 bytes
+    pub anti_sniping_window: u64,    // 8 bytes
+    pub extension_slots: u64,        // 8 bytes
+    pub mode: AuctionMode,           // 1 + 17 bytes
+    #[max_len(MAX_OBJECT_LEN)]
     pub object: String,
 }
 
+#[account]
+#[derive(InitSpace)]
+pub struct PendingReturnsPDA {
+    pub auction_start: u64, // 8 bytes, start slot of the auction the deposit belongs to
+    pub amount: u64,        // 8 bytes, deposited by the bidder and not yet withdrawn
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct SealedBidPDA {
+    pub seller: Pubkey,       // 32 bytes
+    pub auction_start: u64,   // 8 bytes, start slot of the auction the bid belongs to
+    pub hashed_bid: [u8; 32], // 32 bytes, keccak(amount || salt)
+    pub deposit: u64,         // 8 bytes
+    pub penalty: u64,         // 8 bytes
+    pub revealed: bool,       // 1 byte
+}
+
 #[derive(Accounts)]
-#[instruction(auctioned_object: String)]
+#[instruction(auction_id: u64)]
 pub struct StartCtx<'info> {
     #[account(mut)]
     pub seller: Signer<'info>,
     #[account(
         init, 
         payer = seller, 
-        seeds = [auctioned_object.as_ref()],
+        seeds = [b"auction", seller.key().as_ref(), auction_id.to_le_bytes().as_ref()],
         bump,
         space = 8 + AuctionInfo::INIT_SPACE
     )]
@@ -122,36 +531,182 @@ pub struct StartCtx<'info> {
 }
 
 #[derive(Accounts)]
-#[instruction(auctioned_object: String)]
+#[instruction(auction_id: u64)]
 pub struct BidCtx<'info> {
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = bidder.key() != auction_info.seller @ CustomError::SellerCannotBid
+    )]
     pub bidder: Signer<'info>,
     #[account(
         mut,
-        seeds = [auctioned_object.as_ref()],
+        seeds = [b"auction", auction_info.seller.as_ref(), auction_id.to_le_bytes().as_ref()],
         bump,
-        constraint = auction_info.highest_bidder == *current_highest_bidder.key
     )]
     pub auction_info: Account<'info, AuctionInfo>,
+    #[account(
+        init_if_needed,
+        payer = bidder,
+        seeds = [b"pending_returns", auction_info.key().as_ref(), bidder.key().as_ref()],
+        bump,
+        space = 8 + PendingReturnsPDA::INIT_SPACE
+    )]
+    pub pending_returns: Account<'info, PendingReturnsPDA>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(auction_id: u64)]
+pub struct BuyCtx<'info> {
+    #[account(
+        mut,
+        constraint = buyer.key() != auction_info.seller @ CustomError::SellerCannotBid
+    )]
+    pub buyer: Signer<'info>,
     #[account(mut)]
-    pub current_highest_bidder: SystemAccount<'info>,
+    pub seller: SystemAccount<'info>,
+    #[account(
+        mut,
+        close = seller,
+        seeds = [b"auction", seller.key().as_ref(), auction_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = auction_info.seller == *seller.key @ CustomError::InvalidSeller
+    )]
+    pub auction_info: Account<'info, AuctionInfo>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(auctioned_object: String)]
-pub struct EndCtx<'info> {
+#[instruction(auction_id: u64)]
+pub struct CommitBidCtx<'info> {
+    #[account(
+        mut,
+        constraint = bidder.key() != auction_info.seller @ CustomError::SellerCannotBid
+    )]
+    pub bidder: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"auction", auction_info.seller.as_ref(), auction_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub auction_info: Account<'info, AuctionInfo>,
+    #[account(
+        init, 
+        payer = bidder,
+        seeds = [b"sealed_bid", auction_info.key().as_ref(), bidder.key().as_ref()],
+        bump,
+        space = 8 + SealedBidPDA::INIT_SPACE
+    )]
+    pub sealed_bid: Account<'info, SealedBidPDA>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(auction_id: u64)]
+pub struct RevealBidCtx<'info> {
+    pub bidder: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"auction", auction_info.seller.as_ref(), auction_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub auction_info: Account<'info, AuctionInfo>,
+    #[account(
+        mut,
+        seeds = [b"sealed_bid", auction_info.key().as_ref(), bidder.key().as_ref()],
+        bump,
+    )]
+    pub sealed_bid: Account<'info, SealedBidPDA>,
+}
+
+#[derive(Accounts)]
+#[instruction(auction_id: u64)]
+pub struct WithdrawSealedBidCtx<'info> {
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+    #[account(mut)]
+    pub seller: SystemAccount<'info>,
+    /// CHECK: the auction account, only checked to be closed
+    #[account(
+        seeds = [b"auction", seller.key().as_ref(), auction_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub auction_info: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = bidder,
+        seeds = [b"sealed_bid", auction_info.key().as_ref(), bidder.key().as_ref()],
+        bump,
+        constraint = sealed_bid.seller == *seller.key @ CustomError::InvalidSeller
+    )]
+    pub sealed_bid: Account<'info, SealedBidPDA>,
+}
+
+#[derive(Accounts)]
+#[instruction(auction_id: u64)]
+pub struct WithdrawRefundCtx<'info> {
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+    pub seller: SystemAccount<'info>,
+    /// CHECK: the auction account, deserialized in the instruction unless already closed by end
+    #[account(
+        seeds = [b"auction", seller.key().as_ref(), auction_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub auction_info: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"pending_returns", auction_info.key().as_ref(), bidder.key().as_ref()],
+        bump,
+    )]
+    pub pending_returns: Account<'info, PendingReturnsPDA>,
+}
+
+#[derive(Accounts)]
+#[instruction(auction_id: u64)]
+pub struct CancelCtx<'info> {
     #[account(mut)]
     pub seller: Signer<'info>,
     #[account(
         mut,
-        seeds = [auctioned_object.as_ref()],
+        close = seller,
+        seeds = [b"auction", seller.key().as_ref(), auction_id.to_le_bytes().as_ref()],
         bump,
         constraint = auction_info.seller == *seller.key @ CustomError::InvalidSeller
     )]
     pub auction_info: Account<'info, AuctionInfo>,
 }
 
+#[derive(Accounts)]
+#[instruction(auction_id: u64)]
+pub struct EndCtx<'info> {
+    // Anyone can end an expired auction, so the seller cannot keep the deposits locked
+    pub caller: Signer<'info>,
+    #[account(mut)]
+    pub seller: SystemAccount<'info>,
+    #[account(
+        mut,
+        close = seller,
+        seeds = [b"auction", seller.key().as_ref(), auction_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = auction_info.seller == *seller.key @ CustomError::InvalidSeller
+    )]
+    pub auction_info: Account<'info, AuctionInfo>,
+    // The deposit of the winner, English or sealed-bid auction respectively
+    #[account(
+        mut,
+        seeds = [b"pending_returns", auction_info.key().as_ref(), auction_info.highest_bidder.as_ref()],
+        bump,
+    )]
+    pub winner_pending_returns: Option<Account<'info, PendingReturnsPDA>>,
+    #[account(
+        mut,
+        seeds = [b"sealed_bid", auction_info.key().as_ref(), auction_info.highest_bidder.as_ref()],
+        bump,
+    )]
+    pub winner_sealed_bid: Option<Account<'info, SealedBidPDA>>,
+}
+
 #[error_code]
 pub enum CustomError {
     #[msg("The auction is not ended")]
@@ -163,8 +718,47 @@ pub enum CustomError {
     #[msg("Invalid bid amount, should be higher than the previous bid")]
     InvalidBidAmount,
 
+    #[msg("Invalid bid amount, should be at least the previous bid plus the minimum increment")]
+    BidIncrementNotMet,
+
+    #[msg("Invalid extension, must be greater than 0 when the anti-sniping window is set")]
+    InvalidExtension,
+
+    #[msg("Invalid Dutch auction, the floor price must not exceed the start price and the decay must be greater than 0")]
+    InvalidDutchPrice,
+
+    #[msg("The instruction is not supported by the auction mode")]
+    InvalidAuctionMode,
+
+    #[msg("The reveal phase is not started")]
+    RevealNotStarted,
+
+    #[msg("The bid was already revealed")]
+    AlreadyRevealed,
+
+    #[msg("Invalid reveal, the amount and salt do not match the commitment")]
+    InvalidReveal,
+
     #[msg("Invalid seller for the auction provided")]
     InvalidSeller,
+
+    #[msg("The seller cannot bid on its own auction")]
+    SellerCannotBid,
+
+    #[msg("Nothing to withdraw, the highest bid is locked until the end of the auction")]
+    NothingToWithdraw,
+
+    #[msg("The auctioned object name is too long")]
+    ObjectTooLong,
+
+    #[msg("The auction has bids and cannot be cancelled")]
+    AuctionHasBids,
+
+    #[msg("The deposit of the winner must be provided")]
+    MissingWinnerDeposit,
+
+    #[msg("The deposit belongs to a previous auction with the same id")]
+    StaleDeposit,
 }
 
 #[event]
@@ -176,6 +770,20 @@ pub struct Bid {
     amount: u64,
 }
 
+#[event]
+pub struct Extend {
+    end_time: u64,
+}
+
+#[event]
+pub struct Withdraw {
+    sender: Pubkey,
+    amount: u64,
+}
+
+#[event]
+pub struct Cancel {}
+
 #[event]
 pub struct End {
     winner: Pubkey,
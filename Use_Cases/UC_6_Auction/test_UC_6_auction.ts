@@ -2,14 +2,31 @@ import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { PublicKey, Keypair, LAMPORTS_PER_SOL, SystemProgram } from "@solana/web3.js";
 import { expect } from "chai";
+import { keccak_256 } from "@noble/hashes/sha3";
 import BN from "bn.js";
 
 // Generic interface for auction programs - adapt to your specific program type
 interface AuctionProgram extends Program {
   methods: {
-    start(object: string, duration: BN, startingBid: BN): any;
-    bid(object: string, amount: BN): any;
-    end(object: string): any;
+    start(
+      auctionId: BN,
+      object: string,
+      duration: BN,
+      startingBid: BN,
+      reservePrice: BN,
+      minIncrement: any,
+      antiSnipingWindow: BN,
+      extensionSlots: BN,
+      mode: any
+    ): any;
+    bid(auctionId: BN, amount: BN): any;
+    buy(auctionId: BN, maxPrice: BN): any;
+    commitBid(auctionId: BN, hashedBid: number[], deposit: BN): any;
+    revealBid(auctionId: BN, amount: BN, salt: number[]): any;
+    withdrawSealedBid(auctionId: BN): any;
+    withdrawRefund(auctionId: BN): any;
+    cancel(auctionId: BN): any;
+    end(auctionId: BN): any;
   };
 }
 
@@ -33,6 +50,9 @@ describe("Universal Auction Program Test Suite", () => {
   const startingBid = new BN(1 * LAMPORTS_PER_SOL);
   const higherBid = new BN(2 * LAMPORTS_PER_SOL);
   const evenHigherBid = new BN(3 * LAMPORTS_PER_SOL);
+  const auctionedObject = "painting";
+  // English auction with no reserve price, no minimum increment and no anti-sniping window
+  const noExtras = [new BN(0), { absolute: [new BN(0)] }, new BN(0), new BN(0), { english: {} }] as const;
 
   // Utility functions
   const getBalance = async (pubkey: PublicKey): Promise<number> => {
@@ -43,18 +63,23 @@ describe("Universal Auction Program Test Suite", () => {
     return new Promise(resolve => setTimeout(resolve, ms));
   };
 
-  const derivePDA = (object: string): [PublicKey, number] => {
-    // Ensure seed is within 32 byte limit
-    const seed = Buffer.from(object);
-    if (seed.length > 32) {
-      throw new Error(`Seed too long: ${seed.length} bytes. Max 32 bytes allowed.`);
-    }
-    return PublicKey.findProgramAddressSync([seed], program.programId);
+  const deriveAuction = (seller: PublicKey, auctionId: BN): [PublicKey, number] => {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("auction"), seller.toBuffer(), auctionId.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+  };
+
+  const derivePendingReturns = (auction: PublicKey, bidder: PublicKey): PublicKey => {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("pending_returns"), auction.toBuffer(), bidder.toBuffer()],
+      program.programId
+    )[0];
   };
 
-  const getUniqueAuctionName = (): string => {
+  const getUniqueAuctionId = (): BN => {
     testCounter++;
-    return `test${testCounter}`;
+    return new BN(testCounter);
   };
 
   const expectTransactionToFail = async (txPromise: Promise<any>): Promise<boolean> => {
@@ -82,18 +107,18 @@ describe("Universal Auction Program Test Suite", () => {
     // Wait for airdrops to confirm
     await sleep(1000);
 
-    // Derive PDA for auction with unique short name
-    [auctionInfo] = derivePDA(getUniqueAuctionName());
+    // Derive PDA for auction with unique id
+    [auctionInfo] = deriveAuction(seller.publicKey, getUniqueAuctionId());
   });
 
   describe("start()", () => {
     it("creates auction with valid parameters", async () => {
-      const auctionObject = getUniqueAuctionName();
-      const [testAuctionInfo] = derivePDA(auctionObject);
+      const auctionId = getUniqueAuctionId();
+      const [testAuctionInfo] = deriveAuction(seller.publicKey, auctionId);
       const sellerBalanceBefore = await getBalance(seller.publicKey);
       
       await program.methods
-        .start(auctionObject, auctionDuration, startingBid)
+        .start(auctionId, auctionedObject, auctionDuration, startingBid, ...noExtras)
         .accounts({
           seller: seller.publicKey,
           auctionInfo: testAuctionInfo,
@@ -106,7 +131,7 @@ describe("Universal Auction Program Test Suite", () => {
       const auctionAccount = await program.account.auctionInfo.fetch(testAuctionInfo);
       expect(auctionAccount.seller.toString()).to.equal(seller.publicKey.toString());
       expect(auctionAccount.highestBid.toString()).to.equal(startingBid.toString());
-      expect(auctionAccount.object).to.equal(auctionObject);
+      expect(auctionAccount.object).to.equal(auctionedObject);
       
       // Verify seller paid for account creation
       const sellerBalanceAfter = await getBalance(seller.publicKey);
@@ -114,12 +139,12 @@ describe("Universal Auction Program Test Suite", () => {
     });
 
     it("sets correct end time and starting bid", async () => {
-      const auctionObject = getUniqueAuctionName();
-      const [testAuctionInfo] = derivePDA(auctionObject);
+      const auctionId = getUniqueAuctionId();
+      const [testAuctionInfo] = deriveAuction(seller.publicKey, auctionId);
       const currentSlot = await provider.connection.getSlot();
       
       await program.methods
-        .start(auctionObject, auctionDuration, startingBid)
+        .start(auctionId, auctionedObject, auctionDuration, startingBid, ...noExtras)
         .accounts({
           seller: seller.publicKey,
           auctionInfo: testAuctionInfo,
@@ -138,12 +163,12 @@ describe("Universal Auction Program Test Suite", () => {
       expect(auctionAccount.highestBid.toString()).to.equal(startingBid.toString());
     });
 
-    it("prevents duplicate auctions with same object name", async () => {
-      const auctionObject = getUniqueAuctionName();
-      const [testAuctionInfo] = derivePDA(auctionObject);
+    it("prevents duplicate auctions with same id", async () => {
+      const auctionId = getUniqueAuctionId();
+      const [testAuctionInfo] = deriveAuction(seller.publicKey, auctionId);
       // First auction should succeed
       await program.methods
-        .start(auctionObject, auctionDuration, startingBid)
+        .start(auctionId, auctionedObject, auctionDuration, startingBid, ...noExtras)
         .accounts({
           seller: seller.publicKey,
           auctionInfo: testAuctionInfo,
@@ -152,10 +177,10 @@ describe("Universal Auction Program Test Suite", () => {
         .signers([seller])
         .rpc();
 
-      // Second auction with same object should fail
+      // Second auction with same id should fail
       const failed = await expectTransactionToFail(
         program.methods
-          .start(auctionObject, auctionDuration, startingBid)
+          .start(auctionId, auctionedObject, auctionDuration, startingBid, ...noExtras)
           .accounts({
             seller: seller.publicKey,
             auctionInfo: testAuctionInfo,
@@ -167,18 +192,123 @@ describe("Universal Auction Program Test Suite", () => {
       
       expect(failed).to.be.true;
     });
+
+    it("allows different sellers to auction objects with the same name", async () => {
+      const auctionId = getUniqueAuctionId();
+      for (const s of [seller, bidder1]) {
+        const [testAuctionInfo] = deriveAuction(s.publicKey, auctionId);
+        await program.methods
+          .start(auctionId, auctionedObject, auctionDuration, startingBid, ...noExtras)
+          .accounts({
+            seller: s.publicKey,
+            auctionInfo: testAuctionInfo,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([s])
+          .rpc();
+
+        const auctionAccount = await program.account.auctionInfo.fetch(testAuctionInfo);
+        expect(auctionAccount.seller.toString()).to.equal(s.publicKey.toString());
+        expect(auctionAccount.object).to.equal(auctionedObject);
+      }
+    });
+
+    it("rejects object names longer than the limit", async () => {
+      const auctionId = getUniqueAuctionId();
+      const [testAuctionInfo] = deriveAuction(seller.publicKey, auctionId);
+      const failed = await expectTransactionToFail(
+        program.methods
+          .start(auctionId, "x".repeat(101), auctionDuration, startingBid, ...noExtras)
+          .accounts({
+            seller: seller.publicKey,
+            auctionInfo: testAuctionInfo,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([seller])
+          .rpc()
+      );
+
+      expect(failed).to.be.true;
+    });
+  });
+
+  describe("cancel()", () => {
+    it("lets the seller cancel before the first bid and get back the rent", async () => {
+      const auctionId = getUniqueAuctionId();
+      const [testAuctionInfo] = deriveAuction(seller.publicKey, auctionId);
+      await program.methods
+        .start(auctionId, auctionedObject, auctionDuration, startingBid, ...noExtras)
+        .accounts({
+          seller: seller.publicKey,
+          auctionInfo: testAuctionInfo,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([seller])
+        .rpc();
+
+      const sellerBalanceBefore = await getBalance(seller.publicKey);
+      await program.methods
+        .cancel(auctionId)
+        .accounts({
+          seller: seller.publicKey,
+          auctionInfo: testAuctionInfo,
+        })
+        .signers([seller])
+        .rpc();
+
+      expect(await getBalance(seller.publicKey)).to.be.greaterThan(sellerBalanceBefore);
+      expect(await provider.connection.getAccountInfo(testAuctionInfo)).to.be.null;
+    });
+
+    it("rejects cancel after a bid", async () => {
+      const auctionId = getUniqueAuctionId();
+      const [testAuctionInfo] = deriveAuction(seller.publicKey, auctionId);
+      await program.methods
+        .start(auctionId, auctionedObject, auctionDuration, startingBid, ...noExtras)
+        .accounts({
+          seller: seller.publicKey,
+          auctionInfo: testAuctionInfo,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([seller])
+        .rpc();
+
+      await program.methods
+        .bid(auctionId, higherBid)
+        .accounts({
+          bidder: bidder1.publicKey,
+          auctionInfo: testAuctionInfo,
+          pendingReturns: derivePendingReturns(testAuctionInfo, bidder1.publicKey),
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([bidder1])
+        .rpc();
+
+      const failed = await expectTransactionToFail(
+        program.methods
+          .cancel(auctionId)
+          .accounts({
+            seller: seller.publicKey,
+            auctionInfo: testAuctionInfo,
+          })
+          .signers([seller])
+          .rpc()
+      );
+
+      expect(failed).to.be.true;
+    });
   });
 
   describe("bid()", () => {
-    let auctionObject: string;
+    let auctionId: BN;
 
     beforeEach(async () => {
       // Start an auction before each bid test
-      auctionObject = getUniqueAuctionName();
-      [auctionInfo] = derivePDA(auctionObject);
+      auctionId = getUniqueAuctionId();
+      [auctionInfo] = deriveAuction(seller.publicKey, auctionId);
       
       await program.methods
-        .start(auctionObject, auctionDuration, startingBid)
+        .start(auctionId, auctionedObject, auctionDuration, startingBid, ...noExtras)
         .accounts({
           seller: seller.publicKey,
           auctionInfo: auctionInfo,
@@ -190,25 +320,24 @@ describe("Universal Auction Program Test Suite", () => {
 
     it("accepts higher bids", async () => {
       const bidder1BalanceBefore = await getBalance(bidder1.publicKey);
-      const auctionBalanceBefore = await getBalance(auctionInfo);
       
       await program.methods
-        .bid(auctionObject, higherBid)
+        .bid(auctionId, higherBid)
         .accounts({
           bidder: bidder1.publicKey,
           auctionInfo: auctionInfo,
-          currentHighestBidder: seller.publicKey, // seller is initially the highest bidder
+          pendingReturns: derivePendingReturns(auctionInfo, bidder1.publicKey),
           systemProgram: SystemProgram.programId,
         })
         .signers([bidder1])
         .rpc();
 
-      // Verify lamport transfers
+      // Verify lamport transfers, the bid is kept in the bidder's pending-returns PDA
       const bidder1BalanceAfter = await getBalance(bidder1.publicKey);
-      const auctionBalanceAfter = await getBalance(auctionInfo);
+      const escrowBalance = await getBalance(derivePendingReturns(auctionInfo, bidder1.publicKey));
       
       expect(bidder1BalanceAfter).to.be.lessThan(bidder1BalanceBefore);
-      expect(auctionBalanceAfter).to.be.greaterThan(auctionBalanceBefore);
+      expect(escrowBalance).to.be.greaterThan(higherBid.toNumber());
       
       // Verify auction state update
       const auctionAccount = await program.account.auctionInfo.fetch(auctionInfo);
@@ -219,11 +348,11 @@ describe("Universal Auction Program Test Suite", () => {
     it("rejects lower bids", async () => {
       // First, place a higher bid
       await program.methods
-        .bid(auctionObject, higherBid)
+        .bid(auctionId, higherBid)
         .accounts({
           bidder: bidder1.publicKey,
           auctionInfo: auctionInfo,
-          currentHighestBidder: seller.publicKey,
+          pendingReturns: derivePendingReturns(auctionInfo, bidder1.publicKey),
           systemProgram: SystemProgram.programId,
         })
         .signers([bidder1])
@@ -232,11 +361,11 @@ describe("Universal Auction Program Test Suite", () => {
       // Then try to place a lower bid - should fail
       const failed = await expectTransactionToFail(
         program.methods
-          .bid(auctionObject, startingBid) // Lower than higherBid
+          .bid(auctionId, startingBid) // Lower than higherBid
           .accounts({
             bidder: bidder2.publicKey,
             auctionInfo: auctionInfo,
-            currentHighestBidder: bidder1.publicKey,
+            pendingReturns: derivePendingReturns(auctionInfo, bidder2.publicKey),
             systemProgram: SystemProgram.programId,
           })
           .signers([bidder2])
@@ -246,14 +375,14 @@ describe("Universal Auction Program Test Suite", () => {
       expect(failed).to.be.true;
     });
 
-    it("refunds previous bidder", async () => {
+    it("lets the previous bidder withdraw its refund", async () => {
       // First bid
       await program.methods
-        .bid(auctionObject, higherBid)
+        .bid(auctionId, higherBid)
         .accounts({
           bidder: bidder1.publicKey,
           auctionInfo: auctionInfo,
-          currentHighestBidder: seller.publicKey,
+          pendingReturns: derivePendingReturns(auctionInfo, bidder1.publicKey),
           systemProgram: SystemProgram.programId,
         })
         .signers([bidder1])
@@ -263,16 +392,33 @@ describe("Universal Auction Program Test Suite", () => {
 
       // Second, higher bid
       await program.methods
-        .bid(auctionObject, evenHigherBid)
+        .bid(auctionId, evenHigherBid)
         .accounts({
           bidder: bidder2.publicKey,
           auctionInfo: auctionInfo,
-          currentHighestBidder: bidder1.publicKey,
+          pendingReturns: derivePendingReturns(auctionInfo, bidder2.publicKey),
           systemProgram: SystemProgram.programId,
         })
         .signers([bidder2])
         .rpc();
 
+      // The outbid amount is credited, not pushed back
+      const pendingReturns = await program.account.pendingReturnsPda.fetch(
+        derivePendingReturns(auctionInfo, bidder1.publicKey)
+      );
+      expect(pendingReturns.amount.toString()).to.equal(higherBid.toString());
+
+      await program.methods
+        .withdrawRefund(auctionId)
+        .accounts({
+          bidder: bidder1.publicKey,
+          seller: seller.publicKey,
+          auctionInfo: auctionInfo,
+          pendingReturns: derivePendingReturns(auctionInfo, bidder1.publicKey),
+        })
+        .signers([bidder1])
+        .rpc();
+
       // Verify bidder1 was refunded
       const bidder1BalanceAfterRefund = await getBalance(bidder1.publicKey);
       expect(bidder1BalanceAfterRefund).to.be.greaterThan(bidder1BalanceAfterFirstBid);
@@ -282,6 +428,34 @@ describe("Universal Auction Program Test Suite", () => {
       expect(refundAmount).to.be.greaterThan(higherBid.toNumber() * 0.95); // Allow for fees
     });
 
+    it("rejects refund of the highest bid", async () => {
+      await program.methods
+        .bid(auctionId, higherBid)
+        .accounts({
+          bidder: bidder1.publicKey,
+          auctionInfo: auctionInfo,
+          pendingReturns: derivePendingReturns(auctionInfo, bidder1.publicKey),
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([bidder1])
+        .rpc();
+
+      const failed = await expectTransactionToFail(
+        program.methods
+          .withdrawRefund(auctionId)
+          .accounts({
+            bidder: bidder1.publicKey,
+            seller: seller.publicKey,
+            auctionInfo: auctionInfo,
+            pendingReturns: derivePendingReturns(auctionInfo, bidder1.publicKey),
+          })
+          .signers([bidder1])
+          .rpc()
+      );
+
+      expect(failed).to.be.true;
+    });
+
     it("rejects bids after end time", async () => {
       // Wait for auction to end (in real test, you might need to manipulate time or use shorter duration)
       // For this test, we'll assume the auction has a very short duration or we have time manipulation
@@ -289,11 +463,11 @@ describe("Universal Auction Program Test Suite", () => {
       // If your test environment supports time manipulation, use it here
       // Otherwise, create an auction with very short duration (1-2 slots)
       const shortDuration = new BN(1);
-      const shortAuctionObject = getUniqueAuctionName();
-      const [shortAuctionInfo] = derivePDA(shortAuctionObject);
+      const shortAuctionId = getUniqueAuctionId();
+      const [shortAuctionInfo] = deriveAuction(seller.publicKey, shortAuctionId);
       
       await program.methods
-        .start(shortAuctionObject, shortDuration, startingBid)
+        .start(shortAuctionId, auctionedObject, shortDuration, startingBid, ...noExtras)
         .accounts({
           seller: seller.publicKey,
           auctionInfo: shortAuctionInfo,
@@ -307,11 +481,11 @@ describe("Universal Auction Program Test Suite", () => {
 
       const failed = await expectTransactionToFail(
         program.methods
-          .bid(shortAuctionObject, higherBid)
+          .bid(shortAuctionId, higherBid)
           .accounts({
             bidder: bidder1.publicKey,
             auctionInfo: shortAuctionInfo,
-            currentHighestBidder: seller.publicKey,
+            pendingReturns: derivePendingReturns(shortAuctionInfo, bidder1.publicKey),
             systemProgram: SystemProgram.programId,
           })
           .signers([bidder1])
@@ -325,11 +499,11 @@ describe("Universal Auction Program Test Suite", () => {
       const initialAccount = await program.account.auctionInfo.fetch(auctionInfo);
       
       await program.methods
-        .bid(auctionObject, higherBid)
+        .bid(auctionId, higherBid)
         .accounts({
           bidder: bidder1.publicKey,
           auctionInfo: auctionInfo,
-          currentHighestBidder: seller.publicKey,
+          pendingReturns: derivePendingReturns(auctionInfo, bidder1.publicKey),
           systemProgram: SystemProgram.programId,
         })
         .signers([bidder1])
@@ -344,15 +518,15 @@ describe("Universal Auction Program Test Suite", () => {
   });
 
   describe("end()", () => {
-    let auctionObject: string;
+    let auctionId: BN;
 
     beforeEach(async () => {
       // Start auction and place a bid
-      auctionObject = getUniqueAuctionName();
-      [auctionInfo] = derivePDA(auctionObject);
+      auctionId = getUniqueAuctionId();
+      [auctionInfo] = deriveAuction(seller.publicKey, auctionId);
       
       await program.methods
-        .start(auctionObject, auctionDuration, startingBid)
+        .start(auctionId, auctionedObject, auctionDuration, startingBid, ...noExtras)
         .accounts({
           seller: seller.publicKey,
           auctionInfo: auctionInfo,
@@ -362,11 +536,11 @@ describe("Universal Auction Program Test Suite", () => {
         .rpc();
 
       await program.methods
-        .bid(auctionObject, higherBid)
+        .bid(auctionId, higherBid)
         .accounts({
           bidder: bidder1.publicKey,
           auctionInfo: auctionInfo,
-          currentHighestBidder: seller.publicKey,
+          pendingReturns: derivePendingReturns(auctionInfo, bidder1.publicKey),
           systemProgram: SystemProgram.programId,
         })
         .signers([bidder1])
@@ -376,11 +550,11 @@ describe("Universal Auction Program Test Suite", () => {
     it("allows seller to end after duration", async () => {
       // Create auction with very short duration
       const shortDuration = new BN(1);
-      const shortAuctionObject = getUniqueAuctionName();
-      const [shortAuctionInfo] = derivePDA(shortAuctionObject);
+      const shortAuctionId = getUniqueAuctionId();
+      const [shortAuctionInfo] = deriveAuction(seller.publicKey, shortAuctionId);
       
       await program.methods
-        .start(shortAuctionObject, shortDuration, startingBid)
+        .start(shortAuctionId, auctionedObject, shortDuration, startingBid, ...noExtras)
         .accounts({
           seller: seller.publicKey,
           auctionInfo: shortAuctionInfo,
@@ -394,30 +568,28 @@ describe("Universal Auction Program Test Suite", () => {
 
       // Should be able to end the auction
       await program.methods
-        .end(shortAuctionObject)
+        .end(shortAuctionId)
         .accounts({
+          caller: seller.publicKey,
           seller: seller.publicKey,
           auctionInfo: shortAuctionInfo,
+          winnerPendingReturns: null,
+          winnerSealedBid: null,
         })
         .signers([seller])
         .rpc();
 
-      // Verify auction account state or that it was closed
-      try {
-        await program.account.auctionInfo.fetch(shortAuctionInfo);
-        // If account still exists, verify it's marked as ended or closed
-      } catch (error) {
-        // Account was closed, which is also acceptable
-      }
+      // The auction account is closed and the rent returned to the seller
+      expect(await provider.connection.getAccountInfo(shortAuctionInfo)).to.be.null;
     });
 
     it("transfers funds to seller", async () => {
       const shortDuration = new BN(3);
-      const transferAuctionObject = getUniqueAuctionName();
-      const [transferAuctionInfo] = derivePDA(transferAuctionObject);
+      const transferAuctionId = getUniqueAuctionId();
+      const [transferAuctionInfo] = deriveAuction(seller.publicKey, transferAuctionId);
       
       await program.methods
-        .start(transferAuctionObject, shortDuration, startingBid)
+        .start(transferAuctionId, auctionedObject, shortDuration, startingBid, ...noExtras)
         .accounts({
           seller: seller.publicKey,
           auctionInfo: transferAuctionInfo,
@@ -427,11 +599,11 @@ describe("Universal Auction Program Test Suite", () => {
         .rpc();
 
       await program.methods
-        .bid(transferAuctionObject, higherBid)
+        .bid(transferAuctionId, higherBid)
         .accounts({
           bidder: bidder1.publicKey,
           auctionInfo: transferAuctionInfo,
-          currentHighestBidder: seller.publicKey,
+          pendingReturns: derivePendingReturns(transferAuctionInfo, bidder1.publicKey),
           systemProgram: SystemProgram.programId,
         })
         .signers([bidder1])
@@ -442,10 +614,13 @@ describe("Universal Auction Program Test Suite", () => {
       const sellerBalanceBefore = await getBalance(seller.publicKey);
       
       await program.methods
-        .end(transferAuctionObject)
+        .end(transferAuctionId)
         .accounts({
+          caller: seller.publicKey,
           seller: seller.publicKey,
           auctionInfo: transferAuctionInfo,
+          winnerPendingReturns: derivePendingReturns(transferAuctionInfo, bidder1.publicKey),
+          winnerSealedBid: null,
         })
         .signers([seller])
         .rpc();
@@ -458,13 +633,87 @@ describe("Universal Auction Program Test Suite", () => {
       expect(received).to.be.greaterThan(higherBid.toNumber() * 0.95); // Allow for fees
     });
 
+    it("keeps the deposits of an ended auction apart from a restarted one", async () => {
+      const restartAuctionId = getUniqueAuctionId();
+      const [restartAuctionInfo] = deriveAuction(seller.publicKey, restartAuctionId);
+      const start = (duration: BN) =>
+        program.methods
+          .start(restartAuctionId, auctionedObject, duration, startingBid, ...noExtras)
+          .accounts({
+            seller: seller.publicKey,
+            auctionInfo: restartAuctionInfo,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([seller])
+          .rpc();
+      const bid = (bidder: Keypair, amount: BN) =>
+        program.methods
+          .bid(restartAuctionId, amount)
+          .accounts({
+            bidder: bidder.publicKey,
+            auctionInfo: restartAuctionInfo,
+            pendingReturns: derivePendingReturns(restartAuctionInfo, bidder.publicKey),
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([bidder])
+          .rpc();
+
+      await start(new BN(3));
+      await bid(bidder1, higherBid);
+      await bid(bidder2, evenHigherBid);
+
+      await sleep(5000); // Wait for auction to end
+
+      await program.methods
+        .end(restartAuctionId)
+        .accounts({
+          caller: seller.publicKey,
+          seller: seller.publicKey,
+          auctionInfo: restartAuctionInfo,
+          winnerPendingReturns: derivePendingReturns(restartAuctionInfo, bidder2.publicKey),
+          winnerSealedBid: null,
+        })
+        .signers([seller])
+        .rpc();
+
+      // The seller starts a new auction with the same id
+      await start(auctionDuration);
+
+      // The deposit of the previous auction cannot be carried over
+      const failed = await expectTransactionToFail(bid(bidder1, evenHigherBid));
+      expect(failed).to.be.true;
+
+      // It is still withdrawable while the new auction runs
+      const bidderBalanceBefore = await getBalance(bidder1.publicKey);
+      await program.methods
+        .withdrawRefund(restartAuctionId)
+        .accounts({
+          bidder: bidder1.publicKey,
+          seller: seller.publicKey,
+          auctionInfo: restartAuctionInfo,
+          pendingReturns: derivePendingReturns(restartAuctionInfo, bidder1.publicKey),
+        })
+        .signers([bidder1])
+        .rpc();
+      const refund = (await getBalance(bidder1.publicKey)) - bidderBalanceBefore;
+      expect(refund).to.be.greaterThan(higherBid.toNumber() * 0.95);
+
+      // Then the bidder can join the new auction
+      await bid(bidder1, higherBid);
+      const auctionAccount = await program.account.auctionInfo.fetch(restartAuctionInfo);
+      expect(auctionAccount.highestBidder.toString()).to.equal(bidder1.publicKey.toString());
+    });
+
     it("rejects early ending", async () => {
       const failed = await expectTransactionToFail(
         program.methods
-          .end(auctionObject)
+          .end(auctionId)
           .accounts({
+            caller: seller.publicKey,
             seller: seller.publicKey,
             auctionInfo: auctionInfo,
+            winnerPendingReturns: derivePendingReturns(auctionInfo, bidder1.publicKey),
+            winnerSealedBid: null,
           })
           .signers([seller])
           .rpc()
@@ -473,13 +722,13 @@ describe("Universal Auction Program Test Suite", () => {
       expect(failed).to.be.true;
     });
 
-    it("rejects non-seller ending", async () => {
+    it("allows anyone to end the auction, paying the seller", async () => {
       const shortDuration = new BN(1);
-      const nonSellerAuctionObject = getUniqueAuctionName();
-      const [nonSellerAuctionInfo] = derivePDA(nonSellerAuctionObject);
+      const nonSellerAuctionId = getUniqueAuctionId();
+      const [nonSellerAuctionInfo] = deriveAuction(seller.publicKey, nonSellerAuctionId);
       
       await program.methods
-        .start(nonSellerAuctionObject, shortDuration, startingBid)
+        .start(nonSellerAuctionId, auctionedObject, shortDuration, startingBid, ...noExtras)
         .accounts({
           seller: seller.publicKey,
           auctionInfo: nonSellerAuctionInfo,
@@ -490,30 +739,49 @@ describe("Universal Auction Program Test Suite", () => {
 
       await sleep(5000); // Wait for auction to end
 
-      // Bidder trying to end auction should fail
+      // The rent cannot be redirected to the caller
       const failed = await expectTransactionToFail(
         program.methods
-          .end(nonSellerAuctionObject)
+          .end(nonSellerAuctionId)
           .accounts({
+            caller: bidder1.publicKey,
             seller: bidder1.publicKey, // Wrong seller
             auctionInfo: nonSellerAuctionInfo,
+            winnerPendingReturns: null,
+            winnerSealedBid: null,
           })
           .signers([bidder1])
           .rpc()
       );
-
       expect(failed).to.be.true;
+
+      const sellerBalanceBefore = await getBalance(seller.publicKey);
+      await program.methods
+        .end(nonSellerAuctionId)
+        .accounts({
+          caller: bidder1.publicKey,
+          seller: seller.publicKey,
+          auctionInfo: nonSellerAuctionInfo,
+          winnerPendingReturns: null,
+          winnerSealedBid: null,
+        })
+        .signers([bidder1])
+        .rpc();
+
+      // The seller gets back the rent of the closed auction account
+      expect(await getBalance(seller.publicKey)).to.be.greaterThan(sellerBalanceBefore);
+      expect(await provider.connection.getAccountInfo(nonSellerAuctionInfo)).to.be.null;
     });
   });
 
   describe("time validation", () => {
     it("enforces auction duration", async () => {
-      const auctionObject = getUniqueAuctionName();
-      const [testAuctionInfo] = derivePDA(auctionObject);
+      const auctionId = getUniqueAuctionId();
+      const [testAuctionInfo] = deriveAuction(seller.publicKey, auctionId);
       const currentSlot = await provider.connection.getSlot();
       
       await program.methods
-        .start(auctionObject, auctionDuration, startingBid)
+        .start(auctionId, auctionedObject, auctionDuration, startingBid, ...noExtras)
         .accounts({
           seller: seller.publicKey,
           auctionInfo: testAuctionInfo,
@@ -532,11 +800,11 @@ describe("Universal Auction Program Test Suite", () => {
 
     it("prevents bidding after end", async () => {
       const shortDuration = new BN(1);
-      const timeoutAuctionObject = getUniqueAuctionName();
-      const [timeoutAuctionInfo] = derivePDA(timeoutAuctionObject);
+      const timeoutAuctionId = getUniqueAuctionId();
+      const [timeoutAuctionInfo] = deriveAuction(seller.publicKey, timeoutAuctionId);
       
       await program.methods
-        .start(timeoutAuctionObject, shortDuration, startingBid)
+        .start(timeoutAuctionId, auctionedObject, shortDuration, startingBid, ...noExtras)
         .accounts({
           seller: seller.publicKey,
           auctionInfo: timeoutAuctionInfo,
@@ -549,11 +817,11 @@ describe("Universal Auction Program Test Suite", () => {
 
       const failed = await expectTransactionToFail(
         program.methods
-          .bid(timeoutAuctionObject, higherBid)
+          .bid(timeoutAuctionId, higherBid)
           .accounts({
             bidder: bidder1.publicKey,
             auctionInfo: timeoutAuctionInfo,
-            currentHighestBidder: seller.publicKey,
+            pendingReturns: derivePendingReturns(timeoutAuctionInfo, bidder1.publicKey),
             systemProgram: SystemProgram.programId,
           })
           .signers([bidder1])
@@ -564,13 +832,403 @@ describe("Universal Auction Program Test Suite", () => {
     });
   });
 
+  describe("english auction options", () => {
+    it("extends the end time for bids in the anti-sniping window", async () => {
+      const auctionId = getUniqueAuctionId();
+      const [testAuctionInfo] = deriveAuction(seller.publicKey, auctionId);
+
+      // The whole auction is inside the window, so any bid extends it
+      await program.methods
+        .start(auctionId, auctionedObject, auctionDuration, startingBid, new BN(0), { absolute: [new BN(0)] }, auctionDuration.addn(10), new BN(50), { english: {} })
+        .accounts({
+          seller: seller.publicKey,
+          auctionInfo: testAuctionInfo,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([seller])
+        .rpc();
+
+      const before = await program.account.auctionInfo.fetch(testAuctionInfo);
+
+      await program.methods
+        .bid(auctionId, higherBid)
+        .accounts({
+          bidder: bidder1.publicKey,
+          auctionInfo: testAuctionInfo,
+          pendingReturns: derivePendingReturns(testAuctionInfo, bidder1.publicKey),
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([bidder1])
+        .rpc();
+
+      const after = await program.account.auctionInfo.fetch(testAuctionInfo);
+      expect(after.endTime.toNumber()).to.equal(before.endTime.toNumber() + 50);
+    });
+
+    it("rejects bids below the minimum increment", async () => {
+      const auctionId = getUniqueAuctionId();
+      const [testAuctionInfo] = deriveAuction(seller.publicKey, auctionId);
+
+      // 10% of the current highest bid
+      await program.methods
+        .start(auctionId, auctionedObject, auctionDuration, startingBid, new BN(0), { basisPoints: [1000] }, new BN(0), new BN(0), { english: {} })
+        .accounts({
+          seller: seller.publicKey,
+          auctionInfo: testAuctionInfo,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([seller])
+        .rpc();
+
+      const failed = await expectTransactionToFail(
+        program.methods
+          .bid(auctionId, startingBid.addn(1))
+          .accounts({
+            bidder: bidder1.publicKey,
+            auctionInfo: testAuctionInfo,
+            pendingReturns: derivePendingReturns(testAuctionInfo, bidder1.publicKey),
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([bidder1])
+          .rpc()
+      );
+      expect(failed).to.be.true;
+
+      await program.methods
+        .bid(auctionId, startingBid.muln(11).divn(10))
+        .accounts({
+          bidder: bidder1.publicKey,
+          auctionInfo: testAuctionInfo,
+          pendingReturns: derivePendingReturns(testAuctionInfo, bidder1.publicKey),
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([bidder1])
+        .rpc();
+    });
+
+    it("returns the object to the seller when the reserve is not met", async () => {
+      const shortDuration = new BN(3);
+      const auctionId = getUniqueAuctionId();
+      const [testAuctionInfo] = deriveAuction(seller.publicKey, auctionId);
+
+      await program.methods
+        .start(auctionId, auctionedObject, shortDuration, startingBid, evenHigherBid, { absolute: [new BN(0)] }, new BN(0), new BN(0), { english: {} })
+        .accounts({
+          seller: seller.publicKey,
+          auctionInfo: testAuctionInfo,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([seller])
+        .rpc();
+
+      await program.methods
+        .bid(auctionId, higherBid)
+        .accounts({
+          bidder: bidder1.publicKey,
+          auctionInfo: testAuctionInfo,
+          pendingReturns: derivePendingReturns(testAuctionInfo, bidder1.publicKey),
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([bidder1])
+        .rpc();
+
+      await sleep(5000); // Wait for auction to end
+
+      const sellerBalanceBefore = await getBalance(seller.publicKey);
+      await program.methods
+        .end(auctionId)
+        .accounts({
+          caller: seller.publicKey,
+          seller: seller.publicKey,
+          auctionInfo: testAuctionInfo,
+          winnerPendingReturns: null,
+          winnerSealedBid: null,
+        })
+        .signers([seller])
+        .rpc();
+      // The seller only gets back the rent of the closed auction account
+      const received = (await getBalance(seller.publicKey)) - sellerBalanceBefore;
+      expect(received).to.be.lessThan(LAMPORTS_PER_SOL / 100);
+      expect(await provider.connection.getAccountInfo(testAuctionInfo)).to.be.null;
+
+      // The top bidder gets its bid back
+      const bidder1BalanceBefore = await getBalance(bidder1.publicKey);
+      await program.methods
+        .withdrawRefund(auctionId)
+        .accounts({
+          bidder: bidder1.publicKey,
+          seller: seller.publicKey,
+          auctionInfo: testAuctionInfo,
+          pendingReturns: derivePendingReturns(testAuctionInfo, bidder1.publicKey),
+        })
+        .signers([bidder1])
+        .rpc();
+      const refund = (await getBalance(bidder1.publicKey)) - bidder1BalanceBefore;
+      expect(refund).to.be.greaterThan(higherBid.toNumber() * 0.95);
+    });
+  });
+
+  describe("dutch auction", () => {
+    const floorPrice = new BN(LAMPORTS_PER_SOL / 2);
+    const decayPerSlot = new BN(LAMPORTS_PER_SOL / 1000);
+
+    const startDutch = async (auctionId: string, auction: PublicKey) => {
+      await program.methods
+        .start(auctionId, auctionedObject, auctionDuration, higherBid, new BN(0), { absolute: [new BN(0)] }, new BN(0), new BN(0), { dutch: { floorPrice, decayPerSlot } })
+        .accounts({
+          seller: seller.publicKey,
+          auctionInfo: auction,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([seller])
+        .rpc();
+    };
+
+    it("sells to the first buyer at or above the current price", async () => {
+      const auctionId = getUniqueAuctionId();
+      const [testAuctionInfo] = deriveAuction(seller.publicKey, auctionId);
+      await startDutch(auctionId, testAuctionInfo);
+
+      const sellerBalanceBefore = await getBalance(seller.publicKey);
+
+      await program.methods
+        .buy(auctionId, higherBid)
+        .accounts({
+          buyer: bidder1.publicKey,
+          seller: seller.publicKey,
+          auctionInfo: testAuctionInfo,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([bidder1])
+        .rpc();
+
+      // The auction is closed, the seller gets the price and the rent
+      expect(await provider.connection.getAccountInfo(testAuctionInfo)).to.be.null;
+      // The price decreased since the start, but not below the floor
+      const received = (await getBalance(seller.publicKey)) - sellerBalanceBefore;
+      expect(received).to.be.lessThanOrEqual(higherBid.toNumber() + LAMPORTS_PER_SOL / 100);
+      expect(received).to.be.greaterThanOrEqual(floorPrice.toNumber());
+
+      // The auction is over after the first buy
+      const failed = await expectTransactionToFail(
+        program.methods
+          .buy(auctionId, higherBid)
+          .accounts({
+            buyer: bidder2.publicKey,
+            seller: seller.publicKey,
+            auctionInfo: testAuctionInfo,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([bidder2])
+          .rpc()
+      );
+      expect(failed).to.be.true;
+    });
+
+    it("rejects offers below the current price", async () => {
+      const auctionId = getUniqueAuctionId();
+      const [testAuctionInfo] = deriveAuction(seller.publicKey, auctionId);
+      await startDutch(auctionId, testAuctionInfo);
+
+      const failed = await expectTransactionToFail(
+        program.methods
+          .buy(auctionId, floorPrice.subn(1))
+          .accounts({
+            buyer: bidder1.publicKey,
+            seller: seller.publicKey,
+            auctionInfo: testAuctionInfo,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([bidder1])
+          .rpc()
+      );
+      expect(failed).to.be.true;
+    });
+
+    it("rejects english bids", async () => {
+      const auctionId = getUniqueAuctionId();
+      const [testAuctionInfo] = deriveAuction(seller.publicKey, auctionId);
+      await startDutch(auctionId, testAuctionInfo);
+
+      const failed = await expectTransactionToFail(
+        program.methods
+          .bid(auctionId, evenHigherBid)
+          .accounts({
+            bidder: bidder1.publicKey,
+            auctionInfo: testAuctionInfo,
+            pendingReturns: derivePendingReturns(testAuctionInfo, bidder1.publicKey),
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([bidder1])
+          .rpc()
+      );
+      expect(failed).to.be.true;
+    });
+  });
+
+  describe("sealed-bid auction", () => {
+    const commitSlots = new BN(20);
+    const revealSlots = new BN(20);
+    const penalty = new BN(LAMPORTS_PER_SOL / 10);
+
+    const deriveSealedBid = (auction: PublicKey, bidder: PublicKey): PublicKey => {
+      return PublicKey.findProgramAddressSync(
+        [Buffer.from("sealed_bid"), auction.toBuffer(), bidder.toBuffer()],
+        program.programId
+      )[0];
+    };
+
+    const hashBid = (amount: BN, salt: Buffer): number[] => {
+      return Array.from(keccak_256(Buffer.concat([amount.toArrayLike(Buffer, "le", 8), salt])));
+    };
+
+    const commit = async (auctionId: string, auction: PublicKey, bidder: Keypair, amount: BN, salt: Buffer, deposit: BN) => {
+      await program.methods
+        .commitBid(auctionId, hashBid(amount, salt), deposit)
+        .accounts({
+          bidder: bidder.publicKey,
+          auctionInfo: auction,
+          sealedBid: deriveSealedBid(auction, bidder.publicKey),
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([bidder])
+        .rpc();
+    };
+
+    const withdraw = async (auctionId: string, auction: PublicKey, bidder: Keypair) => {
+      await program.methods
+        .withdrawSealedBid(auctionId)
+        .accounts({
+          bidder: bidder.publicKey,
+          seller: seller.publicKey,
+          auctionInfo: auction,
+          sealedBid: deriveSealedBid(auction, bidder.publicKey),
+        })
+        .signers([bidder])
+        .rpc();
+    };
+
+    it("settles a second-price auction and penalizes non-revealers", async () => {
+      const auctionId = getUniqueAuctionId();
+      const [testAuctionInfo] = deriveAuction(seller.publicKey, auctionId);
+      const bidder3 = Keypair.generate();
+      await provider.connection.requestAirdrop(bidder3.publicKey, 10 * LAMPORTS_PER_SOL);
+      await sleep(1000);
+
+      await program.methods
+        .start(auctionId, auctionedObject, commitSlots, startingBid, new BN(0), { absolute: [new BN(0)] }, new BN(0), new BN(0), { sealed: { revealSlots, secondPrice: true, penalty } })
+        .accounts({
+          seller: seller.publicKey,
+          auctionInfo: testAuctionInfo,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([seller])
+        .rpc();
+
+      const salt1 = Buffer.alloc(32, 1);
+      const salt2 = Buffer.alloc(32, 2);
+      // Deposits are higher than the bids to hide the amounts
+      await commit(auctionId, testAuctionInfo, bidder1, evenHigherBid, salt1, new BN(4 * LAMPORTS_PER_SOL));
+      await commit(auctionId, testAuctionInfo, bidder2, higherBid, salt2, new BN(4 * LAMPORTS_PER_SOL));
+      await commit(auctionId, testAuctionInfo, bidder3, higherBid, salt2, new BN(4 * LAMPORTS_PER_SOL));
+
+      await sleep(10000); // Wait for the commit phase to end
+
+      for (const [bidder, amount, salt] of [[bidder1, evenHigherBid, salt1], [bidder2, higherBid, salt2]] as const) {
+        await program.methods
+          .revealBid(auctionId, amount, Array.from(salt))
+          .accounts({
+            bidder: bidder.publicKey,
+            auctionInfo: testAuctionInfo,
+            sealedBid: deriveSealedBid(testAuctionInfo, bidder.publicKey),
+          })
+          .signers([bidder])
+          .rpc();
+      }
+
+      const revealed = await program.account.auctionInfo.fetch(testAuctionInfo);
+      expect(revealed.highestBidder.toString()).to.equal(bidder1.publicKey.toString());
+      expect(revealed.secondBid.toString()).to.equal(higherBid.toString());
+
+      await sleep(10000); // Wait for the reveal phase to end
+
+      const sellerBalanceBefore = await getBalance(seller.publicKey);
+      await program.methods
+        .end(auctionId)
+        .accounts({
+          caller: seller.publicKey,
+          seller: seller.publicKey,
+          auctionInfo: testAuctionInfo,
+          winnerPendingReturns: null,
+          winnerSealedBid: deriveSealedBid(testAuctionInfo, bidder1.publicKey),
+        })
+        .signers([seller])
+        .rpc();
+      // The winner pays the second highest bid
+      const received = (await getBalance(seller.publicKey)) - sellerBalanceBefore;
+      expect(received).to.be.greaterThan(higherBid.toNumber() * 0.95);
+      expect(received).to.be.lessThan(evenHigherBid.toNumber());
+
+      // The winner gets back its deposit minus the price
+      const bidder1BalanceBefore = await getBalance(bidder1.publicKey);
+      await withdraw(auctionId, testAuctionInfo, bidder1);
+      const bidder1Refund = (await getBalance(bidder1.publicKey)) - bidder1BalanceBefore;
+      expect(bidder1Refund).to.be.greaterThan(2 * LAMPORTS_PER_SOL * 0.95);
+
+      // The losing revealer gets back the whole deposit
+      const bidder2BalanceBefore = await getBalance(bidder2.publicKey);
+      await withdraw(auctionId, testAuctionInfo, bidder2);
+      const bidder2Refund = (await getBalance(bidder2.publicKey)) - bidder2BalanceBefore;
+      expect(bidder2Refund).to.be.greaterThan(4 * LAMPORTS_PER_SOL * 0.95);
+
+      // The non-revealer loses the penalty
+      const bidder3BalanceBefore = await getBalance(bidder3.publicKey);
+      await withdraw(auctionId, testAuctionInfo, bidder3);
+      const bidder3Refund = (await getBalance(bidder3.publicKey)) - bidder3BalanceBefore;
+      expect(bidder3Refund).to.be.lessThan(4 * LAMPORTS_PER_SOL - penalty.toNumber() + LAMPORTS_PER_SOL / 100);
+    });
+
+    it("rejects reveals not matching the commitment", async () => {
+      const auctionId = getUniqueAuctionId();
+      const [testAuctionInfo] = deriveAuction(seller.publicKey, auctionId);
+
+      await program.methods
+        .start(auctionId, auctionedObject, new BN(5), startingBid, new BN(0), { absolute: [new BN(0)] }, new BN(0), new BN(0), { sealed: { revealSlots, secondPrice: false, penalty } })
+        .accounts({
+          seller: seller.publicKey,
+          auctionInfo: testAuctionInfo,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([seller])
+        .rpc();
+
+      const salt = Buffer.alloc(32, 7);
+      await commit(auctionId, testAuctionInfo, bidder1, higherBid, salt, higherBid);
+
+      await sleep(5000); // Wait for the commit phase to end
+
+      const failed = await expectTransactionToFail(
+        program.methods
+          .revealBid(auctionId, evenHigherBid, Array.from(salt))
+          .accounts({
+            bidder: bidder1.publicKey,
+            auctionInfo: testAuctionInfo,
+            sealedBid: deriveSealedBid(testAuctionInfo, bidder1.publicKey),
+          })
+          .signers([bidder1])
+          .rpc()
+      );
+      expect(failed).to.be.true;
+    });
+  });
+
   describe("edge cases and security", () => {
     it("handles self-bidding by seller", async () => {
-      const auctionObject = getUniqueAuctionName();
-      const [testAuctionInfo] = derivePDA(auctionObject);
+      const auctionId = getUniqueAuctionId();
+      const [testAuctionInfo] = deriveAuction(seller.publicKey, auctionId);
       
       await program.methods
-        .start(auctionObject, auctionDuration, startingBid)
+        .start(auctionId, auctionedObject, auctionDuration, startingBid, ...noExtras)
         .accounts({
           seller: seller.publicKey,
           auctionInfo: testAuctionInfo,
@@ -583,11 +1241,11 @@ describe("Universal Auction Program Test Suite", () => {
       // Some implementations allow this, others don't
       try {
         await program.methods
-          .bid(auctionObject, higherBid)
+          .bid(auctionId, higherBid)
           .accounts({
             bidder: seller.publicKey,
             auctionInfo: testAuctionInfo,
-            currentHighestBidder: seller.publicKey,
+            pendingReturns: derivePendingReturns(testAuctionInfo, seller.publicKey),
             systemProgram: SystemProgram.programId,
           })
           .signers([seller])
@@ -604,11 +1262,11 @@ describe("Universal Auction Program Test Suite", () => {
     });
 
     it("handles zero bid amounts", async () => {
-      const auctionObject = getUniqueAuctionName();
-      const [testAuctionInfo] = derivePDA(auctionObject);
+      const auctionId = getUniqueAuctionId();
+      const [testAuctionInfo] = deriveAuction(seller.publicKey, auctionId);
       
       await program.methods
-        .start(auctionObject, auctionDuration, startingBid)
+        .start(auctionId, auctionedObject, auctionDuration, startingBid, ...noExtras)
         .accounts({
           seller: seller.publicKey,
           auctionInfo: testAuctionInfo,
@@ -619,11 +1277,11 @@ describe("Universal Auction Program Test Suite", () => {
 
       const failed = await expectTransactionToFail(
         program.methods
-          .bid(auctionObject, new BN(0))
+          .bid(auctionId, new BN(0))
           .accounts({
             bidder: bidder1.publicKey,
             auctionInfo: testAuctionInfo,
-            currentHighestBidder: seller.publicKey,
+            pendingReturns: derivePendingReturns(testAuctionInfo, bidder1.publicKey),
             systemProgram: SystemProgram.programId,
           })
           .signers([bidder1])
@@ -634,11 +1292,11 @@ describe("Universal Auction Program Test Suite", () => {
     });
 
     it("handles multiple rapid bids correctly", async () => {
-      const auctionObject = getUniqueAuctionName();
-      const [testAuctionInfo] = derivePDA(auctionObject);
+      const auctionId = getUniqueAuctionId();
+      const [testAuctionInfo] = deriveAuction(seller.publicKey, auctionId);
       
       await program.methods
-        .start(auctionObject, auctionDuration, startingBid)
+        .start(auctionId, auctionedObject, auctionDuration, startingBid, ...noExtras)
         .accounts({
           seller: seller.publicKey,
           auctionInfo: testAuctionInfo,
@@ -649,22 +1307,22 @@ describe("Universal Auction Program Test Suite", () => {
 
       // Place multiple bids in sequence
       await program.methods
-        .bid(auctionObject, new BN(2 * LAMPORTS_PER_SOL))
+        .bid(auctionId, new BN(2 * LAMPORTS_PER_SOL))
         .accounts({
           bidder: bidder1.publicKey,
           auctionInfo: testAuctionInfo,
-          currentHighestBidder: seller.publicKey,
+          pendingReturns: derivePendingReturns(testAuctionInfo, bidder1.publicKey),
           systemProgram: SystemProgram.programId,
         })
         .signers([bidder1])
         .rpc();
 
       await program.methods
-        .bid(auctionObject, new BN(3 * LAMPORTS_PER_SOL))
+        .bid(auctionId, new BN(3 * LAMPORTS_PER_SOL))
         .accounts({
           bidder: bidder2.publicKey,
           auctionInfo: testAuctionInfo,
-          currentHighestBidder: bidder1.publicKey,
+          pendingReturns: derivePendingReturns(testAuctionInfo, bidder2.publicKey),
           systemProgram: SystemProgram.programId,
         })
         .signers([bidder2])
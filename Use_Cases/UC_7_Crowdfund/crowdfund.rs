@@ -1,29 +1,61 @@
 use anchor_lang::prelude::*;
+use anchor_spl::token::spl_token;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
 
 declare_id!("8Bk7qQpQxqBz5XVX3LqV3vbpnNuZLhCnKq316UHThMHV");
 
+const MAX_REWARD_TIERS: usize = 5;
+const MAX_STRETCH_GOALS: usize = 5;
+const MAX_MILESTONES: usize = 5;
+
 #[program]
 pub mod crowdfund {
     use super::*;
 
+    #[allow(clippy::too_many_arguments)]
     pub fn initialize(
         ctx: Context<InitializeCtx>,
         campaign_name: String,
         end_donate_slot: u64,
         goal_in_lamports: u64,
+        reward_tiers: Vec<u64>,
+        mode: CampaignMode,
+        stretch_goals: Vec<u64>,
+        hard_cap: Option<u64>,
     ) -> Result<()> {
-        require!(goal_in_lamports > 0, CustomError::InvalidAmount);
-        require!(
-            Clock::get()?.slot < end_donate_slot,
-            CustomError::InvalidEndSlot
-        );
+        let campaign_pda = &mut ctx.accounts.campaign_pda;
+        init_campaign(
+            campaign_pda,
+            ctx.accounts.campaign_owner.key(),
+            campaign_name,
+            end_donate_slot,
+            goal_in_lamports,
+            None,
+        )?;
+        set_campaign_terms(campaign_pda, reward_tiers, mode, stretch_goals, hard_cap)
+    }
 
+    #[allow(clippy::too_many_arguments)]
+    pub fn initialize_token(
+        ctx: Context<InitializeTokenCtx>,
+        campaign_name: String,
+        end_donate_slot: u64,
+        goal_in_base_units: u64,
+        reward_tiers: Vec<u64>,
+        mode: CampaignMode,
+        stretch_goals: Vec<u64>,
+        hard_cap: Option<u64>,
+    ) -> Result<()> {
         let campaign_pda = &mut ctx.accounts.campaign_pda;
-        campaign_pda.campaign_name = campaign_name;
-        campaign_pda.campaign_owner = *ctx.accounts.campaign_owner.key;
-        campaign_pda.end_donate_slot = end_donate_slot;
-        campaign_pda.goal_in_lamports = goal_in_lamports;
-        Ok(())
+        init_campaign(
+            campaign_pda,
+            ctx.accounts.campaign_owner.key(),
+            campaign_name,
+            end_donate_slot,
+            goal_in_base_units,
+            Some(ctx.accounts.mint.key()),
+        )?;
+        set_campaign_terms(campaign_pda, reward_tiers, mode, stretch_goals, hard_cap)
     }
 
     pub fn donate(
@@ -35,13 +67,14 @@ pub mod crowdfund {
         let donor = &mut ctx.accounts.donor;
         let deposit_pda = &mut ctx.accounts.deposit_pda;
 
+        require!(campaign_pda.mint.is_none(), CustomError::InvalidMint);
         require!(donated_lamports > 0, CustomError::InvalidAmount);
         require!(
             Clock::get()?.slot <= campaign_pda.end_donate_slot,
             CustomError::TimeoutReached
         );
 
-        deposit_pda.total_donated += donated_lamports;
+        record_donation(campaign_pda, deposit_pda, donated_lamports)?;
 
         let transfer_instruction = anchor_lang::solana_program::system_instruction::transfer(
             &donor.key(),
@@ -65,62 +98,695 @@ pub mod crowdfund {
         let campaign_pda = &mut ctx.accounts.campaign_pda;
         let campaign_owner = &mut ctx.accounts.campaign_owner;
 
-        require!(
-            Clock::get()?.slot >= campaign_pda.end_donate_slot,
-            CustomError::TimeoutNotReached
-        );
+        require!(campaign_pda.mint.is_none(), CustomError::InvalidMint);
+        settle(campaign_pda)?;
+
+        // Lamports transferred directly to the campaign_pda are not donations
+        let fee = withdrawal_fee(campaign_pda)?;
+        if let CampaignMode::KeepWhatYouRaise { fee_recipient, .. } = campaign_pda.mode {
+            let fee_account = ctx
+                .accounts
+                .fee_recipient
+                .as_ref()
+                .ok_or(CustomError::InvalidFeeRecipient)?;
+            require_keys_eq!(
+                fee_account.key(),
+                fee_recipient,
+                CustomError::InvalidFeeRecipient
+            );
 
-        let balance = **campaign_pda.to_account_info().try_borrow_mut_lamports()?;
+            **fee_account.to_account_info().try_borrow_mut_lamports()? += fee;
+            **campaign_pda.to_account_info().try_borrow_mut_lamports()? -= fee;
+        }
+
+        // Rent and unsolicited lamports are left to close_campaign and sweep
+        **campaign_owner.to_account_info().try_borrow_mut_lamports()? +=
+            campaign_pda.total_raised - fee;
+        **campaign_pda.to_account_info().try_borrow_mut_lamports()? -=
+            campaign_pda.total_raised - fee;
+        campaign_pda.released_amount = campaign_pda.total_raised;
+        campaign_pda.state = CampaignState::Withdrawn;
+
+        Ok(())
+    }
+
+    pub fn reclaim(
+        ctx: Context<ReclaimCtx>,
+        _campaign_name: String, // prefixed because not used in instruction, but used for seeds in context
+    ) -> Result<()> {
+        let donor = &mut ctx.accounts.donor;
+        let campaign_pda = &mut ctx.accounts.campaign_pda;
+        let deposit_pda = &mut ctx.accounts.deposit_pda;
+
+        require!(campaign_pda.mint.is_none(), CustomError::InvalidMint);
+        settle(campaign_pda)?;
+        check_reclaim(campaign_pda)?;
+
+        // Return the donated amount to the donor, the deposit_pda is closed by the context
+        // so that the refund cannot be claimed twice and the reward tier is revoked
+        let refund = record_refund(campaign_pda, deposit_pda.total_donated);
+        **donor.to_account_info().try_borrow_mut_lamports()? += refund;
+        **campaign_pda.to_account_info().try_borrow_mut_lamports()? -= refund;
+
+        Ok(())
+    }
+
+    pub fn sweep(
+        ctx: Context<SweepCtx>,
+        _campaign_name: String, // prefixed because not used in instruction, but used for seeds in context
+    ) -> Result<()> {
+        let campaign_pda = &mut ctx.accounts.campaign_pda;
+        let campaign_owner = &mut ctx.accounts.campaign_owner;
+
+        // Unsolicited lamports are the ones exceeding the donations and the rent exemption,
+        // token campaigns hold their donations in the vault
+        let donated_lamports = match campaign_pda.mint {
+            None => campaign_pda.total_raised - campaign_pda.released_amount,
+            Some(_) => 0,
+        };
+        let balance = **campaign_pda.to_account_info().try_borrow_lamports()?;
         let rent_exemption =
             Rent::get()?.minimum_balance(campaign_pda.to_account_info().data_len());
-        let lamports_reached = balance - rent_exemption;
+        let unsolicited = balance - rent_exemption - donated_lamports;
+        require!(unsolicited > 0, CustomError::NothingToSweep);
+
+        **campaign_owner.to_account_info().try_borrow_mut_lamports()? += unsolicited;
+        **campaign_pda.to_account_info().try_borrow_mut_lamports()? -= unsolicited;
+
+        Ok(())
+    }
+
+    pub fn donate_token(
+        ctx: Context<DonateTokenCtx>,
+        _campaign_name: String, // prefixed because not used in instruction, but used for seeds in context
+        donated_amount: u64,    // in base units of the campaign mint
+    ) -> Result<()> {
+        let campaign_pda = &mut ctx.accounts.campaign_pda;
+        let deposit_pda = &mut ctx.accounts.deposit_pda;
+
+        require!(donated_amount > 0, CustomError::InvalidAmount);
+        require!(
+            Clock::get()?.slot <= campaign_pda.end_donate_slot,
+            CustomError::TimeoutReached
+        );
+
+        record_donation(campaign_pda, deposit_pda, donated_amount)?;
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.donor_token_account.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                    authority: ctx.accounts.donor.to_account_info(),
+                },
+            ),
+            donated_amount,
+        )?;
+
+        Ok(())
+    }
+
+    pub fn withdraw_token(
+        ctx: Context<WithdrawTokenCtx>,
+        _campaign_name: String, // prefixed because not used in instruction, but used for seeds in context
+    ) -> Result<()> {
+        let campaign_pda = &mut ctx.accounts.campaign_pda;
+
+        settle(campaign_pda)?;
+        let fee = withdrawal_fee(campaign_pda)?;
+        if let CampaignMode::KeepWhatYouRaise { fee_recipient, .. } = campaign_pda.mode {
+            let fee_account = ctx
+                .accounts
+                .fee_recipient_token_account
+                .as_ref()
+                .ok_or(CustomError::InvalidFeeRecipient)?;
+            require_keys_eq!(
+                fee_account.owner,
+                fee_recipient,
+                CustomError::InvalidFeeRecipient
+            );
+
+            transfer_from_vault(
+                campaign_pda,
+                &ctx.accounts.vault,
+                fee_account,
+                &ctx.accounts.token_program,
+                ctx.program_id,
+                fee,
+            )?;
+        }
+
+        // The rest of the vault is paid out, including tokens transferred directly to it
+        transfer_from_vault(
+            campaign_pda,
+            &ctx.accounts.vault,
+            &ctx.accounts.owner_token_account,
+            &ctx.accounts.token_program,
+            ctx.program_id,
+            ctx.accounts.vault.amount - fee,
+        )?;
+        campaign_pda.released_amount = campaign_pda.total_raised;
+        campaign_pda.state = CampaignState::Withdrawn;
+
+        Ok(())
+    }
+
+    pub fn reclaim_token(
+        ctx: Context<ReclaimTokenCtx>,
+        _campaign_name: String, // prefixed because not used in instruction, but used for seeds in context
+    ) -> Result<()> {
+        let deposit_pda = &ctx.accounts.deposit_pda;
+
+        settle(&mut ctx.accounts.campaign_pda)?;
+        check_reclaim(&ctx.accounts.campaign_pda)?;
+
+        // Return the donated amount to the donor, the deposit_pda is closed by the context
+        let refund = record_refund(&mut ctx.accounts.campaign_pda, deposit_pda.total_donated);
+        transfer_from_vault(
+            &ctx.accounts.campaign_pda,
+            &ctx.accounts.vault,
+            &ctx.accounts.donor_token_account,
+            &ctx.accounts.token_program,
+            ctx.program_id,
+            refund,
+        )?;
+
+        Ok(())
+    }
+
+    pub fn close_campaign(
+        ctx: Context<CloseCampaignCtx>,
+        _campaign_name: String, // prefixed because not used in instruction, but used for seeds in context
+    ) -> Result<()> {
+        let campaign_pda = &mut ctx.accounts.campaign_pda;
+
+        // A failed campaign can be closed only after all the donors have reclaimed
+        settle(campaign_pda)?;
+        require!(
+            campaign_pda.state == CampaignState::Withdrawn
+                || (campaign_pda.state == CampaignState::Failed && campaign_pda.total_raised == 0),
+            CustomError::CampaignNotClosable
+        );
+
+        if campaign_pda.mint.is_some() {
+            let vault = ctx
+                .accounts
+                .vault
+                .as_ref()
+                .ok_or(CustomError::InvalidMint)?;
+            let token_program = ctx
+                .accounts
+                .token_program
+                .as_ref()
+                .ok_or(CustomError::InvalidMint)?;
+            close_vault(
+                campaign_pda,
+                vault,
+                &ctx.accounts.campaign_owner,
+                token_program,
+                ctx.program_id,
+            )?;
+        }
+
+        // The campaign_pda is closed by the context, returning the remaining lamports to the owner
+        Ok(())
+    }
+
+    pub fn enable_milestones(
+        ctx: Context<EnableMilestonesCtx>,
+        _campaign_name: String, // prefixed because not used in instruction, but used for seeds in context
+        milestones: Vec<u16>,   // share of the raised amount of each tranche, in basis points
+        voting_slots: u64,
+        request_slots: u64, // given to the owner to request each tranche, from the end or the last release
+    ) -> Result<()> {
+        let campaign_pda = &mut ctx.accounts.campaign_pda;
+
+        // Donors must know how the funds are disbursed before donating
+        require!(
+            campaign_pda.total_raised == 0 && Clock::get()?.slot <= campaign_pda.end_donate_slot,
+            CustomError::MilestonesLocked
+        );
+        require!(
+            campaign_pda.mode == CampaignMode::AllOrNothing
+                && !milestones.is_empty()
+                && milestones.len() <= MAX_MILESTONES
+                && milestones.iter().all(|share| *share > 0)
+                && milestones.iter().map(|share| *share as u32).sum::<u32>() == 10_000,
+            CustomError::InvalidMilestones
+        );
         require!(
-            lamports_reached >= campaign_pda.goal_in_lamports,
+            voting_slots > 0 && request_slots > 0,
+            CustomError::InvalidMilestones
+        );
+
+        campaign_pda.milestones = milestones;
+        campaign_pda.voting_slots = voting_slots;
+        campaign_pda.request_slots = request_slots;
+        campaign_pda.request_deadline = campaign_pda.end_donate_slot + request_slots;
+        Ok(())
+    }
+
+    pub fn request_milestone(
+        ctx: Context<RequestMilestoneCtx>,
+        _campaign_name: String, // prefixed because not used in instruction, but used for seeds in context
+    ) -> Result<()> {
+        let campaign_pda = &mut ctx.accounts.campaign_pda;
+
+        settle(campaign_pda)?;
+        require!(
+            campaign_pda.state == CampaignState::Succeeded,
             CustomError::GoalNotReached
         );
+        require!(
+            (campaign_pda.released_milestones as usize) < campaign_pda.milestones.len(),
+            CustomError::NoMilestonesLeft
+        );
+        require!(campaign_pda.vote.is_none(), CustomError::VoteInProgress);
+        require!(
+            Clock::get()?.slot <= campaign_pda.request_deadline,
+            CustomError::RequestDeadlinePassed
+        );
 
-        **campaign_owner.to_account_info().try_borrow_mut_lamports()? +=
-            **campaign_pda.to_account_info().try_borrow_mut_lamports()?;
-        **campaign_pda.to_account_info().try_borrow_mut_lamports()? = 0;
+        campaign_pda.vote = Some(MilestoneVote {
+            milestone: campaign_pda.released_milestones,
+            deadline: Clock::get()?.slot + campaign_pda.voting_slots,
+            approve_weight: 0,
+            reject_weight: 0,
+        });
+        Ok(())
+    }
 
+    // Anyone can fail the campaign when the owner does not request the next tranche
+    // in time, so that the donors can reclaim the unreleased share
+    pub fn expire_milestone(
+        ctx: Context<ExpireMilestoneCtx>,
+        _campaign_name: String, // prefixed because not used in instruction, but used for seeds in context
+    ) -> Result<()> {
+        let campaign_pda = &mut ctx.accounts.campaign_pda;
+
+        settle(campaign_pda)?;
+        require!(
+            campaign_pda.state == CampaignState::Succeeded,
+            CustomError::GoalNotReached
+        );
+        require!(
+            (campaign_pda.released_milestones as usize) < campaign_pda.milestones.len(),
+            CustomError::NoMilestonesLeft
+        );
+        require!(campaign_pda.vote.is_none(), CustomError::VoteInProgress);
+        require!(
+            Clock::get()?.slot > campaign_pda.request_deadline,
+            CustomError::RequestDeadlineNotPassed
+        );
+
+        campaign_pda.state = CampaignState::Failed;
         Ok(())
     }
 
-    pub fn reclaim(
-        ctx: Context<ReclaimCtx>,
+    pub fn vote(
+        ctx: Context<VoteCtx>,
         _campaign_name: String, // prefixed because not used in instruction, but used for seeds in context
+        approve: bool,
     ) -> Result<()> {
-        let donor = &mut ctx.accounts.donor;
         let campaign_pda = &mut ctx.accounts.campaign_pda;
         let deposit_pda = &mut ctx.accounts.deposit_pda;
 
+        let vote = campaign_pda
+            .vote
+            .as_mut()
+            .ok_or(CustomError::NoVoteInProgress)?;
+        require!(
+            Clock::get()?.slot <= vote.deadline,
+            CustomError::VotingClosed
+        );
         require!(
-            Clock::get()?.slot >= campaign_pda.end_donate_slot,
-            CustomError::TimeoutNotReached
+            deposit_pda.next_vote <= vote.milestone,
+            CustomError::AlreadyVoted
         );
 
-        let balance = **campaign_pda.to_account_info().try_borrow_mut_lamports()?;
-        let rent_exemption =
-            Rent::get()?.minimum_balance(campaign_pda.to_account_info().data_len());
-        let lamports_reached = balance - rent_exemption;
+        // Votes are weighted by the donated amount
+        if approve {
+            vote.approve_weight += deposit_pda.total_donated;
+        } else {
+            vote.reject_weight += deposit_pda.total_donated;
+        }
+        deposit_pda.next_vote = vote.milestone + 1;
+        Ok(())
+    }
+
+    // Anyone can close the vote after the deadline, so that donors can mark
+    // the campaign as failed when the owner does not
+    pub fn release_milestone(
+        ctx: Context<ReleaseMilestoneCtx>,
+        _campaign_name: String, // prefixed because not used in instruction, but used for seeds in context
+    ) -> Result<()> {
+        let campaign_pda = &mut ctx.accounts.campaign_pda;
+
+        let vote = campaign_pda
+            .vote
+            .clone()
+            .ok_or(CustomError::NoVoteInProgress)?;
         require!(
-            lamports_reached < campaign_pda.goal_in_lamports,
-            CustomError::GoalReached
+            Clock::get()?.slot > vote.deadline,
+            CustomError::VotingNotClosed
         );
+        campaign_pda.vote = None;
 
-        // Close the deposit_pda account and return the rent to the donor
-        **donor.to_account_info().try_borrow_mut_lamports()? +=
-            **deposit_pda.to_account_info().try_borrow_mut_lamports()?;
-        **deposit_pda.to_account_info().try_borrow_mut_lamports()? = 0;
+        // A tranche is released only if approved by the majority of the votes,
+        // otherwise the donors can reclaim the unreleased share
+        if vote.approve_weight <= vote.reject_weight {
+            campaign_pda.state = CampaignState::Failed;
+            return Ok(());
+        }
 
-        // Return the donated amount to the donor
-        **donor.to_account_info().try_borrow_mut_lamports()? += deposit_pda.total_donated;
-        **campaign_pda.to_account_info().try_borrow_mut_lamports()? -= deposit_pda.total_donated;
+        let amount = tranche_amount(campaign_pda, vote.milestone);
+        match campaign_pda.mint {
+            None => {
+                **ctx
+                    .accounts
+                    .campaign_owner
+                    .to_account_info()
+                    .try_borrow_mut_lamports()? += amount;
+                **campaign_pda.to_account_info().try_borrow_mut_lamports()? -= amount;
+            }
+            Some(_) => {
+                let vault = ctx
+                    .accounts
+                    .vault
+                    .as_ref()
+                    .ok_or(CustomError::InvalidMint)?;
+                let owner_token_account = ctx
+                    .accounts
+                    .owner_token_account
+                    .as_ref()
+                    .ok_or(CustomError::InvalidMint)?;
+                let token_program = ctx
+                    .accounts
+                    .token_program
+                    .as_ref()
+                    .ok_or(CustomError::InvalidMint)?;
+                require_keys_eq!(
+                    owner_token_account.owner,
+                    campaign_pda.campaign_owner,
+                    CustomError::InvalidOwner
+                );
+                transfer_from_vault(
+                    campaign_pda,
+                    vault,
+                    owner_token_account,
+                    token_program,
+                    ctx.program_id,
+                    amount,
+                )?;
+            }
+        }
 
+        campaign_pda.released_amount += amount;
+        campaign_pda.released_milestones += 1;
+        if campaign_pda.released_milestones as usize == campaign_pda.milestones.len() {
+            campaign_pda.state = CampaignState::Withdrawn;
+        }
+        campaign_pda.request_deadline = Clock::get()?.slot + campaign_pda.request_slots;
         Ok(())
     }
 }
 
+fn init_campaign(
+    campaign_pda: &mut Account<CampaignPDA>,
+    campaign_owner: Pubkey,
+    campaign_name: String,
+    end_donate_slot: u64,
+    goal: u64,
+    mint: Option<Pubkey>,
+) -> Result<()> {
+    require!(goal > 0, CustomError::InvalidAmount);
+    require!(
+        Clock::get()?.slot < end_donate_slot,
+        CustomError::InvalidEndSlot
+    );
+
+    campaign_pda.campaign_name = campaign_name;
+    campaign_pda.campaign_owner = campaign_owner;
+    campaign_pda.created_slot = Clock::get()?.slot;
+    campaign_pda.end_donate_slot = end_donate_slot;
+    campaign_pda.goal_in_lamports = goal;
+    campaign_pda.total_raised = 0;
+    campaign_pda.mint = mint;
+    campaign_pda.state = CampaignState::Active;
+    campaign_pda.milestones = Vec::new();
+    campaign_pda.voting_slots = 0;
+    campaign_pda.request_slots = 0;
+    campaign_pda.request_deadline = 0;
+    campaign_pda.released_milestones = 0;
+    campaign_pda.released_amount = 0;
+    campaign_pda.vote = None;
+    Ok(())
+}
+
+fn set_campaign_terms(
+    campaign_pda: &mut Account<CampaignPDA>,
+    reward_tiers: Vec<u64>,
+    mode: CampaignMode,
+    stretch_goals: Vec<u64>,
+    hard_cap: Option<u64>,
+) -> Result<()> {
+    let goal = campaign_pda.goal_in_lamports;
+
+    // Thresholds must be positive and strictly increasing, tier 1 being the lowest one
+    require!(
+        reward_tiers.len() <= MAX_REWARD_TIERS
+            && reward_tiers.first() != Some(&0)
+            && reward_tiers.windows(2).all(|w| w[0] < w[1]),
+        CustomError::InvalidRewardTiers
+    );
+    if let CampaignMode::KeepWhatYouRaise {
+        fee_basis_points, ..
+    } = mode
+    {
+        require!(fee_basis_points <= 10_000, CustomError::InvalidFee);
+    }
+    if let Some(cap) = hard_cap {
+        require!(cap >= goal, CustomError::InvalidHardCap);
+    }
+    // Stretch goals must be strictly increasing, above the goal and within the hard cap
+    let above_goal = match stretch_goals.first() {
+        Some(first) => *first > goal,
+        None => true,
+    };
+    let within_cap = match (stretch_goals.last(), hard_cap) {
+        (Some(last), Some(cap)) => *last <= cap,
+        _ => true,
+    };
+    require!(
+        stretch_goals.len() <= MAX_STRETCH_GOALS
+            && above_goal
+            && within_cap
+            && stretch_goals.windows(2).all(|w| w[0] < w[1]),
+        CustomError::InvalidStretchGoals
+    );
+
+    campaign_pda.reward_tiers = reward_tiers;
+    campaign_pda.mode = mode;
+    campaign_pda.stretch_goals = stretch_goals;
+    campaign_pda.hard_cap = hard_cap;
+    Ok(())
+}
+
+fn record_donation(
+    campaign_pda: &mut Account<CampaignPDA>,
+    deposit_pda: &mut Account<DepositPDA>,
+    amount: u64,
+) -> Result<()> {
+    let raised_before = campaign_pda.total_raised;
+    let raised_after = raised_before + amount;
+    if let Some(cap) = campaign_pda.hard_cap {
+        require!(raised_after <= cap, CustomError::HardCapExceeded);
+    }
+
+    // The name of a closed campaign can be reused. A deposit left from the previous campaign
+    // has nothing to reclaim, since close_campaign requires the donations to be paid out or
+    // refunded, so it is reset for the new one
+    if deposit_pda.campaign_slot != campaign_pda.created_slot {
+        deposit_pda.campaign_slot = campaign_pda.created_slot;
+        deposit_pda.total_donated = 0;
+        deposit_pda.tier = 0;
+        deposit_pda.next_vote = 0;
+    }
+
+    deposit_pda.total_donated += amount;
+    deposit_pda.tier = reward_tier(&campaign_pda.reward_tiers, deposit_pda.total_donated);
+    campaign_pda.total_raised = raised_after;
+
+    for stretch_goal in campaign_pda.stretch_goals.iter() {
+        if raised_before < *stretch_goal && raised_after >= *stretch_goal {
+            emit!(StretchGoalReached {
+                campaign_name: campaign_pda.campaign_name.clone(),
+                stretch_goal: *stretch_goal,
+                total_raised: raised_after,
+            });
+        }
+    }
+    Ok(())
+}
+
+// Moves an active campaign to Succeeded or Failed once the donation period is over
+fn settle(campaign_pda: &mut CampaignPDA) -> Result<()> {
+    require!(
+        Clock::get()?.slot >= campaign_pda.end_donate_slot,
+        CustomError::TimeoutNotReached
+    );
+
+    if campaign_pda.state == CampaignState::Active {
+        let succeeded = match campaign_pda.mode {
+            CampaignMode::AllOrNothing => {
+                campaign_pda.total_raised >= campaign_pda.goal_in_lamports
+            }
+            CampaignMode::KeepWhatYouRaise { .. } => true,
+        };
+        campaign_pda.state = if succeeded {
+            CampaignState::Succeeded
+        } else {
+            CampaignState::Failed
+        };
+    }
+    Ok(())
+}
+
+// Checks that the owner can withdraw and returns the platform fee on the raised amount
+fn withdrawal_fee(campaign_pda: &CampaignPDA) -> Result<u64> {
+    match campaign_pda.state {
+        CampaignState::Succeeded => {}
+        CampaignState::Withdrawn => return err!(CustomError::AlreadyWithdrawn),
+        _ => return err!(CustomError::GoalNotReached),
+    }
+    require!(
+        campaign_pda.milestones.is_empty(),
+        CustomError::MilestoneCampaign
+    );
+
+    match campaign_pda.mode {
+        CampaignMode::AllOrNothing => Ok(0),
+        CampaignMode::KeepWhatYouRaise {
+            fee_basis_points, ..
+        } => Ok((campaign_pda.total_raised as u128 * fee_basis_points as u128 / 10_000) as u64),
+    }
+}
+
+// Donors can only reclaim from all-or-nothing campaigns that missed the goal
+fn check_reclaim(campaign_pda: &CampaignPDA) -> Result<()> {
+    require!(
+        campaign_pda.mode == CampaignMode::AllOrNothing,
+        CustomError::ReclaimNotAllowed
+    );
+    require!(
+        campaign_pda.state == CampaignState::Failed,
+        CustomError::GoalReached
+    );
+    Ok(())
+}
+
+// Updates the campaign for a refund and returns the amount due to the donor,
+// that is the donor's share of the donations not yet released to the owner
+fn record_refund(campaign_pda: &mut CampaignPDA, total_donated: u64) -> u64 {
+    let unreleased = campaign_pda.total_raised - campaign_pda.released_amount;
+    let refund =
+        (total_donated as u128 * unreleased as u128 / campaign_pda.total_raised as u128) as u64;
+
+    // The released share of the donor is removed too, keeping the proportion for the others
+    campaign_pda.released_amount -= total_donated - refund;
+    campaign_pda.total_raised -= total_donated;
+    refund
+}
+
+// The last tranche takes the remainder, so that rounding leaves nothing in the campaign
+fn tranche_amount(campaign_pda: &CampaignPDA, milestone: u8) -> u64 {
+    if milestone as usize == campaign_pda.milestones.len() - 1 {
+        campaign_pda.total_raised - campaign_pda.released_amount
+    } else {
+        (campaign_pda.total_raised as u128 * campaign_pda.milestones[milestone as usize] as u128
+            / 10_000) as u64
+    }
+}
+
+// Returns the highest tier reached by the donated amount, 0 if no threshold is reached
+fn reward_tier(reward_tiers: &[u64], total_donated: u64) -> u8 {
+    reward_tiers
+        .iter()
+        .take_while(|threshold| total_donated >= **threshold)
+        .count() as u8
+}
+
+// The vault is owned by the campaign_pda, so the transfer must be signed with its seeds
+fn transfer_from_vault<'info>(
+    campaign_pda: &Account<'info, CampaignPDA>,
+    vault: &Account<'info, TokenAccount>,
+    to: &Account<'info, TokenAccount>,
+    token_program: &Program<'info, Token>,
+    program_id: &Pubkey,
+    amount: u64,
+) -> Result<()> {
+    let (campaign_pda_key, campaign_bump) =
+        Pubkey::find_program_address(&[campaign_pda.campaign_name.as_bytes()], program_id);
+
+    let campaign_pda_signer_seeds: &[&[&[u8]]] =
+        &[&[campaign_pda.campaign_name.as_bytes(), &[campaign_bump]]];
+
+    anchor_lang::solana_program::program::invoke_signed(
+        &spl_token::instruction::transfer(
+            &anchor_spl::token::ID,
+            &vault.key(),
+            &to.key(),
+            &campaign_pda_key, //owner
+            &[&campaign_pda_key],
+            amount,
+        )?,
+        &[
+            vault.to_account_info(),
+            to.to_account_info(),
+            campaign_pda.to_account_info(),
+            token_program.to_account_info(),
+        ],
+        campaign_pda_signer_seeds,
+    )?;
+
+    Ok(())
+}
+
+// The vault is emptied by withdraw or reclaim, its rent goes back to the owner
+fn close_vault<'info>(
+    campaign_pda: &Account<'info, CampaignPDA>,
+    vault: &Account<'info, TokenAccount>,
+    campaign_owner: &Signer<'info>,
+    token_program: &Program<'info, Token>,
+    program_id: &Pubkey,
+) -> Result<()> {
+    let (campaign_pda_key, campaign_bump) =
+        Pubkey::find_program_address(&[campaign_pda.campaign_name.as_bytes()], program_id);
+
+    let campaign_pda_signer_seeds: &[&[&[u8]]] =
+        &[&[campaign_pda.campaign_name.as_bytes(), &[campaign_bump]]];
+
+    anchor_lang::solana_program::program::invoke_signed(
+        &spl_token::instruction::close_account(
+            &anchor_spl::token::ID,
+            &vault.key(),
+            &campaign_owner.key(),
+            &campaign_pda_key,
+            &[&campaign_pda_key],
+        )?,
+        &[
+            vault.to_account_info(),
+            campaign_owner.to_account_info(),
+            campaign_pda.to_account_info(),
+            token_program.to_account_info(),
+        ],
+        campaign_pda_signer_seeds,
+    )?;
+
+    Ok(())
+}
+
 #[derive(Accounts)]
 #[instruction(campaign_name: String)]
 pub struct InitializeCtx<'info> {
@@ -137,6 +803,35 @@ pub struct InitializeCtx<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+#[instruction(campaign_name: String)]
+pub struct InitializeTokenCtx<'info> {
+    #[account(mut)]
+    pub campaign_owner: Signer<'info>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = campaign_owner,
+        seeds = [campaign_name.as_ref()],
+        bump,
+        space = 8 + CampaignPDA::INIT_SPACE
+    )]
+    pub campaign_pda: Account<'info, CampaignPDA>,
+    #[account(
+        init,
+        payer = campaign_owner,
+        seeds = ["vault".as_ref(), campaign_name.as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = campaign_pda
+    )]
+    pub vault: Account<'info, TokenAccount>,
+    // Programs and other
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
 #[derive(Accounts)]
 #[instruction(_campaign_name: String)]
 pub struct DonateCtx<'info> {
@@ -160,7 +855,28 @@ pub struct DonateCtx<'info> {
 pub struct WithdrawCtx<'info> {
     #[account(mut)]
     pub campaign_owner: Signer<'info>,
-    #[account(mut, seeds = [_campaign_name.as_ref()], bump )]
+    #[account(
+        mut,
+        seeds = [_campaign_name.as_ref()],
+        bump,
+        constraint = campaign_pda.campaign_owner == *campaign_owner.key @ CustomError::InvalidOwner
+    )]
+    pub campaign_pda: Account<'info, CampaignPDA>,
+    #[account(mut)]
+    pub fee_recipient: Option<SystemAccount<'info>>, // only for keep-what-you-raise campaigns
+}
+
+#[derive(Accounts)]
+#[instruction(_campaign_name: String)]
+pub struct SweepCtx<'info> {
+    #[account(mut)]
+    pub campaign_owner: Signer<'info>,
+    #[account(
+        mut, 
+        seeds = [_campaign_name.as_ref()],
+        bump,
+        constraint = campaign_pda.campaign_owner == *campaign_owner.key @ CustomError::InvalidOwner
+    )]
     pub campaign_pda: Account<'info, CampaignPDA>,
 }
 
@@ -175,8 +891,209 @@ pub struct ReclaimCtx<'info> {
         mut, 
         seeds = ["deposit".as_ref(), _campaign_name.as_ref(), donor.key().as_ref()],
         bump,
+        constraint = deposit_pda.campaign_slot == campaign_pda.created_slot @ CustomError::StaleDeposit,
+        close = donor
+    )]
+    pub deposit_pda: Account<'info, DepositPDA>,
+}
+
+#[derive(Accounts)]
+#[instruction(_campaign_name: String)]
+pub struct DonateTokenCtx<'info> {
+    #[account(mut)]
+    pub donor: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [_campaign_name.as_ref()],
+        bump,
+        constraint = campaign_pda.mint == Some(mint.key()) @ CustomError::InvalidMint
+    )]
+    pub campaign_pda: Account<'info, CampaignPDA>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        constraint = donor_token_account.mint == mint.key() @ CustomError::InvalidMint
+    )]
+    pub donor_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = ["vault".as_ref(), _campaign_name.as_ref()],
+        bump
+    )]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = donor,
+        seeds = ["deposit".as_ref(), _campaign_name.as_ref(), donor.key().as_ref()],
+        bump,
+        space = 8 + DepositPDA::INIT_SPACE
+    )]
+    pub deposit_pda: Account<'info, DepositPDA>,
+    // Programs and other
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(_campaign_name: String)]
+pub struct WithdrawTokenCtx<'info> {
+    pub campaign_owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [_campaign_name.as_ref()],
+        bump,
+        constraint = campaign_pda.campaign_owner == *campaign_owner.key @ CustomError::InvalidOwner,
+        constraint = campaign_pda.mint == Some(mint.key()) @ CustomError::InvalidMint
+    )]
+    pub campaign_pda: Account<'info, CampaignPDA>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        constraint = owner_token_account.mint == mint.key() @ CustomError::InvalidMint
+    )]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    // only for keep-what-you-raise campaigns
+    #[account(
+        mut,
+        constraint = fee_recipient_token_account.mint == mint.key() @ CustomError::InvalidMint
+    )]
+    pub fee_recipient_token_account: Option<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = ["vault".as_ref(), _campaign_name.as_ref()],
+        bump
+    )]
+    pub vault: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(_campaign_name: String)]
+pub struct ReclaimTokenCtx<'info> {
+    #[account(mut)]
+    pub donor: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [_campaign_name.as_ref()],
+        bump,
+        constraint = campaign_pda.mint == Some(mint.key()) @ CustomError::InvalidMint
+    )]
+    pub campaign_pda: Account<'info, CampaignPDA>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        constraint = donor_token_account.mint == mint.key() @ CustomError::InvalidMint
+    )]
+    pub donor_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = ["vault".as_ref(), _campaign_name.as_ref()],
+        bump
+    )]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = ["deposit".as_ref(), _campaign_name.as_ref(), donor.key().as_ref()],
+        bump,
+        constraint = deposit_pda.campaign_slot == campaign_pda.created_slot @ CustomError::StaleDeposit,
+        close = donor
     )]
     pub deposit_pda: Account<'info, DepositPDA>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(_campaign_name: String)]
+pub struct CloseCampaignCtx<'info> {
+    #[account(mut)]
+    pub campaign_owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [_campaign_name.as_ref()],
+        bump,
+        constraint = campaign_pda.campaign_owner == *campaign_owner.key @ CustomError::InvalidOwner,
+        close = campaign_owner
+    )]
+    pub campaign_pda: Account<'info, CampaignPDA>,
+    // only for token campaigns
+    #[account(
+        mut,
+        seeds = ["vault".as_ref(), _campaign_name.as_ref()],
+        bump
+    )]
+    pub vault: Option<Account<'info, TokenAccount>>,
+    pub token_program: Option<Program<'info, Token>>,
+}
+
+#[derive(Accounts)]
+#[instruction(_campaign_name: String)]
+pub struct EnableMilestonesCtx<'info> {
+    pub campaign_owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [_campaign_name.as_ref()],
+        bump,
+        constraint = campaign_pda.campaign_owner == *campaign_owner.key @ CustomError::InvalidOwner
+    )]
+    pub campaign_pda: Account<'info, CampaignPDA>,
+}
+
+#[derive(Accounts)]
+#[instruction(_campaign_name: String)]
+pub struct RequestMilestoneCtx<'info> {
+    pub campaign_owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [_campaign_name.as_ref()],
+        bump,
+        constraint = campaign_pda.campaign_owner == *campaign_owner.key @ CustomError::InvalidOwner
+    )]
+    pub campaign_pda: Account<'info, CampaignPDA>,
+}
+
+#[derive(Accounts)]
+#[instruction(_campaign_name: String)]
+pub struct ExpireMilestoneCtx<'info> {
+    #[account(mut, seeds = [_campaign_name.as_ref()], bump)]
+    pub campaign_pda: Account<'info, CampaignPDA>,
+}
+
+#[derive(Accounts)]
+#[instruction(_campaign_name: String)]
+pub struct VoteCtx<'info> {
+    pub donor: Signer<'info>,
+    #[account(mut, seeds = [_campaign_name.as_ref()], bump)]
+    pub campaign_pda: Account<'info, CampaignPDA>,
+    #[account(
+        mut,
+        seeds = ["deposit".as_ref(), _campaign_name.as_ref(), donor.key().as_ref()],
+        bump,
+        constraint = deposit_pda.campaign_slot == campaign_pda.created_slot @ CustomError::StaleDeposit
+    )]
+    pub deposit_pda: Account<'info, DepositPDA>,
+}
+
+#[derive(Accounts)]
+#[instruction(_campaign_name: String)]
+pub struct ReleaseMilestoneCtx<'info> {
+    /// CHECK: only receives the released lamports, checked against the campaign owner
+    #[account(
+        mut,
+        constraint = campaign_pda.campaign_owner == *campaign_owner.key @ CustomError::InvalidOwner
+    )]
+    pub campaign_owner: UncheckedAccount<'info>,
+    #[account(mut, seeds = [_campaign_name.as_ref()], bump)]
+    pub campaign_pda: Account<'info, CampaignPDA>,
+    // only for token campaigns
+    #[account(
+        mut,
+        seeds = ["vault".as_ref(), _campaign_name.as_ref()],
+        bump
+    )]
+    pub vault: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub owner_token_account: Option<Account<'info, TokenAccount>>,
+    pub token_program: Option<Program<'info, Token>>,
 }
 
 #[account]
@@ -185,14 +1102,67 @@ pub struct CampaignPDA {
     #[max_len(30)]
     pub campaign_name: String,
     pub campaign_owner: Pubkey, // 32 bytes
+    pub created_slot: u64,      // 8 bytes, tells apart the campaigns reusing the same name
     pub end_donate_slot: u64,   // 8 bytes
-    pub goal_in_lamports: u64,  // 8 bytes
+    pub goal_in_lamports: u64,  // 8 bytes, in base units of the mint for token campaigns
+    pub total_raised: u64,      // 8 bytes
+    pub mint: Option<Pubkey>,   // 1 + 32 bytes, None for lamport campaigns
+    #[max_len(MAX_REWARD_TIERS)]
+    pub reward_tiers: Vec<u64>, // 4 + 8 * MAX_REWARD_TIERS bytes
+    pub mode: CampaignMode,
+    #[max_len(MAX_STRETCH_GOALS)]
+    pub stretch_goals: Vec<u64>, // 4 + 8 * MAX_STRETCH_GOALS bytes
+    pub hard_cap: Option<u64>, // 1 + 8 bytes
+    pub state: CampaignState,
+    #[max_len(MAX_MILESTONES)]
+    pub milestones: Vec<u16>, // 4 + 2 * MAX_MILESTONES bytes, empty if disbursed at once
+    pub voting_slots: u64,       // 8 bytes
+    pub request_slots: u64,      // 8 bytes
+    pub request_deadline: u64,   // 8 bytes, last slot to request the next tranche
+    pub released_milestones: u8, // 1 byte
+    pub released_amount: u64,    // 8 bytes
+    pub vote: Option<MilestoneVote>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Clone, InitSpace)]
+pub struct MilestoneVote {
+    pub milestone: u8,       // index of the requested tranche
+    pub deadline: u64,       // last slot to vote
+    pub approve_weight: u64, // donated amount voting in favour
+    pub reject_weight: u64,  // donated amount voting against
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Clone, InitSpace)]
+pub enum CampaignState {
+    Active,    // donations are open
+    Succeeded, // the owner can withdraw
+    Failed,    // the donors can reclaim
+    Withdrawn, // the donations were paid out to the owner
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Clone, InitSpace)]
+pub enum CampaignMode {
+    AllOrNothing,
+    KeepWhatYouRaise {
+        fee_basis_points: u16, // of the raised amount, paid at withdraw
+        fee_recipient: Pubkey,
+    },
+}
+
+#[event]
+pub struct StretchGoalReached {
+    campaign_name: String,
+    stretch_goal: u64,
+    total_raised: u64,
 }
 
 #[account]
 #[derive(InitSpace)]
 pub struct DepositPDA {
-    pub total_donated: u64, // 8 bytes
+    pub campaign_slot: u64, // 8 bytes, created_slot of the campaign the donations belong to
+    pub total_donated: u64, // 8 bytes, lamports or base units of the campaign mint
+    pub tier: u8,           // 1 byte, 0 if no reward tier was reached
+    pub next_vote: u8,      // 1 byte, first milestone the donor has not voted on
 }
 
 #[error_code]
@@ -214,4 +1184,78 @@ pub enum CustomError {
 
     #[msg("The goal was reached")]
     GoalReached,
+
+    #[msg("Invalid campaign owner")]
+    InvalidOwner,
+
+    #[msg("There are no unsolicited lamports to sweep")]
+    NothingToSweep,
+
+    #[msg("Invalid mint")]
+    InvalidMint,
+
+    #[msg("Invalid reward tiers, thresholds must be positive and strictly increasing")]
+    InvalidRewardTiers,
+
+    #[msg("Invalid fee, must be at most 10000 basis points")]
+    InvalidFee,
+
+    #[msg("Invalid fee recipient")]
+    InvalidFeeRecipient,
+
+    #[msg("Invalid hard cap, must be greater than or equal to the goal")]
+    InvalidHardCap,
+
+    #[msg(
+        "Invalid stretch goals, must be strictly increasing and between the goal and the hard cap"
+    )]
+    InvalidStretchGoals,
+
+    #[msg("The donation exceeds the hard cap")]
+    HardCapExceeded,
+
+    #[msg("Reclaim is only allowed for failed all-or-nothing campaigns")]
+    ReclaimNotAllowed,
+
+    #[msg("The donations were already withdrawn")]
+    AlreadyWithdrawn,
+
+    #[msg("The campaign can be closed only after the withdraw or after all donors have reclaimed")]
+    CampaignNotClosable,
+
+    #[msg("Invalid milestones, shares must be positive and sum to 10000 basis points")]
+    InvalidMilestones,
+
+    #[msg("Milestones cannot be changed after the first donation")]
+    MilestonesLocked,
+
+    #[msg("Milestone campaigns are paid out through milestone votes")]
+    MilestoneCampaign,
+
+    #[msg("All the milestones were already released")]
+    NoMilestonesLeft,
+
+    #[msg("A milestone vote is already in progress")]
+    VoteInProgress,
+
+    #[msg("There is no milestone vote in progress")]
+    NoVoteInProgress,
+
+    #[msg("The voting period is over")]
+    VotingClosed,
+
+    #[msg("The voting period is not over yet")]
+    VotingNotClosed,
+
+    #[msg("The donor already voted on this milestone")]
+    AlreadyVoted,
+
+    #[msg("The deposit belongs to a previous campaign with the same name")]
+    StaleDeposit,
+
+    #[msg("The deadline to request the next milestone has passed")]
+    RequestDeadlinePassed,
+
+    #[msg("The deadline to request the next milestone has not passed yet")]
+    RequestDeadlineNotPassed,
 }
@@ -1,15 +1,33 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
-import { PublicKey, Keypair, SystemProgram, LAMPORTS_PER_SOL } from "@solana/web3.js";
+import { PublicKey, Keypair, SystemProgram, Transaction, LAMPORTS_PER_SOL } from "@solana/web3.js";
+import {
+  TOKEN_PROGRAM_ID,
+  createMint,
+  mintTo,
+  getOrCreateAssociatedTokenAccount,
+  getAccount,
+} from "@solana/spl-token";
 import { expect } from "chai";
 import BN from "bn.js";
 
 interface CrowdfundProgram extends Program {
   methods: {
-    initialize(name: string, endSlot: BN, goal: BN): any;
+    initialize(name: string, endSlot: BN, goal: BN, rewardTiers: BN[], mode: any, stretchGoals: BN[], hardCap: BN | null): any;
     donate(name: string, amount: BN): any;
     withdraw(name: string): any;
     reclaim(name: string): any;
+    sweep(name: string): any;
+    initializeToken(name: string, endSlot: BN, goal: BN, rewardTiers: BN[], mode: any, stretchGoals: BN[], hardCap: BN | null): any;
+    donateToken(name: string, amount: BN): any;
+    withdrawToken(name: string): any;
+    reclaimToken(name: string): any;
+    closeCampaign(name: string): any;
+    enableMilestones(name: string, milestones: number[], votingSlots: BN, requestSlots: BN): any;
+    requestMilestone(name: string): any;
+    expireMilestone(name: string): any;
+    vote(name: string, approve: boolean): any;
+    releaseMilestone(name: string): any;
   };
 }
 
@@ -29,6 +47,8 @@ describe("Crowdfund Program - Universal Test Suite", () => {
   // Test data
   const campaignName = "test-campaign";
   const goalAmount = new BN(5 * LAMPORTS_PER_SOL);
+  const rewardTiers = [new BN(1 * LAMPORTS_PER_SOL), new BN(3 * LAMPORTS_PER_SOL)];
+  const allOrNothing = { allOrNothing: {} };
   let endSlot: BN;
   
   // PDAs
@@ -74,7 +94,7 @@ describe("Crowdfund Program - Universal Test Suite", () => {
   describe("initialize()", () => {
     it("creates campaign with valid parameters", async () => {
       const tx = await program.methods
-        .initialize(campaignName, endSlot, goalAmount)
+        .initialize(campaignName, endSlot, goalAmount, rewardTiers, allOrNothing, [], null)
         .accounts({
           campaignOwner: campaignOwner.publicKey,
           campaignPda,
@@ -90,6 +110,37 @@ describe("Crowdfund Program - Universal Test Suite", () => {
       expect(campaignAccount.campaignName).to.equal(campaignName);
       expect(campaignAccount.campaignOwner.toString()).to.equal(campaignOwner.publicKey.toString());
       expect(campaignAccount.goalInLamports.toString()).to.equal(goalAmount.toString());
+      expect(campaignAccount.totalRaised.toNumber()).to.equal(0);
+      expect(campaignAccount.rewardTiers.map((t) => t.toString())).to.deep.equal(
+        rewardTiers.map((t) => t.toString())
+      );
+    });
+
+    it("rejects reward tiers that are not strictly increasing", async () => {
+      const invalidTiersCampaignName = "invalid-tiers";
+      const [invalidTiersCampaignPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from(invalidTiersCampaignName)],
+        program.programId
+      );
+
+      try {
+        await program.methods
+          .initialize(invalidTiersCampaignName, endSlot, goalAmount, [
+            new BN(3 * LAMPORTS_PER_SOL),
+            new BN(1 * LAMPORTS_PER_SOL),
+          ], allOrNothing, [], null)
+          .accounts({
+            campaignOwner: campaignOwner.publicKey,
+            campaignPda: invalidTiersCampaignPda,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([campaignOwner])
+          .rpc();
+
+        expect.fail("Should have thrown an error");
+      } catch (error) {
+        expect(error).to.exist;
+      }
     });
 
     it("rejects zero goal amount", async () => {
@@ -102,7 +153,7 @@ describe("Crowdfund Program - Universal Test Suite", () => {
       
       try {
         await program.methods
-          .initialize(invalidCampaignName, endSlot, zeroGoal)
+          .initialize(invalidCampaignName, endSlot, zeroGoal, [], allOrNothing, [], null)
           .accounts({
             campaignOwner: campaignOwner.publicKey,
             campaignPda: invalidCampaignPda,
@@ -127,7 +178,7 @@ describe("Crowdfund Program - Universal Test Suite", () => {
       
       try {
         await program.methods
-          .initialize(pastCampaignName, pastSlot, goalAmount)
+          .initialize(pastCampaignName, pastSlot, goalAmount, [], allOrNothing, [], null)
           .accounts({
             campaignOwner: campaignOwner.publicKey,
             campaignPda: pastCampaignPda,
@@ -232,6 +283,14 @@ describe("Crowdfund Program - Universal Test Suite", () => {
       expect(donor2DepositAccount.totalDonated.toString()).to.equal(donor2Donation.toString());
     });
 
+    it("records the reward tier reached by each donor", async () => {
+      // donor1 donated 3 SOL in total, donor2 donated 2 SOL
+      const donor1DepositAccount = await program.account.depositPda.fetch(donor1DepositPda);
+      const donor2DepositAccount = await program.account.depositPda.fetch(donor2DepositPda);
+      expect(donor1DepositAccount.tier).to.equal(2);
+      expect(donor2DepositAccount.tier).to.equal(1);
+    });
+
     it("tracks donation amounts correctly", async () => {
       const campaignBalance = await provider.connection.getBalance(campaignPda);
       const rentExemption = await provider.connection.getMinimumBalanceForRentExemption(
@@ -251,6 +310,7 @@ describe("Crowdfund Program - Universal Test Suite", () => {
           .accounts({
             campaignOwner: campaignOwner.publicKey,
             campaignPda,
+            feeRecipient: null,
           })
           .signers([campaignOwner])
           .rpc();
@@ -274,7 +334,7 @@ describe("Crowdfund Program - Universal Test Suite", () => {
       
       // Create and fund a campaign that will expire soon
       await program.methods
-        .initialize(expiredCampaignName, expiredEndSlot, new BN(1 * LAMPORTS_PER_SOL))
+        .initialize(expiredCampaignName, expiredEndSlot, new BN(1 * LAMPORTS_PER_SOL), [], allOrNothing, [], null)
         .accounts({
           campaignOwner: campaignOwner.publicKey,
           campaignPda: expiredCampaignPda,
@@ -303,20 +363,22 @@ describe("Crowdfund Program - Universal Test Suite", () => {
       // Wait for campaign to end
       await waitForSlot(expiredEndSlot.toNumber() + 1);
       
+      let error: any;
       try {
         await program.methods
           .withdraw(expiredCampaignName)
           .accounts({
             campaignOwner: donor1.publicKey, // Wrong owner
             campaignPda: expiredCampaignPda,
+            feeRecipient: null,
           })
           .signers([donor1])
           .rpc();
-        
-        expect.fail("Should have thrown an error");
-      } catch (error) {
-        expect(error).to.exist;
+      } catch (e) {
+        error = e;
       }
+      expect(error, "Should have thrown an error").to.exist;
+      expect(error.error.errorCode.code).to.equal("InvalidOwner");
     });
 
     it("allows owner withdrawal after deadline when goal is met", async () => {
@@ -332,7 +394,7 @@ describe("Crowdfund Program - Universal Test Suite", () => {
       
       // Create campaign
       await program.methods
-        .initialize(withdrawCampaignName, withdrawEndSlot, new BN(1 * LAMPORTS_PER_SOL))
+        .initialize(withdrawCampaignName, withdrawEndSlot, new BN(1 * LAMPORTS_PER_SOL), [], allOrNothing, [], null)
         .accounts({
           campaignOwner: campaignOwner.publicKey,
           campaignPda: withdrawCampaignPda,
@@ -369,6 +431,7 @@ describe("Crowdfund Program - Universal Test Suite", () => {
         .accounts({
           campaignOwner: campaignOwner.publicKey,
           campaignPda: withdrawCampaignPda,
+          feeRecipient: null,
         })
         .signers([campaignOwner])
         .rpc();
@@ -379,6 +442,66 @@ describe("Crowdfund Program - Universal Test Suite", () => {
       // Verify funds transferred to owner
       expect(finalOwnerBalance).to.be.greaterThan(initialOwnerBalance);
       expect(finalCampaignBalance).to.be.lessThan(initialCampaignBalance);
+
+      const campaignAccount = await program.account.campaignPda.fetch(withdrawCampaignPda);
+      expect(campaignAccount.state).to.deep.equal({ withdrawn: {} });
+
+      // The donations cannot be withdrawn twice
+      try {
+        await program.methods
+          .withdraw(withdrawCampaignName)
+          .accounts({
+            campaignOwner: campaignOwner.publicKey,
+            campaignPda: withdrawCampaignPda,
+            feeRecipient: null,
+          })
+          .signers([campaignOwner])
+          .rpc();
+
+        expect.fail("Should have thrown an error");
+      } catch (error) {
+        expect(error).to.exist;
+      }
+
+      // Closing returns the rent to the owner and removes the campaign
+      await program.methods
+        .closeCampaign(withdrawCampaignName)
+        .accounts({
+          campaignOwner: campaignOwner.publicKey,
+          campaignPda: withdrawCampaignPda,
+          vault: null,
+          tokenProgram: null,
+        })
+        .signers([campaignOwner])
+        .rpc();
+
+      expect(await program.account.campaignPda.fetchNullable(withdrawCampaignPda)).to.be.null;
+
+      // A new campaign with the same name does not inherit the deposits of the closed one
+      const reusedEndSlot = new BN((await provider.connection.getSlot()) + 20);
+      await program.methods
+        .initialize(withdrawCampaignName, reusedEndSlot, new BN(5 * LAMPORTS_PER_SOL), [], allOrNothing, [], null)
+        .accounts({
+          campaignOwner: campaignOwner.publicKey,
+          campaignPda: withdrawCampaignPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([campaignOwner])
+        .rpc();
+
+      await program.methods
+        .donate(withdrawCampaignName, new BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          donor: donor1.publicKey,
+          campaignPda: withdrawCampaignPda,
+          depositPda: withdrawDonorDepositPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([donor1])
+        .rpc();
+
+      const depositAccount = await program.account.depositPda.fetch(withdrawDonorDepositPda);
+      expect(depositAccount.totalDonated.toString()).to.equal(new BN(1 * LAMPORTS_PER_SOL).toString());
     });
   });
 
@@ -406,7 +529,7 @@ describe("Crowdfund Program - Universal Test Suite", () => {
       
       // Create failed campaign
       await program.methods
-        .initialize(failedCampaignName, shortEndSlot, lowGoal)
+        .initialize(failedCampaignName, shortEndSlot, lowGoal, [], allOrNothing, [], null)
         .accounts({
           campaignOwner: campaignOwner.publicKey,
           campaignPda: failedCampaignPda,
@@ -454,6 +577,30 @@ describe("Crowdfund Program - Universal Test Suite", () => {
       
       // Verify funds returned to donor
       expect(finalDonorBalance).to.be.greaterThan(initialDonorBalance);
+
+      // The deposit is closed and the campaign is marked as failed
+      expect(await program.account.depositPda.fetchNullable(failedDonorDepositPda)).to.be.null;
+      const campaignAccount = await program.account.campaignPda.fetch(failedCampaignPda);
+      expect(campaignAccount.state).to.deep.equal({ failed: {} });
+      expect(campaignAccount.totalRaised.toNumber()).to.equal(0);
+    });
+
+    it("rejects a second reclaim by the same donor", async () => {
+      try {
+        await program.methods
+          .reclaim(failedCampaignName)
+          .accounts({
+            donor: donor1.publicKey,
+            campaignPda: failedCampaignPda,
+            depositPda: failedDonorDepositPda,
+          })
+          .signers([donor1])
+          .rpc();
+
+        expect.fail("Should have thrown an error");
+      } catch (error) {
+        expect(error).to.exist;
+      }
     });
 
     it("rejects reclaim by non-donor", async () => {
@@ -494,6 +641,744 @@ describe("Crowdfund Program - Universal Test Suite", () => {
     });
   });
 
+  describe("sweep() - Unsolicited Lamports", () => {
+    const sweepCampaignName = "sweep-campaign";
+    const donation = new BN(1 * LAMPORTS_PER_SOL);
+    const unsolicited = 2 * LAMPORTS_PER_SOL;
+    let sweepCampaignPda: PublicKey;
+    let sweepDonorDepositPda: PublicKey;
+
+    before(async () => {
+      const currentSlot = await provider.connection.getSlot();
+
+      [sweepCampaignPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from(sweepCampaignName)],
+        program.programId
+      );
+
+      [sweepDonorDepositPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("deposit"), Buffer.from(sweepCampaignName), donor1.publicKey.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .initialize(sweepCampaignName, new BN(currentSlot + 50), new BN(2 * LAMPORTS_PER_SOL), [], allOrNothing, [], null)
+        .accounts({
+          campaignOwner: campaignOwner.publicKey,
+          campaignPda: sweepCampaignPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([campaignOwner])
+        .rpc();
+
+      await program.methods
+        .donate(sweepCampaignName, donation)
+        .accounts({
+          donor: donor1.publicKey,
+          campaignPda: sweepCampaignPda,
+          depositPda: sweepDonorDepositPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([donor1])
+        .rpc();
+
+      // Send lamports directly to the campaign, bypassing donate()
+      const tx = new Transaction().add(
+        SystemProgram.transfer({
+          fromPubkey: donor2.publicKey,
+          toPubkey: sweepCampaignPda,
+          lamports: unsolicited,
+        })
+      );
+      await provider.sendAndConfirm(tx, [donor2]);
+    });
+
+    it("does not count direct transfers towards the goal", async () => {
+      const campaignAccount = await program.account.campaignPda.fetch(sweepCampaignPda);
+      expect(campaignAccount.totalRaised.toString()).to.equal(donation.toString());
+      expect(campaignAccount.totalRaised.lt(campaignAccount.goalInLamports)).to.be.true;
+    });
+
+    it("rejects sweep by non-owner", async () => {
+      try {
+        await program.methods
+          .sweep(sweepCampaignName)
+          .accounts({
+            campaignOwner: donor2.publicKey, // Wrong owner
+            campaignPda: sweepCampaignPda,
+          })
+          .signers([donor2])
+          .rpc();
+
+        expect.fail("Should have thrown an error");
+      } catch (error) {
+        expect(error).to.exist;
+      }
+    });
+
+    it("allows owner to sweep unsolicited lamports only", async () => {
+      const initialOwnerBalance = await provider.connection.getBalance(campaignOwner.publicKey);
+      const initialCampaignBalance = await provider.connection.getBalance(sweepCampaignPda);
+
+      await program.methods
+        .sweep(sweepCampaignName)
+        .accounts({
+          campaignOwner: campaignOwner.publicKey,
+          campaignPda: sweepCampaignPda,
+        })
+        .signers([campaignOwner])
+        .rpc();
+
+      const finalOwnerBalance = await provider.connection.getBalance(campaignOwner.publicKey);
+      const finalCampaignBalance = await provider.connection.getBalance(sweepCampaignPda);
+
+      // Donations stay in the campaign
+      expect(initialCampaignBalance - finalCampaignBalance).to.equal(unsolicited);
+      expect(finalOwnerBalance).to.be.greaterThan(initialOwnerBalance);
+    });
+
+    it("rejects sweep when there is nothing to sweep", async () => {
+      try {
+        await program.methods
+          .sweep(sweepCampaignName)
+          .accounts({
+            campaignOwner: campaignOwner.publicKey,
+            campaignPda: sweepCampaignPda,
+          })
+          .signers([campaignOwner])
+          .rpc();
+
+        expect.fail("Should have thrown an error");
+      } catch (error) {
+        expect(error).to.exist;
+      }
+    });
+  });
+
+  describe("token campaigns", () => {
+    const DECIMALS = 6;
+    let mint: PublicKey;
+    let ownerTokenAccount: PublicKey;
+    let donor1TokenAccount: PublicKey;
+    let donor2TokenAccount: PublicKey;
+
+    const tokenAmount = (units: number) => new BN(units * Math.pow(10, DECIMALS));
+
+    function deriveTokenCampaign(name: string, donor: PublicKey) {
+      const [pda] = PublicKey.findProgramAddressSync([Buffer.from(name)], program.programId);
+      const [vault] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), Buffer.from(name)],
+        program.programId
+      );
+      const [deposit] = PublicKey.findProgramAddressSync(
+        [Buffer.from("deposit"), Buffer.from(name), donor.toBuffer()],
+        program.programId
+      );
+      return { pda, vault, deposit };
+    }
+
+    async function initializeTokenCampaign(name: string, endSlot: BN, goal: BN, rewardTiers: BN[] = []) {
+      const { pda, vault } = deriveTokenCampaign(name, donor1.publicKey);
+      await program.methods
+        .initializeToken(name, endSlot, goal, rewardTiers, allOrNothing, [], null)
+        .accounts({
+          campaignOwner: campaignOwner.publicKey,
+          mint,
+          campaignPda: pda,
+          vault,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([campaignOwner])
+        .rpc();
+    }
+
+    async function donateToken(name: string, donor: Keypair, donorTokenAccount: PublicKey, amount: BN) {
+      const { pda, vault, deposit } = deriveTokenCampaign(name, donor.publicKey);
+      await program.methods
+        .donateToken(name, amount)
+        .accounts({
+          donor: donor.publicKey,
+          campaignPda: pda,
+          mint,
+          donorTokenAccount,
+          vault,
+          depositPda: deposit,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([donor])
+        .rpc();
+    }
+
+    before(async () => {
+      mint = await createMint(
+        provider.connection,
+        donor1,
+        donor1.publicKey,
+        null,
+        DECIMALS
+      );
+
+      ownerTokenAccount = (
+        await getOrCreateAssociatedTokenAccount(provider.connection, donor1, mint, campaignOwner.publicKey)
+      ).address;
+      donor1TokenAccount = (
+        await getOrCreateAssociatedTokenAccount(provider.connection, donor1, mint, donor1.publicKey)
+      ).address;
+      donor2TokenAccount = (
+        await getOrCreateAssociatedTokenAccount(provider.connection, donor1, mint, donor2.publicKey)
+      ).address;
+
+      await mintTo(provider.connection, donor1, mint, donor1TokenAccount, donor1, tokenAmount(1000).toNumber());
+      await mintTo(provider.connection, donor1, mint, donor2TokenAccount, donor1, tokenAmount(1000).toNumber());
+    });
+
+    it("moves donations into the vault and tracks base units", async () => {
+      const name = "token-campaign";
+      const currentSlot = await provider.connection.getSlot();
+      const tokenEndSlot = new BN(currentSlot + 10);
+      const { pda, vault, deposit } = deriveTokenCampaign(name, donor1.publicKey);
+
+      await initializeTokenCampaign(name, tokenEndSlot, tokenAmount(100));
+      await donateToken(name, donor1, donor1TokenAccount, tokenAmount(60));
+      await donateToken(name, donor2, donor2TokenAccount, tokenAmount(50));
+
+      const vaultAccount = await getAccount(provider.connection, vault);
+      expect(vaultAccount.amount.toString()).to.equal(tokenAmount(110).toString());
+
+      const depositAccount = await program.account.depositPda.fetch(deposit);
+      expect(depositAccount.totalDonated.toString()).to.equal(tokenAmount(60).toString());
+
+      const campaignAccount = await program.account.campaignPda.fetch(pda);
+      expect(campaignAccount.mint.toBase58()).to.equal(mint.toBase58());
+      expect(campaignAccount.totalRaised.toString()).to.equal(tokenAmount(110).toString());
+
+      // Lamport donations are rejected on token campaigns
+      try {
+        await program.methods
+          .donate(name, new BN(1 * LAMPORTS_PER_SOL))
+          .accounts({
+            donor: donor1.publicKey,
+            campaignPda: pda,
+            depositPda: deposit,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([donor1])
+          .rpc();
+
+        expect.fail("Should have thrown an error");
+      } catch (error) {
+        expect(error).to.exist;
+      }
+
+      await waitForSlot(tokenEndSlot.toNumber() + 1);
+
+      await program.methods
+        .withdrawToken(name)
+        .accounts({
+          campaignOwner: campaignOwner.publicKey,
+          campaignPda: pda,
+          mint,
+          ownerTokenAccount,
+          feeRecipientTokenAccount: null,
+          vault,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([campaignOwner])
+        .rpc();
+
+      const ownerAccount = await getAccount(provider.connection, ownerTokenAccount);
+      expect(ownerAccount.amount.toString()).to.equal(tokenAmount(110).toString());
+    });
+
+    it("returns tokens to donors when the goal is not reached", async () => {
+      const name = "token-failed";
+      const currentSlot = await provider.connection.getSlot();
+      const tokenEndSlot = new BN(currentSlot + 5);
+      const { pda, vault, deposit } = deriveTokenCampaign(name, donor2.publicKey);
+
+      await initializeTokenCampaign(name, tokenEndSlot, tokenAmount(500), [tokenAmount(10)]);
+      await donateToken(name, donor2, donor2TokenAccount, tokenAmount(20));
+      expect((await program.account.depositPda.fetch(deposit)).tier).to.equal(1);
+
+      const balanceAfterDonation = (await getAccount(provider.connection, donor2TokenAccount)).amount;
+
+      await waitForSlot(tokenEndSlot.toNumber() + 1);
+
+      await program.methods
+        .reclaimToken(name)
+        .accounts({
+          donor: donor2.publicKey,
+          campaignPda: pda,
+          mint,
+          donorTokenAccount: donor2TokenAccount,
+          vault,
+          depositPda: deposit,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([donor2])
+        .rpc();
+
+      const finalBalance = (await getAccount(provider.connection, donor2TokenAccount)).amount;
+      expect((finalBalance - balanceAfterDonation).toString()).to.equal(tokenAmount(20).toString());
+
+      // The deposit is closed on reclaim, revoking the reward tier
+      expect(await program.account.depositPda.fetchNullable(deposit)).to.be.null;
+
+      // All the donors have reclaimed, so the owner can close the campaign and its vault
+      await program.methods
+        .closeCampaign(name)
+        .accounts({
+          campaignOwner: campaignOwner.publicKey,
+          campaignPda: pda,
+          vault,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([campaignOwner])
+        .rpc();
+
+      expect(await program.account.campaignPda.fetchNullable(pda)).to.be.null;
+      expect(await provider.connection.getAccountInfo(vault)).to.be.null;
+    });
+
+    it("rejects a second token reclaim by the same donor", async () => {
+      const name = "token-reclaim-twice";
+      const currentSlot = await provider.connection.getSlot();
+      const tokenEndSlot = new BN(currentSlot + 5);
+      const { pda, vault, deposit } = deriveTokenCampaign(name, donor2.publicKey);
+
+      await initializeTokenCampaign(name, tokenEndSlot, tokenAmount(500));
+      await donateToken(name, donor1, donor1TokenAccount, tokenAmount(30));
+      await donateToken(name, donor2, donor2TokenAccount, tokenAmount(20));
+
+      await waitForSlot(tokenEndSlot.toNumber() + 1);
+
+      const reclaimToken = () =>
+        program.methods
+          .reclaimToken(name)
+          .accounts({
+            donor: donor2.publicKey,
+            campaignPda: pda,
+            mint,
+            donorTokenAccount: donor2TokenAccount,
+            vault,
+            depositPda: deposit,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([donor2])
+          .rpc();
+
+      await reclaimToken();
+
+      try {
+        await reclaimToken();
+        expect.fail("Should have thrown an error");
+      } catch (error) {
+        expect(error.message).to.not.include("Should have thrown an error");
+      }
+
+      // The tokens of the other donor are still in the vault
+      const vaultAccount = await getAccount(provider.connection, vault);
+      expect(vaultAccount.amount.toString()).to.equal(tokenAmount(30).toString());
+    });
+  });
+
+  describe("campaign modes", () => {
+    function deriveCampaign(name: string, donor: PublicKey) {
+      const [pda] = PublicKey.findProgramAddressSync([Buffer.from(name)], program.programId);
+      const [deposit] = PublicKey.findProgramAddressSync(
+        [Buffer.from("deposit"), Buffer.from(name), donor.toBuffer()],
+        program.programId
+      );
+      return { pda, deposit };
+    }
+
+    async function initializeCampaign(
+      name: string,
+      endSlot: BN,
+      goal: BN,
+      mode: any,
+      stretchGoals: BN[] = [],
+      hardCap: BN | null = null
+    ) {
+      const { pda } = deriveCampaign(name, donor1.publicKey);
+      await program.methods
+        .initialize(name, endSlot, goal, [], mode, stretchGoals, hardCap)
+        .accounts({
+          campaignOwner: campaignOwner.publicKey,
+          campaignPda: pda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([campaignOwner])
+        .rpc();
+    }
+
+    async function donate(name: string, donor: Keypair, amount: BN) {
+      const { pda, deposit } = deriveCampaign(name, donor.publicKey);
+      await program.methods
+        .donate(name, amount)
+        .accounts({
+          donor: donor.publicKey,
+          campaignPda: pda,
+          depositPda: deposit,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([donor])
+        .rpc();
+    }
+
+    it("keep-what-you-raise pays out a partial raise minus the platform fee", async () => {
+      const name = "flexible-campaign";
+      const platform = Keypair.generate();
+      await airdropSol(platform.publicKey, 1);
+
+      const currentSlot = await provider.connection.getSlot();
+      const flexibleEndSlot = new BN(currentSlot + 5);
+      const mode = { keepWhatYouRaise: { feeBasisPoints: 500, feeRecipient: platform.publicKey } };
+      const { pda, deposit } = deriveCampaign(name, donor1.publicKey);
+
+      await initializeCampaign(name, flexibleEndSlot, new BN(5 * LAMPORTS_PER_SOL), mode);
+      await donate(name, donor1, new BN(1 * LAMPORTS_PER_SOL));
+
+      await waitForSlot(flexibleEndSlot.toNumber() + 1);
+
+      // Donors cannot reclaim from a keep-what-you-raise campaign
+      try {
+        await program.methods
+          .reclaim(name)
+          .accounts({
+            donor: donor1.publicKey,
+            campaignPda: pda,
+            depositPda: deposit,
+          })
+          .signers([donor1])
+          .rpc();
+
+        expect.fail("Should have thrown an error");
+      } catch (error) {
+        expect(error).to.exist;
+      }
+
+      const initialPlatformBalance = await provider.connection.getBalance(platform.publicKey);
+
+      await program.methods
+        .withdraw(name)
+        .accounts({
+          campaignOwner: campaignOwner.publicKey,
+          campaignPda: pda,
+          feeRecipient: platform.publicKey,
+        })
+        .signers([campaignOwner])
+        .rpc();
+
+      const finalPlatformBalance = await provider.connection.getBalance(platform.publicKey);
+      expect(finalPlatformBalance - initialPlatformBalance).to.equal(0.05 * LAMPORTS_PER_SOL);
+    });
+
+    it("rejects donations exceeding the hard cap", async () => {
+      const name = "capped-campaign";
+      const currentSlot = await provider.connection.getSlot();
+
+      await initializeCampaign(
+        name,
+        new BN(currentSlot + 50),
+        new BN(1 * LAMPORTS_PER_SOL),
+        allOrNothing,
+        [],
+        new BN(2 * LAMPORTS_PER_SOL)
+      );
+      await donate(name, donor1, new BN(1 * LAMPORTS_PER_SOL));
+
+      try {
+        await donate(name, donor2, new BN(2 * LAMPORTS_PER_SOL));
+        expect.fail("Should have thrown an error");
+      } catch (error) {
+        expect(error).to.exist;
+      }
+
+      const { pda } = deriveCampaign(name, donor1.publicKey);
+      const campaignAccount = await program.account.campaignPda.fetch(pda);
+      expect(campaignAccount.totalRaised.toString()).to.equal(new BN(1 * LAMPORTS_PER_SOL).toString());
+    });
+
+    it("emits an event when a stretch goal is crossed", async () => {
+      const name = "stretch-campaign";
+      const stretchGoal = new BN(2 * LAMPORTS_PER_SOL);
+      const currentSlot = await provider.connection.getSlot();
+
+      await initializeCampaign(name, new BN(currentSlot + 50), new BN(1 * LAMPORTS_PER_SOL), allOrNothing, [
+        stretchGoal,
+      ]);
+
+      const events: any[] = [];
+      const listener = program.addEventListener("stretchGoalReached", (event) => events.push(event));
+
+      await donate(name, donor1, new BN(1 * LAMPORTS_PER_SOL));
+      await donate(name, donor2, new BN(1 * LAMPORTS_PER_SOL));
+      await new Promise((resolve) => setTimeout(resolve, 1000));
+      await program.removeEventListener(listener);
+
+      expect(events).to.have.length(1);
+      expect(events[0].stretchGoal.toString()).to.equal(stretchGoal.toString());
+    });
+
+    it("rejects stretch goals not above the goal", async () => {
+      const currentSlot = await provider.connection.getSlot();
+
+      try {
+        await initializeCampaign("invalid-stretch", new BN(currentSlot + 50), new BN(2 * LAMPORTS_PER_SOL), allOrNothing, [
+          new BN(1 * LAMPORTS_PER_SOL),
+        ]);
+        expect.fail("Should have thrown an error");
+      } catch (error) {
+        expect(error).to.exist;
+      }
+    });
+  });
+
+  describe("milestone disbursement", () => {
+    const name = "milestone-campaign";
+    let milestoneCampaignPda: PublicKey;
+    let donor1MilestoneDepositPda: PublicKey;
+    let donor2MilestoneDepositPda: PublicKey;
+
+    async function vote(donor: Keypair, depositPda: PublicKey, approve: boolean) {
+      await program.methods
+        .vote(name, approve)
+        .accounts({
+          donor: donor.publicKey,
+          campaignPda: milestoneCampaignPda,
+          depositPda,
+        })
+        .signers([donor])
+        .rpc();
+    }
+
+    async function requestAndCloseVote(donor1Approves: boolean, donor2Approves: boolean) {
+      await program.methods
+        .requestMilestone(name)
+        .accounts({
+          campaignOwner: campaignOwner.publicKey,
+          campaignPda: milestoneCampaignPda,
+        })
+        .signers([campaignOwner])
+        .rpc();
+
+      await vote(donor1, donor1MilestoneDepositPda, donor1Approves);
+      await vote(donor2, donor2MilestoneDepositPda, donor2Approves);
+
+      const campaignAccount = await program.account.campaignPda.fetch(milestoneCampaignPda);
+      await waitForSlot(campaignAccount.vote.deadline.toNumber() + 1);
+
+      await program.methods
+        .releaseMilestone(name)
+        .accounts({
+          campaignOwner: campaignOwner.publicKey,
+          campaignPda: milestoneCampaignPda,
+          vault: null,
+          ownerTokenAccount: null,
+          tokenProgram: null,
+        })
+        .rpc();
+    }
+
+    before(async () => {
+      const currentSlot = await provider.connection.getSlot();
+      const milestoneEndSlot = new BN(currentSlot + 8);
+
+      [milestoneCampaignPda] = PublicKey.findProgramAddressSync([Buffer.from(name)], program.programId);
+      [donor1MilestoneDepositPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("deposit"), Buffer.from(name), donor1.publicKey.toBuffer()],
+        program.programId
+      );
+      [donor2MilestoneDepositPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("deposit"), Buffer.from(name), donor2.publicKey.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .initialize(name, milestoneEndSlot, new BN(2 * LAMPORTS_PER_SOL), [], allOrNothing, [], null)
+        .accounts({
+          campaignOwner: campaignOwner.publicKey,
+          campaignPda: milestoneCampaignPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([campaignOwner])
+        .rpc();
+
+      // Two tranches of 50% each, with a voting period of 6 slots and 100 slots to request each one
+      await program.methods
+        .enableMilestones(name, [5000, 5000], new BN(6), new BN(100))
+        .accounts({
+          campaignOwner: campaignOwner.publicKey,
+          campaignPda: milestoneCampaignPda,
+        })
+        .signers([campaignOwner])
+        .rpc();
+
+      for (const [donor, depositPda, amount] of [
+        [donor1, donor1MilestoneDepositPda, 2],
+        [donor2, donor2MilestoneDepositPda, 1],
+      ] as [Keypair, PublicKey, number][]) {
+        await program.methods
+          .donate(name, new BN(amount * LAMPORTS_PER_SOL))
+          .accounts({
+            donor: donor.publicKey,
+            campaignPda: milestoneCampaignPda,
+            depositPda,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([donor])
+          .rpc();
+      }
+
+      await waitForSlot(milestoneEndSlot.toNumber() + 1);
+    });
+
+    it("rejects a direct withdraw of a milestone campaign", async () => {
+      try {
+        await program.methods
+          .withdraw(name)
+          .accounts({
+            campaignOwner: campaignOwner.publicKey,
+            campaignPda: milestoneCampaignPda,
+            feeRecipient: null,
+          })
+          .signers([campaignOwner])
+          .rpc();
+
+        expect.fail("Should have thrown an error");
+      } catch (error) {
+        expect(error).to.exist;
+      }
+    });
+
+    it("releases a tranche approved by the weighted majority", async () => {
+      const initialOwnerBalance = await provider.connection.getBalance(campaignOwner.publicKey);
+
+      // donor1 (2 SOL) approves, donor2 (1 SOL) rejects
+      await requestAndCloseVote(true, false);
+
+      const finalOwnerBalance = await provider.connection.getBalance(campaignOwner.publicKey);
+      expect(finalOwnerBalance - initialOwnerBalance).to.equal(1.5 * LAMPORTS_PER_SOL);
+
+      const campaignAccount = await program.account.campaignPda.fetch(milestoneCampaignPda);
+      expect(campaignAccount.releasedMilestones).to.equal(1);
+      expect(campaignAccount.vote).to.be.null;
+    });
+
+    it("fails the campaign when a tranche is rejected", async () => {
+      const initialOwnerBalance = await provider.connection.getBalance(campaignOwner.publicKey);
+
+      // donor1 (2 SOL) rejects, donor2 (1 SOL) approves
+      await requestAndCloseVote(false, true);
+
+      const finalOwnerBalance = await provider.connection.getBalance(campaignOwner.publicKey);
+      expect(finalOwnerBalance).to.equal(initialOwnerBalance);
+
+      const campaignAccount = await program.account.campaignPda.fetch(milestoneCampaignPda);
+      expect(campaignAccount.state).to.deep.equal({ failed: {} });
+    });
+
+    it("refunds the unreleased share pro rata", async () => {
+      const initialDonorBalance = await provider.connection.getBalance(donor2.publicKey);
+
+      await program.methods
+        .reclaim(name)
+        .accounts({
+          donor: donor2.publicKey,
+          campaignPda: milestoneCampaignPda,
+          depositPda: donor2MilestoneDepositPda,
+        })
+        .signers([donor2])
+        .rpc();
+
+      // Half of the donations was released, so donor2 gets back half of 1 SOL plus the deposit rent
+      const finalDonorBalance = await provider.connection.getBalance(donor2.publicKey);
+      expect(finalDonorBalance - initialDonorBalance).to.be.greaterThanOrEqual(0.5 * LAMPORTS_PER_SOL);
+      expect(finalDonorBalance - initialDonorBalance).to.be.lessThan(0.6 * LAMPORTS_PER_SOL);
+    });
+
+    it("lets anyone fail the campaign when the owner stops requesting tranches", async () => {
+      const idleName = "idle-milestones";
+      const idleEndSlot = new BN((await provider.connection.getSlot()) + 8);
+      const [idleCampaignPda] = PublicKey.findProgramAddressSync([Buffer.from(idleName)], program.programId);
+      const [idleDepositPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("deposit"), Buffer.from(idleName), donor1.publicKey.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .initialize(idleName, idleEndSlot, new BN(1 * LAMPORTS_PER_SOL), [], allOrNothing, [], null)
+        .accounts({
+          campaignOwner: campaignOwner.publicKey,
+          campaignPda: idleCampaignPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([campaignOwner])
+        .rpc();
+
+      // The owner has 10 slots after the end to request the first tranche
+      await program.methods
+        .enableMilestones(idleName, [10000], new BN(6), new BN(10))
+        .accounts({
+          campaignOwner: campaignOwner.publicKey,
+          campaignPda: idleCampaignPda,
+        })
+        .signers([campaignOwner])
+        .rpc();
+
+      await program.methods
+        .donate(idleName, new BN(1 * LAMPORTS_PER_SOL))
+        .accounts({
+          donor: donor1.publicKey,
+          campaignPda: idleCampaignPda,
+          depositPda: idleDepositPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([donor1])
+        .rpc();
+
+      // The campaign succeeded, so it cannot be failed before the request deadline
+      await waitForSlot(idleEndSlot.toNumber() + 1);
+      try {
+        await program.methods
+          .expireMilestone(idleName)
+          .accounts({ campaignPda: idleCampaignPda })
+          .rpc();
+
+        expect.fail("Should have thrown an error");
+      } catch (error) {
+        expect(error.error.errorCode.code).to.equal("RequestDeadlineNotPassed");
+      }
+
+      await waitForSlot(idleEndSlot.toNumber() + 12);
+      await program.methods
+        .expireMilestone(idleName)
+        .accounts({ campaignPda: idleCampaignPda })
+        .rpc();
+
+      const campaignAccount = await program.account.campaignPda.fetch(idleCampaignPda);
+      expect(campaignAccount.state).to.deep.equal({ failed: {} });
+
+      // Nothing was released, so the donor gets back the whole donation
+      const initialDonorBalance = await provider.connection.getBalance(donor1.publicKey);
+      await program.methods
+        .reclaim(idleName)
+        .accounts({
+          donor: donor1.publicKey,
+          campaignPda: idleCampaignPda,
+          depositPda: idleDepositPda,
+        })
+        .signers([donor1])
+        .rpc();
+      const finalDonorBalance = await provider.connection.getBalance(donor1.publicKey);
+      expect(finalDonorBalance - initialDonorBalance).to.be.greaterThanOrEqual(1 * LAMPORTS_PER_SOL);
+    });
+  });
+
   describe("time validation", () => {
     it("enforces campaign deadline for donations", async () => {
       // Create a campaign with very short duration
@@ -512,7 +1397,7 @@ describe("Crowdfund Program - Universal Test Suite", () => {
       );
       
       await program.methods
-        .initialize(expiredCampaignName, expiredEndSlot, goalAmount)
+        .initialize(expiredCampaignName, expiredEndSlot, goalAmount, [], allOrNothing, [], null)
         .accounts({
           campaignOwner: campaignOwner.publicKey,
           campaignPda: expiredCampaignPda,
@@ -627,7 +1512,7 @@ describe("Crowdfund Program - Universal Test Suite", () => {
     
     try {
       await program.methods
-        .initialize(name, pastSlot, goalAmount)
+        .initialize(name, pastSlot, goalAmount, [], allOrNothing, [], null)
         .accounts({
           campaignOwner: campaignOwner.publicKey,
           campaignPda,
@@ -659,14 +1544,24 @@ To use this test suite with your crowdfund program:
    program = anchor.workspace.Crowdfund as CrowdfundProgram;
 
 3. Ensure your program implements the expected interface:
-   - initialize(name: String, end_slot: u64, goal: u64)
+   - initialize(name: String, end_slot: u64, goal: u64, reward_tiers: Vec<u64>, mode: CampaignMode, stretch_goals: Vec<u64>, hard_cap: Option<u64>)
    - donate(name: String, amount: u64) 
    - withdraw(name: String)
    - reclaim(name: String)
+   - sweep(name: String)
+   - initialize_token(name: String, end_slot: u64, goal: u64, reward_tiers: Vec<u64>, mode: CampaignMode, stretch_goals: Vec<u64>, hard_cap: Option<u64>)
+   - donate_token(name: String, amount: u64)
+   - withdraw_token(name: String)
+   - reclaim_token(name: String)
+   - close_campaign(name: String)
+   - enable_milestones(name: String, milestones: Vec<u16>, voting_slots: u64)
+   - request_milestone(name: String)
+   - vote(name: String, approve: bool)
+   - release_milestone(name: String)
 
 4. Update account structures if needed:
-   - CampaignPDA with fields: campaign_name, campaign_owner, end_donate_slot, goal_in_lamports
-   - DepositPDA with field: total_donated
+   - CampaignPDA with fields: campaign_name, campaign_owner, end_donate_slot, goal_in_lamports, total_raised, mint, reward_tiers, mode, stretch_goals, hard_cap, state, milestones, voting_slots, released_milestones, released_amount, vote
+   - DepositPDA with fields: total_donated, tier, next_vote
 
 5. Run tests:
    anchor test
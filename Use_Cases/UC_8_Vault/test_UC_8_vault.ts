@@ -8,20 +8,48 @@ import {
   Connection,
   Transaction
 } from "@solana/web3.js";
+import {
+  TOKEN_PROGRAM_ID,
+  createMint,
+  mintTo,
+  getOrCreateAssociatedTokenAccount,
+  getAccount,
+} from "@solana/spl-token";
 import { BN } from "bn.js";
 import { assert, expect } from "chai";
 
 // Generic vault program interface - adapt to your specific program
 interface VaultProgram {
   methods: {
-    initialize(waitTime: BN, initialAmount: BN): any;
-    withdraw(amount: BN): any;
-    finalize(): any;
-    cancel(): any;
+    initialize(vaultId: BN, waitTime: BN, rotationWaitTime: BN, initialAmount: BN): any;
+    initializeToken(vaultId: BN, waitTime: BN, rotationWaitTime: BN, initialAmount: BN): any;
+    deposit(vaultId: BN, amount: BN): any;
+    depositToken(vaultId: BN, amount: BN): any;
+    withdraw(vaultId: BN, requestId: BN, amount: BN): any;
+    withdrawToken(vaultId: BN, requestId: BN, amount: BN): any;
+    instantWithdraw(vaultId: BN, amount: BN): any;
+    finalize(vaultId: BN, requestId: BN): any;
+    finalizeToken(vaultId: BN, requestId: BN): any;
+    cancel(vaultId: BN, requestId: BN): any;
+    recover(vaultId: BN): any;
+    recoverToken(vaultId: BN): any;
+    closeVault(vaultId: BN): any;
+    rotateOwner(vaultId: BN, newOwner: PublicKey): any;
+    rotateRecovery(vaultId: BN, newRecovery: PublicKey): any;
+    finalizeRotation(vaultId: BN): any;
+    cancelRotation(vaultId: BN): any;
+    requestAllowanceChange(vaultId: BN, allowance: BN, allowancePeriod: BN): any;
+    finalizeAllowanceChange(vaultId: BN): any;
+    cancelAllowanceChange(vaultId: BN): any;
   };
   account: {
     vaultInfo: {
       fetch(address: PublicKey): Promise<any>;
+      fetchNullable(address: PublicKey): Promise<any>;
+    };
+    requestPda: {
+      fetch(address: PublicKey): Promise<any>;
+      fetchNullable(address: PublicKey): Promise<any>;
     };
   };
 }
@@ -38,13 +66,16 @@ describe("Vault Program", () => {
   let owner: Keypair;
   let recovery: Keypair;
   let receiver: Keypair;
+  let vaultId: BN;
   let vaultInfo: PublicKey;
   let vaultBump: number;
   
   // Test constants
   const INITIAL_AMOUNT = new BN(LAMPORTS_PER_SOL);
   const WAIT_TIME = new BN(10); // 10 slots
+  const ROTATION_WAIT_TIME = new BN(10); // 10 slots
   const WITHDRAWAL_AMOUNT = new BN(0.5 * LAMPORTS_PER_SOL);
+  const REQUEST_ID = new BN(1);
 
   beforeEach(async () => {
     // Generate fresh keypairs for each test
@@ -52,9 +83,10 @@ describe("Vault Program", () => {
     recovery = Keypair.generate();
     receiver = Keypair.generate();
 
-    // Derive PDA for vault info
+    // Derive PDA for vault info, seeded by an id so that it survives key rotations
+    vaultId = new BN(Math.floor(Math.random() * 1_000_000_000));
     [vaultInfo, vaultBump] = PublicKey.findProgramAddressSync(
-      [owner.publicKey.toBuffer()],
+      [Buffer.from("vault"), vaultId.toArrayLike(Buffer, "le", 8)],
       program.programId
     );
 
@@ -74,6 +106,22 @@ describe("Vault Program", () => {
     return await provider.connection.getBalance(publicKey);
   };
 
+  const deriveRequest = (requestId: BN): PublicKey => {
+    const [request] = PublicKey.findProgramAddressSync(
+      [Buffer.from("request"), vaultInfo.toBuffer(), requestId.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    return request;
+  };
+
+  const waitUntilSlot = async (targetSlot: number) => {
+    let currentSlot = await provider.connection.getSlot();
+    while (currentSlot < targetSlot) {
+      await new Promise(resolve => setTimeout(resolve, 400)); // Wait ~400ms per slot
+      currentSlot = await provider.connection.getSlot();
+    }
+  };
+
   const expectTransactionToFail = async (transactionPromise: Promise<any>) => {
     try {
       await transactionPromise;
@@ -89,7 +137,7 @@ describe("Vault Program", () => {
     initialAmount: BN = INITIAL_AMOUNT
   ) => {
     await program.methods
-      .initialize(waitTime, initialAmount)
+      .initialize(vaultId, waitTime, ROTATION_WAIT_TIME, initialAmount)
       .accounts({
         owner: owner.publicKey,
         recovery: recovery.publicKey,
@@ -150,6 +198,48 @@ describe("Vault Program", () => {
     });
   });
 
+  describe("deposit()", () => {
+    beforeEach(async () => {
+      await initializeVault();
+    });
+
+    it("allows anyone to top up the vault", async () => {
+      const depositor = Keypair.generate();
+      await provider.connection.requestAirdrop(depositor.publicKey, LAMPORTS_PER_SOL);
+      await new Promise(resolve => setTimeout(resolve, 1000));
+
+      const depositAmount = new BN(0.25 * LAMPORTS_PER_SOL);
+      const vaultBalanceBefore = await getAccountBalance(vaultInfo);
+
+      await program.methods
+        .deposit(vaultId, depositAmount)
+        .accounts({
+          sender: depositor.publicKey,
+          vaultInfo,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([depositor])
+        .rpc();
+
+      const vaultBalanceAfter = await getAccountBalance(vaultInfo);
+      expect(vaultBalanceAfter - vaultBalanceBefore).to.equal(depositAmount.toNumber());
+    });
+
+    it("rejects zero deposit", async () => {
+      await expectTransactionToFail(
+        program.methods
+          .deposit(vaultId, new BN(0))
+          .accounts({
+            sender: owner.publicKey,
+            vaultInfo,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([owner])
+          .rpc()
+      );
+    });
+  });
+
   describe("withdraw()", () => {
     beforeEach(async () => {
       await initializeVault();
@@ -157,45 +247,54 @@ describe("Vault Program", () => {
 
     it("allows owner to request withdrawal", async () => {
       await program.methods
-        .withdraw(WITHDRAWAL_AMOUNT)
+        .withdraw(vaultId, REQUEST_ID, WITHDRAWAL_AMOUNT)
         .accounts({
           owner: owner.publicKey,
           receiver: receiver.publicKey,
           vaultInfo,
+          request: deriveRequest(REQUEST_ID),
+          systemProgram: SystemProgram.programId,
         })
         .signers([owner])
         .rpc();
 
+      const requestAccount = await program.account.requestPda.fetch(deriveRequest(REQUEST_ID));
+      expect(requestAccount.amount.toString()).to.equal(WITHDRAWAL_AMOUNT.toString());
+      expect(requestAccount.receiver.toString()).to.equal(receiver.publicKey.toString());
+
       const vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
-      expect(vaultAccount.amount.toString()).to.equal(WITHDRAWAL_AMOUNT.toString());
-      expect(vaultAccount.receiver.toString()).to.equal(receiver.publicKey.toString());
+      expect(vaultAccount.pendingAmount.toString()).to.equal(WITHDRAWAL_AMOUNT.toString());
     });
 
     it("sets correct wait period", async () => {
       const slot = await provider.connection.getSlot();
       
       await program.methods
-        .withdraw(WITHDRAWAL_AMOUNT)
+        .withdraw(vaultId, REQUEST_ID, WITHDRAWAL_AMOUNT)
         .accounts({
           owner: owner.publicKey,
           receiver: receiver.publicKey,
           vaultInfo,
+          request: deriveRequest(REQUEST_ID),
+          systemProgram: SystemProgram.programId,
         })
         .signers([owner])
         .rpc();
 
-      const vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
-      expect(vaultAccount.requestTime.toNumber()).to.be.greaterThanOrEqual(slot);
+      const requestAccount = await program.account.requestPda.fetch(deriveRequest(REQUEST_ID));
+      expect(requestAccount.requestTime.toNumber()).to.be.greaterThanOrEqual(slot);
     });
 
     it("rejects zero amount", async () => {
       await expectTransactionToFail(
         program.methods
-          .withdraw(new BN(0))
+          .withdraw(vaultId, REQUEST_ID, new BN(0))
           .accounts({
             owner: owner.publicKey,
             receiver: receiver.publicKey,
             vaultInfo,
+            request: deriveRequest(REQUEST_ID),
+            systemProgram: SystemProgram.programId,
           })
           .signers([owner])
           .rpc()
@@ -208,11 +307,13 @@ describe("Vault Program", () => {
 
       await expectTransactionToFail(
         program.methods
-          .withdraw(excessiveAmount)
+          .withdraw(vaultId, REQUEST_ID, excessiveAmount)
           .accounts({
             owner: owner.publicKey,
             receiver: receiver.publicKey,
             vaultInfo,
+            request: deriveRequest(REQUEST_ID),
+            systemProgram: SystemProgram.programId,
           })
           .signers([owner])
           .rpc()
@@ -226,37 +327,122 @@ describe("Vault Program", () => {
 
       await expectTransactionToFail(
         program.methods
-          .withdraw(WITHDRAWAL_AMOUNT)
+          .withdraw(vaultId, REQUEST_ID, WITHDRAWAL_AMOUNT)
           .accounts({
             owner: nonOwner.publicKey,
             receiver: receiver.publicKey,
             vaultInfo,
+            request: deriveRequest(REQUEST_ID),
+            systemProgram: SystemProgram.programId,
           })
           .signers([nonOwner])
           .rpc()
       );
     });
 
-    it("prevents multiple concurrent withdrawal requests", async () => {
+    it("allows multiple concurrent withdrawal requests", async () => {
+      const secondRequestId = new BN(2);
+
+      await program.methods
+        .withdraw(vaultId, REQUEST_ID, WITHDRAWAL_AMOUNT)
+        .accounts({
+          owner: owner.publicKey,
+          receiver: receiver.publicKey,
+          vaultInfo,
+          request: deriveRequest(REQUEST_ID),
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .withdraw(vaultId, secondRequestId, WITHDRAWAL_AMOUNT)
+        .accounts({
+          owner: owner.publicKey,
+          receiver: receiver.publicKey,
+          vaultInfo,
+          request: deriveRequest(secondRequestId),
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
+      expect(vaultAccount.pendingAmount.toString()).to.equal(WITHDRAWAL_AMOUNT.muln(2).toString());
+
+      // Cancelling one request leaves the other pending
+      await program.methods
+        .cancel(vaultId, REQUEST_ID)
+        .accounts({
+          recovery: recovery.publicKey,
+          owner: owner.publicKey,
+          vaultInfo,
+          request: deriveRequest(REQUEST_ID),
+        })
+        .signers([recovery])
+        .rpc();
+
+      const secondRequest = await program.account.requestPda.fetch(deriveRequest(secondRequestId));
+      expect(secondRequest.amount.toString()).to.equal(WITHDRAWAL_AMOUNT.toString());
+    });
+
+    it("rejects pending requests exceeding the spendable balance", async () => {
+      const vaultBalance = await getAccountBalance(vaultInfo);
+      const halfBalance = new BN(Math.floor(vaultBalance / 2));
+
+      await program.methods
+        .withdraw(vaultId, REQUEST_ID, halfBalance)
+        .accounts({
+          owner: owner.publicKey,
+          receiver: receiver.publicKey,
+          vaultInfo,
+          request: deriveRequest(REQUEST_ID),
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      // Together both requests would eat into the rent exemption
+      const secondRequestId = new BN(2);
+      await expectTransactionToFail(
+        program.methods
+          .withdraw(vaultId, secondRequestId, halfBalance)
+          .accounts({
+            owner: owner.publicKey,
+            receiver: receiver.publicKey,
+            vaultInfo,
+            request: deriveRequest(secondRequestId),
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([owner])
+          .rpc()
+      );
+    });
+
+    it("rejects reusing a pending request id", async () => {
       // First withdrawal request
       await program.methods
-        .withdraw(WITHDRAWAL_AMOUNT)
+        .withdraw(vaultId, REQUEST_ID, WITHDRAWAL_AMOUNT)
         .accounts({
           owner: owner.publicKey,
           receiver: receiver.publicKey,
           vaultInfo,
+          request: deriveRequest(REQUEST_ID),
+          systemProgram: SystemProgram.programId,
         })
         .signers([owner])
         .rpc();
 
-      // Second withdrawal request should fail
+      // Second withdrawal request with the same id should fail
       await expectTransactionToFail(
         program.methods
-          .withdraw(WITHDRAWAL_AMOUNT)
+          .withdraw(vaultId, REQUEST_ID, WITHDRAWAL_AMOUNT)
           .accounts({
             owner: owner.publicKey,
             receiver: receiver.publicKey,
             vaultInfo,
+            request: deriveRequest(REQUEST_ID),
+            systemProgram: SystemProgram.programId,
           })
           .signers([owner])
           .rpc()
@@ -268,11 +454,13 @@ describe("Vault Program", () => {
     beforeEach(async () => {
       await initializeVault();
       await program.methods
-        .withdraw(WITHDRAWAL_AMOUNT)
+        .withdraw(vaultId, REQUEST_ID, WITHDRAWAL_AMOUNT)
         .accounts({
           owner: owner.publicKey,
           receiver: receiver.publicKey,
           vaultInfo,
+          request: deriveRequest(REQUEST_ID),
+          systemProgram: SystemProgram.programId,
         })
         .signers([owner])
         .rpc();
@@ -281,11 +469,12 @@ describe("Vault Program", () => {
     it("rejects premature finalization", async () => {
       await expectTransactionToFail(
         program.methods
-          .finalize()
+          .finalize(vaultId, REQUEST_ID)
           .accounts({
             owner: owner.publicKey,
             receiver: receiver.publicKey,
             vaultInfo,
+            request: deriveRequest(REQUEST_ID),
           })
           .signers([owner])
           .rpc()
@@ -294,8 +483,8 @@ describe("Vault Program", () => {
 
     it("completes withdrawal after wait time", async () => {
       // Wait for the wait period to pass
-      const vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
-      const targetSlot = vaultAccount.requestTime.toNumber() + vaultAccount.waitTime.toNumber();
+      const requestAccount = await program.account.requestPda.fetch(deriveRequest(REQUEST_ID));
+      const targetSlot = requestAccount.requestTime.toNumber() + WAIT_TIME.toNumber();
       
       // Wait until target slot is reached
       let currentSlot = await provider.connection.getSlot();
@@ -308,11 +497,12 @@ describe("Vault Program", () => {
       const vaultBalanceBefore = await getAccountBalance(vaultInfo);
 
       await program.methods
-        .finalize()
+        .finalize(vaultId, REQUEST_ID)
         .accounts({
           owner: owner.publicKey,
           receiver: receiver.publicKey,
           vaultInfo,
+          request: deriveRequest(REQUEST_ID),
         })
         .signers([owner])
         .rpc();
@@ -327,8 +517,8 @@ describe("Vault Program", () => {
 
     it("resets vault state", async () => {
       // Wait for the wait period to pass
-      const vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
-      const targetSlot = vaultAccount.requestTime.toNumber() + vaultAccount.waitTime.toNumber();
+      const requestAccount = await program.account.requestPda.fetch(deriveRequest(REQUEST_ID));
+      const targetSlot = requestAccount.requestTime.toNumber() + WAIT_TIME.toNumber();
       
       let currentSlot = await provider.connection.getSlot();
       while (currentSlot < targetSlot) {
@@ -337,19 +527,23 @@ describe("Vault Program", () => {
       }
 
       await program.methods
-        .finalize()
+        .finalize(vaultId, REQUEST_ID)
         .accounts({
           owner: owner.publicKey,
           receiver: receiver.publicKey,
           vaultInfo,
+          request: deriveRequest(REQUEST_ID),
         })
         .signers([owner])
         .rpc();
 
       const finalVaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
-      
-      // State should be reset (assuming Idle = 0)
-      expect(finalVaultAccount.state).to.deep.equal({ idle: {} });
+      expect(finalVaultAccount.pendingAmount.toNumber()).to.equal(0);
+      expect(finalVaultAccount.openRequests.toNumber()).to.equal(0);
+
+      // The request account is closed once finalized
+      const requestAccount = await program.account.requestPda.fetchNullable(deriveRequest(REQUEST_ID));
+      expect(requestAccount).to.be.null;
     });
 
     it("rejects finalization with wrong receiver", async () => {
@@ -358,8 +552,8 @@ describe("Vault Program", () => {
       await new Promise(resolve => setTimeout(resolve, 1000));
 
       // Wait for the wait period to pass
-      const vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
-      const targetSlot = vaultAccount.requestTime.toNumber() + vaultAccount.waitTime.toNumber();
+      const requestAccount = await program.account.requestPda.fetch(deriveRequest(REQUEST_ID));
+      const targetSlot = requestAccount.requestTime.toNumber() + WAIT_TIME.toNumber();
       
       let currentSlot = await provider.connection.getSlot();
       while (currentSlot < targetSlot) {
@@ -369,11 +563,12 @@ describe("Vault Program", () => {
 
       await expectTransactionToFail(
         program.methods
-          .finalize()
+          .finalize(vaultId, REQUEST_ID)
           .accounts({
             owner: owner.publicKey,
             receiver: wrongReceiver.publicKey,
             vaultInfo,
+            request: deriveRequest(REQUEST_ID),
           })
           .signers([owner])
           .rpc()
@@ -385,11 +580,13 @@ describe("Vault Program", () => {
     beforeEach(async () => {
       await initializeVault();
       await program.methods
-        .withdraw(WITHDRAWAL_AMOUNT)
+        .withdraw(vaultId, REQUEST_ID, WITHDRAWAL_AMOUNT)
         .accounts({
           owner: owner.publicKey,
           receiver: receiver.publicKey,
           vaultInfo,
+          request: deriveRequest(REQUEST_ID),
+          systemProgram: SystemProgram.programId,
         })
         .signers([owner])
         .rpc();
@@ -397,17 +594,21 @@ describe("Vault Program", () => {
 
     it("allows recovery to cancel request", async () => {
       await program.methods
-        .cancel()
+        .cancel(vaultId, REQUEST_ID)
         .accounts({
           recovery: recovery.publicKey,
           owner: owner.publicKey,
           vaultInfo,
+          request: deriveRequest(REQUEST_ID),
         })
         .signers([recovery])
         .rpc();
 
       const vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
-      expect(vaultAccount.state).to.deep.equal({ idle: {} });
+      expect(vaultAccount.pendingAmount.toNumber()).to.equal(0);
+
+      const requestAccount = await program.account.requestPda.fetchNullable(deriveRequest(REQUEST_ID));
+      expect(requestAccount).to.be.null;
     });
 
     it("rejects non-recovery cancellation", async () => {
@@ -417,11 +618,12 @@ describe("Vault Program", () => {
 
       await expectTransactionToFail(
         program.methods
-          .cancel()
+          .cancel(vaultId, REQUEST_ID)
           .accounts({
             recovery: nonRecovery.publicKey,
             owner: owner.publicKey,
             vaultInfo,
+            request: deriveRequest(REQUEST_ID),
           })
           .signers([nonRecovery])
           .rpc()
@@ -431,11 +633,12 @@ describe("Vault Program", () => {
     it("rejects owner cancellation", async () => {
       await expectTransactionToFail(
         program.methods
-          .cancel()
+          .cancel(vaultId, REQUEST_ID)
           .accounts({
             recovery: owner.publicKey, // Owner trying to cancel
             owner: owner.publicKey,
             vaultInfo,
+            request: deriveRequest(REQUEST_ID),
           })
           .signers([owner])
           .rpc()
@@ -444,25 +647,31 @@ describe("Vault Program", () => {
 
     it("resets withdrawal state", async () => {
       await program.methods
-        .cancel()
+        .cancel(vaultId, REQUEST_ID)
         .accounts({
           recovery: recovery.publicKey,
           owner: owner.publicKey,
           vaultInfo,
+          request: deriveRequest(REQUEST_ID),
         })
         .signers([recovery])
         .rpc();
 
       const vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
-      expect(vaultAccount.state).to.deep.equal({ idle: {} });
+      expect(vaultAccount.pendingAmount.toNumber()).to.equal(0);
+
+      const requestAccount = await program.account.requestPda.fetchNullable(deriveRequest(REQUEST_ID));
+      expect(requestAccount).to.be.null;
 
       // Should be able to make a new withdrawal request
       await program.methods
-        .withdraw(WITHDRAWAL_AMOUNT)
+        .withdraw(vaultId, REQUEST_ID, WITHDRAWAL_AMOUNT)
         .accounts({
           owner: owner.publicKey,
           receiver: receiver.publicKey,
           vaultInfo,
+          request: deriveRequest(REQUEST_ID),
+          systemProgram: SystemProgram.programId,
         })
         .signers([owner])
         .rpc();
@@ -471,11 +680,12 @@ describe("Vault Program", () => {
     it("rejects cancellation when no request pending", async () => {
       // Cancel first request
       await program.methods
-        .cancel()
+        .cancel(vaultId, REQUEST_ID)
         .accounts({
           recovery: recovery.publicKey,
           owner: owner.publicKey,
           vaultInfo,
+          request: deriveRequest(REQUEST_ID),
         })
         .signers([recovery])
         .rpc();
@@ -483,11 +693,12 @@ describe("Vault Program", () => {
       // Try to cancel again when no request is pending
       await expectTransactionToFail(
         program.methods
-          .cancel()
+          .cancel(vaultId, REQUEST_ID)
           .accounts({
             recovery: recovery.publicKey,
             owner: owner.publicKey,
             vaultInfo,
+            request: deriveRequest(REQUEST_ID),
           })
           .signers([recovery])
           .rpc()
@@ -495,29 +706,58 @@ describe("Vault Program", () => {
     });
   });
 
-  describe("time validation", () => {
+  describe("recover()", () => {
+    let destination: Keypair;
+
     beforeEach(async () => {
+      destination = Keypair.generate();
       await initializeVault();
-    });
-
-    it("enforces wait period", async () => {
       await program.methods
-        .withdraw(WITHDRAWAL_AMOUNT)
+        .withdraw(vaultId, REQUEST_ID, WITHDRAWAL_AMOUNT)
         .accounts({
           owner: owner.publicKey,
           receiver: receiver.publicKey,
           vaultInfo,
+          request: deriveRequest(REQUEST_ID),
+          systemProgram: SystemProgram.programId,
         })
         .signers([owner])
         .rpc();
+    });
 
-      // Try to finalize immediately - should fail
+    it("moves all funds above rent to the destination", async () => {
+      const vaultBalanceBefore = await getAccountBalance(vaultInfo);
+
+      await program.methods
+        .recover(vaultId)
+        .accounts({
+          recovery: recovery.publicKey,
+          destination: destination.publicKey,
+          vaultInfo,
+        })
+        .signers([recovery])
+        .rpc();
+
+      const vaultBalanceAfter = await getAccountBalance(vaultInfo);
+      const destinationBalance = await getAccountBalance(destination.publicKey);
+      const rent = await provider.connection.getMinimumBalanceForRentExemption(
+        (await provider.connection.getAccountInfo(vaultInfo)).data.length
+      );
+
+      expect(vaultBalanceAfter).to.equal(rent);
+      expect(destinationBalance).to.equal(vaultBalanceBefore - rent);
+
+      const vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
+      expect(vaultAccount.pendingAmount.toNumber()).to.equal(0);
+    });
+
+    it("rejects non-recovery caller", async () => {
       await expectTransactionToFail(
         program.methods
-          .finalize()
+          .recover(vaultId)
           .accounts({
-            owner: owner.publicKey,
-            receiver: receiver.publicKey,
+            recovery: owner.publicKey,
+            destination: destination.publicKey,
             vaultInfo,
           })
           .signers([owner])
@@ -525,151 +765,839 @@ describe("Vault Program", () => {
       );
     });
 
-    it("prevents early finalization", async () => {
+    it("invalidates pending requests", async () => {
       await program.methods
-        .withdraw(WITHDRAWAL_AMOUNT)
+        .recover(vaultId)
         .accounts({
-          owner: owner.publicKey,
-          receiver: receiver.publicKey,
+          recovery: recovery.publicKey,
+          destination: destination.publicKey,
           vaultInfo,
         })
-        .signers([owner])
+        .signers([recovery])
         .rpc();
 
-      const vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
-      const currentSlot = await provider.connection.getSlot();
-      const requiredSlot = vaultAccount.requestTime.toNumber() + vaultAccount.waitTime.toNumber();
-
-      expect(currentSlot).to.be.lessThan(requiredSlot);
+      const requestAccount = await program.account.requestPda.fetch(deriveRequest(REQUEST_ID));
+      await waitUntilSlot(requestAccount.requestTime.toNumber() + WAIT_TIME.toNumber());
 
-      // Should fail until wait time passes
       await expectTransactionToFail(
         program.methods
-          .finalize()
+          .finalize(vaultId, REQUEST_ID)
           .accounts({
             owner: owner.publicKey,
             receiver: receiver.publicKey,
             vaultInfo,
+            request: deriveRequest(REQUEST_ID),
           })
           .signers([owner])
           .rpc()
       );
+
+      // The stale request can still be cancelled to reclaim its rent
+      await program.methods
+        .cancel(vaultId, REQUEST_ID)
+        .accounts({
+          recovery: recovery.publicKey,
+          owner: owner.publicKey,
+          vaultInfo,
+          request: deriveRequest(REQUEST_ID),
+        })
+        .signers([recovery])
+        .rpc();
+
+      const vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
+      expect(vaultAccount.pendingAmount.toNumber()).to.equal(0);
     });
   });
 
-  describe("integration scenarios", () => {
-    it("handles full vault lifecycle", async () => {
-      // Initialize
+  describe("closeVault()", () => {
+    const closeVault = async () => {
+      await program.methods
+        .closeVault(vaultId)
+        .accounts({
+          owner: owner.publicKey,
+          vaultInfo,
+          tokenVault: null,
+          ownerTokenAccount: null,
+          tokenProgram: null,
+        })
+        .signers([owner])
+        .rpc();
+    };
+
+    beforeEach(async () => {
       await initializeVault();
-      
-      // Request withdrawal
+    });
+
+    it("returns all the lamports, rent included, to the owner", async () => {
+      const vaultBalance = await getAccountBalance(vaultInfo);
+      const ownerBalanceBefore = await getAccountBalance(owner.publicKey);
+
+      await closeVault();
+
+      const ownerBalanceAfter = await getAccountBalance(owner.publicKey);
+      // Owner pays the transaction fee
+      expect(ownerBalanceAfter - ownerBalanceBefore).to.be.greaterThan(vaultBalance - 10_000);
+
+      const vaultAccount = await program.account.vaultInfo.fetchNullable(vaultInfo);
+      expect(vaultAccount).to.be.null;
+    });
+
+    it("rejects closing while a request is pending", async () => {
       await program.methods
-        .withdraw(WITHDRAWAL_AMOUNT)
+        .withdraw(vaultId, REQUEST_ID, WITHDRAWAL_AMOUNT)
         .accounts({
           owner: owner.publicKey,
           receiver: receiver.publicKey,
           vaultInfo,
+          request: deriveRequest(REQUEST_ID),
+          systemProgram: SystemProgram.programId,
         })
         .signers([owner])
         .rpc();
 
-      // Cancel and request again
+      await expectTransactionToFail(closeVault());
+
+      // Once the request is cancelled the vault can be closed
       await program.methods
-        .cancel()
+        .cancel(vaultId, REQUEST_ID)
         .accounts({
           recovery: recovery.publicKey,
           owner: owner.publicKey,
           vaultInfo,
+          request: deriveRequest(REQUEST_ID),
         })
         .signers([recovery])
         .rpc();
 
-      // New withdrawal request
+      await closeVault();
+    });
+
+    it("rejects non-owner", async () => {
+      await expectTransactionToFail(
+        program.methods
+          .closeVault(vaultId)
+          .accounts({
+            owner: recovery.publicKey,
+            vaultInfo,
+            tokenVault: null,
+            ownerTokenAccount: null,
+            tokenProgram: null,
+          })
+          .signers([recovery])
+          .rpc()
+      );
+    });
+  });
+
+  describe("key rotation", () => {
+    let newKey: Keypair;
+
+    beforeEach(async () => {
+      newKey = Keypair.generate();
+      await initializeVault();
+    });
+
+    it("rotates the owner after the rotation wait time", async () => {
       await program.methods
-        .withdraw(WITHDRAWAL_AMOUNT)
+        .rotateOwner(vaultId, newKey.publicKey)
         .accounts({
-          owner: owner.publicKey,
-          receiver: receiver.publicKey,
+          recovery: recovery.publicKey,
           vaultInfo,
         })
-        .signers([owner])
+        .signers([recovery])
         .rpc();
 
-      // Wait and finalize
-      const vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
-      const targetSlot = vaultAccount.requestTime.toNumber() + vaultAccount.waitTime.toNumber();
-      
-      let currentSlot = await provider.connection.getSlot();
-      while (currentSlot < targetSlot) {
-        await new Promise(resolve => setTimeout(resolve, 400));
-        currentSlot = await provider.connection.getSlot();
-      }
+      let vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
+      expect(vaultAccount.ownerRotation.newKey.toString()).to.equal(newKey.publicKey.toString());
 
-      const receiverBalanceBefore = await getAccountBalance(receiver.publicKey);
+      await waitUntilSlot(
+        vaultAccount.ownerRotation.requestTime.toNumber() + ROTATION_WAIT_TIME.toNumber()
+      );
 
       await program.methods
-        .finalize()
+        .finalizeRotation(vaultId)
         .accounts({
-          owner: owner.publicKey,
-          receiver: receiver.publicKey,
+          signer: receiver.publicKey,
           vaultInfo,
         })
-        .signers([owner])
+        .signers([receiver])
         .rpc();
 
-      const receiverBalanceAfter = await getAccountBalance(receiver.publicKey);
-      expect(receiverBalanceAfter - receiverBalanceBefore).to.equal(WITHDRAWAL_AMOUNT.toNumber());
+      // The vault address does not change with the owner
+      vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
+      expect(vaultAccount.owner.toString()).to.equal(newKey.publicKey.toString());
+      expect(vaultAccount.ownerRotation).to.be.null;
     });
 
-    it("handles multiple withdrawal cycles", async () => {
-      await initializeVault();
-
-      // First withdrawal cycle
+    it("rotates the recovery after the rotation wait time", async () => {
       await program.methods
-        .withdraw(WITHDRAWAL_AMOUNT)
+        .rotateRecovery(vaultId, newKey.publicKey)
         .accounts({
           owner: owner.publicKey,
-          receiver: receiver.publicKey,
           vaultInfo,
         })
         .signers([owner])
         .rpc();
 
-      // Wait and finalize first
       let vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
-      let targetSlot = vaultAccount.requestTime.toNumber() + vaultAccount.waitTime.toNumber();
-      
-      let currentSlot = await provider.connection.getSlot();
-      while (currentSlot < targetSlot) {
-        await new Promise(resolve => setTimeout(resolve, 400));
-        currentSlot = await provider.connection.getSlot();
-      }
+      await waitUntilSlot(
+        vaultAccount.recoveryRotation.requestTime.toNumber() + ROTATION_WAIT_TIME.toNumber()
+      );
 
       await program.methods
-        .finalize()
+        .finalizeRotation(vaultId)
         .accounts({
-          owner: owner.publicKey,
-          receiver: receiver.publicKey,
+          signer: owner.publicKey,
           vaultInfo,
         })
         .signers([owner])
         .rpc();
 
-      // Second withdrawal cycle
-      const secondWithdrawal = new BN(0.1 * LAMPORTS_PER_SOL);
+      vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
+      expect(vaultAccount.recovery.toString()).to.equal(newKey.publicKey.toString());
+      expect(vaultAccount.recoveryRotation).to.be.null;
+    });
+
+    it("rejects premature rotation", async () => {
       await program.methods
-        .withdraw(secondWithdrawal)
+        .rotateOwner(vaultId, newKey.publicKey)
         .accounts({
-          owner: owner.publicKey,
-          receiver: receiver.publicKey,
+          recovery: recovery.publicKey,
           vaultInfo,
         })
-        .signers([owner])
+        .signers([recovery])
         .rpc();
 
-      // Verify second withdrawal can be made
-      vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
-      expect(vaultAccount.amount.toString()).to.equal(secondWithdrawal.toString());
+      await expectTransactionToFail(
+        program.methods
+          .finalizeRotation(vaultId)
+          .accounts({
+            signer: owner.publicKey,
+            vaultInfo,
+          })
+          .signers([owner])
+          .rpc()
+      );
+    });
+
+    it("rejects owner rotation requested by the owner", async () => {
+      await expectTransactionToFail(
+        program.methods
+          .rotateOwner(vaultId, newKey.publicKey)
+          .accounts({
+            recovery: owner.publicKey,
+            vaultInfo,
+          })
+          .signers([owner])
+          .rpc()
+      );
+    });
+
+    it("allows only the recovery key to cancel the owner rotation it proposed", async () => {
+      await program.methods
+        .rotateOwner(vaultId, newKey.publicKey)
+        .accounts({
+          recovery: recovery.publicKey,
+          vaultInfo,
+        })
+        .signers([recovery])
+        .rpc();
+
+      // The owner being replaced cannot veto its own rotation
+      await expectTransactionToFail(
+        program.methods
+          .cancelRotation(vaultId)
+          .accounts({
+            signer: owner.publicKey,
+            vaultInfo,
+          })
+          .signers([owner])
+          .rpc()
+      );
+
+      await program.methods
+        .cancelRotation(vaultId)
+        .accounts({
+          signer: recovery.publicKey,
+          vaultInfo,
+        })
+        .signers([recovery])
+        .rpc();
+
+      const vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
+      expect(vaultAccount.ownerRotation).to.be.null;
+      expect(vaultAccount.owner.toString()).to.equal(owner.publicKey.toString());
+    });
+
+    it("invalidates requests of the previous owner", async () => {
+      await program.methods
+        .withdraw(vaultId, REQUEST_ID, WITHDRAWAL_AMOUNT)
+        .accounts({
+          owner: owner.publicKey,
+          receiver: receiver.publicKey,
+          vaultInfo,
+          request: deriveRequest(REQUEST_ID),
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      await program.methods
+        .rotateOwner(vaultId, newKey.publicKey)
+        .accounts({
+          recovery: recovery.publicKey,
+          vaultInfo,
+        })
+        .signers([recovery])
+        .rpc();
+
+      const vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
+      await waitUntilSlot(
+        vaultAccount.ownerRotation.requestTime.toNumber() + ROTATION_WAIT_TIME.toNumber()
+      );
+
+      await program.methods
+        .finalizeRotation(vaultId)
+        .accounts({
+          signer: recovery.publicKey,
+          vaultInfo,
+        })
+        .signers([recovery])
+        .rpc();
+
+      await provider.connection.requestAirdrop(newKey.publicKey, LAMPORTS_PER_SOL);
+      await new Promise(resolve => setTimeout(resolve, 1000));
+
+      await expectTransactionToFail(
+        program.methods
+          .finalize(vaultId, REQUEST_ID)
+          .accounts({
+            owner: newKey.publicKey,
+            receiver: receiver.publicKey,
+            vaultInfo,
+            request: deriveRequest(REQUEST_ID),
+          })
+          .signers([newKey])
+          .rpc()
+      );
+    });
+  });
+
+  describe("spending allowance", () => {
+    const ALLOWANCE = new BN(0.1 * LAMPORTS_PER_SOL);
+    const ALLOWANCE_PERIOD = new BN(1000); // slots
+
+    const requestAllowance = async (allowance: BN, period: BN) => {
+      await program.methods
+        .requestAllowanceChange(vaultId, allowance, period)
+        .accounts({
+          owner: owner.publicKey,
+          vaultInfo,
+        })
+        .signers([owner])
+        .rpc();
+    };
+
+    const finalizeAllowance = async () => {
+      await program.methods
+        .finalizeAllowanceChange(vaultId)
+        .accounts({
+          owner: owner.publicKey,
+          vaultInfo,
+        })
+        .signers([owner])
+        .rpc();
+    };
+
+    const instantWithdraw = async (amount: BN) => {
+      await program.methods
+        .instantWithdraw(vaultId, amount)
+        .accounts({
+          owner: owner.publicKey,
+          receiver: receiver.publicKey,
+          vaultInfo,
+        })
+        .signers([owner])
+        .rpc();
+    };
+
+    beforeEach(async () => {
+      await initializeVault();
+    });
+
+    it("rejects instant withdrawals without an allowance", async () => {
+      await expectTransactionToFail(instantWithdraw(new BN(1000)));
+    });
+
+    it("applies an allowance change only after the wait time", async () => {
+      await requestAllowance(ALLOWANCE, ALLOWANCE_PERIOD);
+      await expectTransactionToFail(finalizeAllowance());
+
+      const vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
+      await waitUntilSlot(vaultAccount.allowanceChange.requestTime.toNumber() + WAIT_TIME.toNumber());
+      await finalizeAllowance();
+
+      const updatedVaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
+      expect(updatedVaultAccount.allowance.toString()).to.equal(ALLOWANCE.toString());
+      expect(updatedVaultAccount.allowancePeriod.toString()).to.equal(ALLOWANCE_PERIOD.toString());
+      expect(updatedVaultAccount.allowanceChange).to.be.null;
+    });
+
+    it("allows instant withdrawals up to the allowance", async () => {
+      await requestAllowance(ALLOWANCE, ALLOWANCE_PERIOD);
+      const vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
+      await waitUntilSlot(vaultAccount.allowanceChange.requestTime.toNumber() + WAIT_TIME.toNumber());
+      await finalizeAllowance();
+
+      const receiverBalanceBefore = await getAccountBalance(receiver.publicKey);
+      await instantWithdraw(ALLOWANCE.divn(2));
+      await instantWithdraw(ALLOWANCE.divn(2));
+      const receiverBalanceAfter = await getAccountBalance(receiver.publicKey);
+      expect(receiverBalanceAfter - receiverBalanceBefore).to.equal(ALLOWANCE.toNumber());
+
+      // The allowance is spent for the rest of the period
+      await expectTransactionToFail(instantWithdraw(new BN(1)));
+
+      const updatedVaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
+      expect(updatedVaultAccount.spentInPeriod.toString()).to.equal(ALLOWANCE.toString());
+    });
+
+    it("allows recovery to cancel an allowance change", async () => {
+      await requestAllowance(ALLOWANCE, ALLOWANCE_PERIOD);
+
+      await program.methods
+        .cancelAllowanceChange(vaultId)
+        .accounts({
+          recovery: recovery.publicKey,
+          vaultInfo,
+        })
+        .signers([recovery])
+        .rpc();
+
+      const vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
+      expect(vaultAccount.allowanceChange).to.be.null;
+      expect(vaultAccount.allowance.toNumber()).to.equal(0);
+    });
+
+    it("rejects allowance changes from non-owner", async () => {
+      await expectTransactionToFail(
+        program.methods
+          .requestAllowanceChange(vaultId, ALLOWANCE, ALLOWANCE_PERIOD)
+          .accounts({
+            owner: recovery.publicKey,
+            vaultInfo,
+          })
+          .signers([recovery])
+          .rpc()
+      );
+    });
+  });
+
+  describe("token vault", () => {
+    const TOKEN_INITIAL_AMOUNT = new BN(1_000_000);
+    const TOKEN_WITHDRAWAL_AMOUNT = new BN(400_000);
+
+    let mint: PublicKey;
+    let tokenVault: PublicKey;
+    let ownerTokenAccount: PublicKey;
+    let receiverTokenAccount: PublicKey;
+
+    beforeEach(async () => {
+      mint = await createMint(provider.connection, owner, owner.publicKey, null, 6);
+      ownerTokenAccount = (
+        await getOrCreateAssociatedTokenAccount(provider.connection, owner, mint, owner.publicKey)
+      ).address;
+      receiverTokenAccount = (
+        await getOrCreateAssociatedTokenAccount(provider.connection, owner, mint, receiver.publicKey)
+      ).address;
+      await mintTo(provider.connection, owner, mint, ownerTokenAccount, owner, 2 * TOKEN_INITIAL_AMOUNT.toNumber());
+
+      [tokenVault] = PublicKey.findProgramAddressSync(
+        [Buffer.from("token_vault"), vaultId.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
+      await program.methods
+        .initializeToken(vaultId, WAIT_TIME, ROTATION_WAIT_TIME, TOKEN_INITIAL_AMOUNT)
+        .accounts({
+          owner: owner.publicKey,
+          recovery: recovery.publicKey,
+          mint,
+          ownerTokenAccount,
+          vaultInfo,
+          tokenVault,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([owner])
+        .rpc();
+    });
+
+    const requestTokenWithdrawal = async (amount: BN) => {
+      await program.methods
+        .withdrawToken(vaultId, REQUEST_ID, amount)
+        .accounts({
+          owner: owner.publicKey,
+          receiver: receiver.publicKey,
+          vaultInfo,
+          tokenVault,
+          request: deriveRequest(REQUEST_ID),
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+    };
+
+    it("holds the initial tokens in a vault-owned account", async () => {
+      const vaultTokenAccount = await getAccount(provider.connection, tokenVault);
+      expect(vaultTokenAccount.amount.toString()).to.equal(TOKEN_INITIAL_AMOUNT.toString());
+      expect(vaultTokenAccount.owner.toString()).to.equal(vaultInfo.toString());
+
+      const vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
+      expect(vaultAccount.mint.toString()).to.equal(mint.toString());
+    });
+
+    it("accepts token deposits", async () => {
+      await program.methods
+        .depositToken(vaultId, TOKEN_INITIAL_AMOUNT)
+        .accounts({
+          sender: owner.publicKey,
+          senderTokenAccount: ownerTokenAccount,
+          vaultInfo,
+          tokenVault,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([owner])
+        .rpc();
+
+      const vaultTokenAccount = await getAccount(provider.connection, tokenVault);
+      expect(vaultTokenAccount.amount.toString()).to.equal(TOKEN_INITIAL_AMOUNT.muln(2).toString());
+    });
+
+    it("rejects requests above the token balance", async () => {
+      await expectTransactionToFail(requestTokenWithdrawal(TOKEN_INITIAL_AMOUNT.addn(1)));
+    });
+
+    it("rejects lamport withdrawals from a token vault", async () => {
+      await expectTransactionToFail(
+        program.methods
+          .withdraw(vaultId, REQUEST_ID, WITHDRAWAL_AMOUNT)
+          .accounts({
+            owner: owner.publicKey,
+            receiver: receiver.publicKey,
+            vaultInfo,
+            request: deriveRequest(REQUEST_ID),
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([owner])
+          .rpc()
+      );
+    });
+
+    it("moves tokens to the receiver after the wait time", async () => {
+      await requestTokenWithdrawal(TOKEN_WITHDRAWAL_AMOUNT);
+
+      const requestAccount = await program.account.requestPda.fetch(deriveRequest(REQUEST_ID));
+      await waitUntilSlot(requestAccount.requestTime.toNumber() + WAIT_TIME.toNumber());
+
+      await program.methods
+        .finalizeToken(vaultId, REQUEST_ID)
+        .accounts({
+          owner: owner.publicKey,
+          receiverTokenAccount,
+          vaultInfo,
+          tokenVault,
+          request: deriveRequest(REQUEST_ID),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([owner])
+        .rpc();
+
+      const receiverAccount = await getAccount(provider.connection, receiverTokenAccount);
+      expect(receiverAccount.amount.toString()).to.equal(TOKEN_WITHDRAWAL_AMOUNT.toString());
+
+      const vaultTokenAccount = await getAccount(provider.connection, tokenVault);
+      expect(vaultTokenAccount.amount.toString()).to.equal(
+        TOKEN_INITIAL_AMOUNT.sub(TOKEN_WITHDRAWAL_AMOUNT).toString()
+      );
+    });
+
+    it("rejects premature token finalization", async () => {
+      await requestTokenWithdrawal(TOKEN_WITHDRAWAL_AMOUNT);
+
+      await expectTransactionToFail(
+        program.methods
+          .finalizeToken(vaultId, REQUEST_ID)
+          .accounts({
+            owner: owner.publicKey,
+            receiverTokenAccount,
+            vaultInfo,
+            tokenVault,
+            request: deriveRequest(REQUEST_ID),
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([owner])
+          .rpc()
+      );
+    });
+
+    it("allows recovery to cancel a token request", async () => {
+      await requestTokenWithdrawal(TOKEN_WITHDRAWAL_AMOUNT);
+
+      await program.methods
+        .cancel(vaultId, REQUEST_ID)
+        .accounts({
+          recovery: recovery.publicKey,
+          owner: owner.publicKey,
+          vaultInfo,
+          request: deriveRequest(REQUEST_ID),
+        })
+        .signers([recovery])
+        .rpc();
+
+      const vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
+      expect(vaultAccount.pendingAmount.toNumber()).to.equal(0);
+    });
+
+    it("returns the remaining tokens when closed", async () => {
+      const ownerBalanceBefore = (await getAccount(provider.connection, ownerTokenAccount)).amount;
+
+      await program.methods
+        .closeVault(vaultId)
+        .accounts({
+          owner: owner.publicKey,
+          vaultInfo,
+          tokenVault,
+          ownerTokenAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([owner])
+        .rpc();
+
+      const ownerBalanceAfter = (await getAccount(provider.connection, ownerTokenAccount)).amount;
+      expect((ownerBalanceAfter - ownerBalanceBefore).toString()).to.equal(TOKEN_INITIAL_AMOUNT.toString());
+
+      const tokenVaultInfo = await provider.connection.getAccountInfo(tokenVault);
+      expect(tokenVaultInfo).to.be.null;
+    });
+
+    it("allows recovery to sweep the tokens", async () => {
+      const destinationTokenAccount = (
+        await getOrCreateAssociatedTokenAccount(provider.connection, owner, mint, recovery.publicKey)
+      ).address;
+
+      await program.methods
+        .recoverToken(vaultId)
+        .accounts({
+          recovery: recovery.publicKey,
+          destinationTokenAccount,
+          vaultInfo,
+          tokenVault,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([recovery])
+        .rpc();
+
+      const destinationAccount = await getAccount(provider.connection, destinationTokenAccount);
+      expect(destinationAccount.amount.toString()).to.equal(TOKEN_INITIAL_AMOUNT.toString());
+    });
+  });
+
+  describe("time validation", () => {
+    beforeEach(async () => {
+      await initializeVault();
+    });
+
+    it("enforces wait period", async () => {
+      await program.methods
+        .withdraw(vaultId, REQUEST_ID, WITHDRAWAL_AMOUNT)
+        .accounts({
+          owner: owner.publicKey,
+          receiver: receiver.publicKey,
+          vaultInfo,
+          request: deriveRequest(REQUEST_ID),
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      // Try to finalize immediately - should fail
+      await expectTransactionToFail(
+        program.methods
+          .finalize(vaultId, REQUEST_ID)
+          .accounts({
+            owner: owner.publicKey,
+            receiver: receiver.publicKey,
+            vaultInfo,
+            request: deriveRequest(REQUEST_ID),
+          })
+          .signers([owner])
+          .rpc()
+      );
+    });
+
+    it("prevents early finalization", async () => {
+      await program.methods
+        .withdraw(vaultId, REQUEST_ID, WITHDRAWAL_AMOUNT)
+        .accounts({
+          owner: owner.publicKey,
+          receiver: receiver.publicKey,
+          vaultInfo,
+          request: deriveRequest(REQUEST_ID),
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      const requestAccount = await program.account.requestPda.fetch(deriveRequest(REQUEST_ID));
+      const currentSlot = await provider.connection.getSlot();
+      const requiredSlot = requestAccount.requestTime.toNumber() + WAIT_TIME.toNumber();
+
+      expect(currentSlot).to.be.lessThan(requiredSlot);
+
+      // Should fail until wait time passes
+      await expectTransactionToFail(
+        program.methods
+          .finalize(vaultId, REQUEST_ID)
+          .accounts({
+            owner: owner.publicKey,
+            receiver: receiver.publicKey,
+            vaultInfo,
+            request: deriveRequest(REQUEST_ID),
+          })
+          .signers([owner])
+          .rpc()
+      );
+    });
+  });
+
+  describe("integration scenarios", () => {
+    it("handles full vault lifecycle", async () => {
+      // Initialize
+      await initializeVault();
+      
+      // Request withdrawal
+      await program.methods
+        .withdraw(vaultId, REQUEST_ID, WITHDRAWAL_AMOUNT)
+        .accounts({
+          owner: owner.publicKey,
+          receiver: receiver.publicKey,
+          vaultInfo,
+          request: deriveRequest(REQUEST_ID),
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      // Cancel and request again
+      await program.methods
+        .cancel(vaultId, REQUEST_ID)
+        .accounts({
+          recovery: recovery.publicKey,
+          owner: owner.publicKey,
+          vaultInfo,
+          request: deriveRequest(REQUEST_ID),
+        })
+        .signers([recovery])
+        .rpc();
+
+      // New withdrawal request
+      await program.methods
+        .withdraw(vaultId, REQUEST_ID, WITHDRAWAL_AMOUNT)
+        .accounts({
+          owner: owner.publicKey,
+          receiver: receiver.publicKey,
+          vaultInfo,
+          request: deriveRequest(REQUEST_ID),
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      // Wait and finalize
+      const requestAccount = await program.account.requestPda.fetch(deriveRequest(REQUEST_ID));
+      const targetSlot = requestAccount.requestTime.toNumber() + WAIT_TIME.toNumber();
+      
+      let currentSlot = await provider.connection.getSlot();
+      while (currentSlot < targetSlot) {
+        await new Promise(resolve => setTimeout(resolve, 400));
+        currentSlot = await provider.connection.getSlot();
+      }
+
+      const receiverBalanceBefore = await getAccountBalance(receiver.publicKey);
+
+      await program.methods
+        .finalize(vaultId, REQUEST_ID)
+        .accounts({
+          owner: owner.publicKey,
+          receiver: receiver.publicKey,
+          vaultInfo,
+          request: deriveRequest(REQUEST_ID),
+        })
+        .signers([owner])
+        .rpc();
+
+      const receiverBalanceAfter = await getAccountBalance(receiver.publicKey);
+      expect(receiverBalanceAfter - receiverBalanceBefore).to.equal(WITHDRAWAL_AMOUNT.toNumber());
+    });
+
+    it("handles multiple withdrawal cycles", async () => {
+      await initializeVault();
+
+      // First withdrawal cycle
+      await program.methods
+        .withdraw(vaultId, REQUEST_ID, WITHDRAWAL_AMOUNT)
+        .accounts({
+          owner: owner.publicKey,
+          receiver: receiver.publicKey,
+          vaultInfo,
+          request: deriveRequest(REQUEST_ID),
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      // Wait and finalize first
+      const requestAccount = await program.account.requestPda.fetch(deriveRequest(REQUEST_ID));
+      const targetSlot = requestAccount.requestTime.toNumber() + WAIT_TIME.toNumber();
+      
+      let currentSlot = await provider.connection.getSlot();
+      while (currentSlot < targetSlot) {
+        await new Promise(resolve => setTimeout(resolve, 400));
+        currentSlot = await provider.connection.getSlot();
+      }
+
+      await program.methods
+        .finalize(vaultId, REQUEST_ID)
+        .accounts({
+          owner: owner.publicKey,
+          receiver: receiver.publicKey,
+          vaultInfo,
+          request: deriveRequest(REQUEST_ID),
+        })
+        .signers([owner])
+        .rpc();
+
+      // Second withdrawal cycle
+      const secondWithdrawal = new BN(0.1 * LAMPORTS_PER_SOL);
+      await program.methods
+        .withdraw(vaultId, REQUEST_ID, secondWithdrawal)
+        .accounts({
+          owner: owner.publicKey,
+          receiver: receiver.publicKey,
+          vaultInfo,
+          request: deriveRequest(REQUEST_ID),
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([owner])
+        .rpc();
+
+      // Verify second withdrawal can be made
+      const secondRequest = await program.account.requestPda.fetch(deriveRequest(REQUEST_ID));
+      expect(secondRequest.amount.toString()).to.equal(secondWithdrawal.toString());
+      const vaultAccount = await program.account.vaultInfo.fetch(vaultInfo);
+      expect(vaultAccount.pendingAmount.toString()).to.equal(secondWithdrawal.toString());
     });
   });
 });
@@ -1,5 +1,6 @@
 use anchor_lang::prelude::*;
-use borsh::{BorshDeserialize, BorshSerialize};
+use anchor_spl::token::spl_token;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
 
 declare_id!("7BEre5a4UcJpNUzmoJvxUcWpaw4LrYE8pVhmLxWDrqsS");
 
@@ -9,19 +10,20 @@ pub mod vault {
 
     pub fn initialize(
         ctx: Context<InitializeCtx>,
+        vault_id: u64,
         wait_time: u64,
+        rotation_wait_time: u64,
         initial_amount: u64,
     ) -> Result<()> {
-        require!(wait_time > 0, CustomError::InvalidWaitTime);
-
-        let vault_info = &mut ctx.accounts.vault_info;
-        vault_info.owner = *ctx.accounts.owner.key;
-        vault_info.recovery = *ctx.accounts.recovery.key;
-        vault_info.receiver = Pubkey::default(); // temporal
-        vault_info.wait_time = wait_time;
-        vault_info.request_time = 0;
-        vault_info.amount = 0;
-        vault_info.state = State::Idle;
+        msg!("Vault id: {}", vault_id);
+        init_vault(
+            &mut ctx.accounts.vault_info,
+            ctx.accounts.owner.key(),
+            ctx.accounts.recovery.key(),
+            wait_time,
+            rotation_wait_time,
+            None,
+        )?;
 
         // Transfer lamports
         let transfer_instruction = anchor_lang::solana_program::system_instruction::transfer(
@@ -42,79 +44,661 @@ pub mod vault {
         Ok(())
     }
 
-    pub fn withdraw(ctx: Context<WithdrawCtx>, amount: u64) -> Result<()> {
+    pub fn initialize_token(
+        ctx: Context<InitializeTokenCtx>,
+        vault_id: u64,
+        wait_time: u64,
+        rotation_wait_time: u64,
+        initial_amount: u64, // in base units of the vault mint
+    ) -> Result<()> {
+        msg!("Vault id: {}", vault_id);
+        init_vault(
+            &mut ctx.accounts.vault_info,
+            ctx.accounts.owner.key(),
+            ctx.accounts.recovery.key(),
+            wait_time,
+            rotation_wait_time,
+            Some(ctx.accounts.mint.key()),
+        )?;
+
+        if initial_amount > 0 {
+            token::transfer(
+                CpiContext::new(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.owner_token_account.to_account_info(),
+                        to: ctx.accounts.token_vault.to_account_info(),
+                        authority: ctx.accounts.owner.to_account_info(),
+                    },
+                ),
+                initial_amount,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn deposit(ctx: Context<DepositCtx>, _vault_id: u64, amount: u64) -> Result<()> {
+        require!(amount > 0, CustomError::InvalidAmount);
+
+        // Transfer lamports
+        let transfer_instruction = anchor_lang::solana_program::system_instruction::transfer(
+            &ctx.accounts.sender.key(),
+            &ctx.accounts.vault_info.key(),
+            amount,
+        );
+
+        anchor_lang::solana_program::program::invoke(
+            &transfer_instruction,
+            &[
+                ctx.accounts.sender.to_account_info(),
+                ctx.accounts.vault_info.to_account_info(),
+            ],
+        )
+        .unwrap();
+
+        Ok(())
+    }
+
+    pub fn deposit_token(ctx: Context<DepositTokenCtx>, _vault_id: u64, amount: u64) -> Result<()> {
+        require!(amount > 0, CustomError::InvalidAmount);
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.sender_token_account.to_account_info(),
+                    to: ctx.accounts.token_vault.to_account_info(),
+                    authority: ctx.accounts.sender.to_account_info(),
+                },
+            ),
+            amount,
+        )
+    }
+
+    pub fn withdraw(
+        ctx: Context<WithdrawCtx>,
+        _vault_id: u64,
+        request_id: u64,
+        amount: u64,
+    ) -> Result<()> {
+        let vault_info = &mut ctx.accounts.vault_info;
+        let available = available_lamports(vault_info)?;
+
+        record_request(
+            vault_info,
+            &mut ctx.accounts.request,
+            ctx.accounts.receiver.key(),
+            amount,
+            available,
+        )?;
+
+        msg!("Withdraw request {} of {} lamports", request_id, amount);
+        Ok(())
+    }
+
+    pub fn withdraw_token(
+        ctx: Context<WithdrawTokenCtx>,
+        _vault_id: u64,
+        request_id: u64,
+        amount: u64, // in base units of the vault mint
+    ) -> Result<()> {
+        let available = ctx.accounts.token_vault.amount;
+
+        record_request(
+            &mut ctx.accounts.vault_info,
+            &mut ctx.accounts.request,
+            ctx.accounts.receiver.key(),
+            amount,
+            available,
+        )?;
+
+        msg!("Withdraw request {} of {} tokens", request_id, amount);
+        Ok(())
+    }
+
+    pub fn instant_withdraw(
+        ctx: Context<InstantWithdrawCtx>,
+        _vault_id: u64,
+        amount: u64,
+    ) -> Result<()> {
         let vault_info = &mut ctx.accounts.vault_info;
-        let min_rent_lamports =
-            Rent::get()?.minimum_balance(vault_info.to_account_info().data_len());
+        let available = available_lamports(vault_info)?;
 
-        require!(vault_info.state == State::Idle, CustomError::InvalidState);
+        // Lamports reserved for the pending requests cannot be spent instantly
         require!(amount > 0, CustomError::InvalidAmount);
         require!(
-            vault_info.to_account_info().lamports() - amount >= min_rent_lamports,
+            vault_info
+                .pending_amount
+                .checked_add(amount)
+                .ok_or(CustomError::InvalidAmount)?
+                <= available,
             CustomError::InvalidAmount
         );
 
-        vault_info.amount = amount;
-        vault_info.request_time = Clock::get()?.slot;
-        vault_info.receiver = *ctx.accounts.receiver.key;
-        vault_info.state = State::Req;
+        // A new period starts once the previous one has elapsed
+        let slot = Clock::get()?.slot;
+        if slot >= vault_info.period_start + vault_info.allowance_period {
+            vault_info.period_start = slot;
+            vault_info.spent_in_period = 0;
+        }
+        let spent_in_period = vault_info
+            .spent_in_period
+            .checked_add(amount)
+            .ok_or(CustomError::AllowanceExceeded)?;
+        require!(
+            spent_in_period <= vault_info.allowance,
+            CustomError::AllowanceExceeded
+        );
+        vault_info.spent_in_period = spent_in_period;
+
+        let receiver = &mut ctx.accounts.receiver;
+
+        // Transfer lamports
+        **receiver.to_account_info().try_borrow_mut_lamports()? += amount;
+        **vault_info.to_account_info().try_borrow_mut_lamports()? -= amount;
+
+        Ok(())
+    }
+
+    pub fn finalize(ctx: Context<FinalizeCtx>, _vault_id: u64, _request_id: u64) -> Result<()> {
+        let request = &ctx.accounts.request;
+        let vault_info = &mut ctx.accounts.vault_info;
+        let available = available_lamports(vault_info)?;
+
+        check_finalize(vault_info, request, available)?;
+        release_request(vault_info, request);
+
+        let receiver = &mut ctx.accounts.receiver;
+
+        // Transfer lamports, the request is closed by the context
+        **receiver.to_account_info().try_borrow_mut_lamports()? += request.amount;
+        **vault_info.to_account_info().try_borrow_mut_lamports()? -= request.amount;
+
+        Ok(())
+    }
+
+    pub fn finalize_token(
+        ctx: Context<FinalizeTokenCtx>,
+        vault_id: u64,
+        _request_id: u64,
+    ) -> Result<()> {
+        let request = &ctx.accounts.request;
+        let vault_info = &mut ctx.accounts.vault_info;
+        let available = ctx.accounts.token_vault.amount;
+
+        check_finalize(vault_info, request, available)?;
+        release_request(vault_info, request);
+
+        // Transfer tokens, the request is closed by the context
+        transfer_from_vault(
+            vault_info,
+            vault_id,
+            &ctx.accounts.token_vault,
+            &ctx.accounts.receiver_token_account,
+            &ctx.accounts.token_program,
+            ctx.program_id,
+            request.amount,
+        )
+    }
+
+    pub fn cancel(ctx: Context<CancelCtx>, _vault_id: u64, _request_id: u64) -> Result<()> {
+        // The request is closed by the context, returning its rent to the owner
+        release_request(&mut ctx.accounts.vault_info, &ctx.accounts.request);
+        Ok(())
+    }
+
+    pub fn recover(ctx: Context<RecoverCtx>, _vault_id: u64) -> Result<()> {
+        let vault_info = &mut ctx.accounts.vault_info;
+        let amount = available_lamports(vault_info)?;
+
+        // Every pending request becomes stale, the owner has to issue new ones
+        vault_info.pending_amount = 0;
+        vault_info.generation += 1;
+
+        let destination = &mut ctx.accounts.destination;
+
+        // Transfer lamports
+        **destination.to_account_info().try_borrow_mut_lamports()? += amount;
+        **vault_info.to_account_info().try_borrow_mut_lamports()? -= amount;
+
+        msg!("Recovered {} lamports", amount);
+        Ok(())
+    }
+
+    pub fn recover_token(ctx: Context<RecoverTokenCtx>, vault_id: u64) -> Result<()> {
+        let amount = ctx.accounts.token_vault.amount;
+
+        // Every pending request becomes stale, the owner has to issue new ones
+        let vault_info = &mut ctx.accounts.vault_info;
+        vault_info.pending_amount = 0;
+        vault_info.generation += 1;
+
+        transfer_from_vault(
+            vault_info,
+            vault_id,
+            &ctx.accounts.token_vault,
+            &ctx.accounts.destination_token_account,
+            &ctx.accounts.token_program,
+            ctx.program_id,
+            amount,
+        )?;
+
+        msg!("Recovered {} tokens", amount);
+        Ok(())
+    }
+
+    pub fn close_vault(ctx: Context<CloseVaultCtx>, vault_id: u64) -> Result<()> {
+        let vault_info = &ctx.accounts.vault_info;
+
+        // Stale requests must be cancelled too, otherwise their rent could not be reclaimed
+        require!(vault_info.open_requests == 0, CustomError::RequestPending);
+
+        if vault_info.mint.is_some() {
+            let token_vault = ctx
+                .accounts
+                .token_vault
+                .as_ref()
+                .ok_or(CustomError::InvalidMint)?;
+            let owner_token_account = ctx
+                .accounts
+                .owner_token_account
+                .as_ref()
+                .ok_or(CustomError::InvalidMint)?;
+            let token_program = ctx
+                .accounts
+                .token_program
+                .as_ref()
+                .ok_or(CustomError::InvalidMint)?;
+            require!(
+                Some(owner_token_account.mint) == vault_info.mint,
+                CustomError::InvalidMint
+            );
+
+            transfer_from_vault(
+                vault_info,
+                vault_id,
+                token_vault,
+                owner_token_account,
+                token_program,
+                ctx.program_id,
+                token_vault.amount,
+            )?;
+            close_token_vault(
+                vault_info,
+                vault_id,
+                token_vault,
+                &ctx.accounts.owner,
+                token_program,
+                ctx.program_id,
+            )?;
+        }
+
+        // The vault_info is closed by the context, returning all the lamports, rent included, to the owner
+        Ok(())
+    }
+
+    pub fn rotate_owner(
+        ctx: Context<RotateOwnerCtx>,
+        _vault_id: u64,
+        new_owner: Pubkey,
+    ) -> Result<()> {
+        let vault_info = &mut ctx.accounts.vault_info;
+        require!(
+            vault_info.owner_rotation.is_none(),
+            CustomError::RotationPending
+        );
+
+        vault_info.owner_rotation = Some(Rotation {
+            new_key: new_owner,
+            request_time: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    pub fn rotate_recovery(
+        ctx: Context<RotateRecoveryCtx>,
+        _vault_id: u64,
+        new_recovery: Pubkey,
+    ) -> Result<()> {
+        let vault_info = &mut ctx.accounts.vault_info;
+        require!(
+            vault_info.recovery_rotation.is_none(),
+            CustomError::RotationPending
+        );
 
+        vault_info.recovery_rotation = Some(Rotation {
+            new_key: new_recovery,
+            request_time: Clock::get()?.slot,
+        });
         Ok(())
     }
 
-    pub fn finalize(ctx: Context<FinalizeCtx>) -> Result<()> {
+    pub fn finalize_rotation(ctx: Context<FinalizeRotationCtx>, _vault_id: u64) -> Result<()> {
+        let vault_info = &mut ctx.accounts.vault_info;
         require!(
-            ctx.accounts.vault_info.state == State::Req,
-            CustomError::InvalidState
+            vault_info.owner_rotation.is_some() || vault_info.recovery_rotation.is_some(),
+            CustomError::NoRotationPending
         );
 
+        let slot = Clock::get()?.slot;
+        let mut rotated = false;
+
+        if let Some(rotation) = vault_info.owner_rotation.clone() {
+            if slot >= rotation.request_time + vault_info.rotation_wait_time {
+                vault_info.owner = rotation.new_key;
+                vault_info.owner_rotation = None;
+
+                // Requests issued by the previous owner cannot be finalized anymore
+                vault_info.pending_amount = 0;
+                vault_info.generation += 1;
+                rotated = true;
+            }
+        }
+        if let Some(rotation) = vault_info.recovery_rotation.clone() {
+            if slot >= rotation.request_time + vault_info.rotation_wait_time {
+                vault_info.recovery = rotation.new_key;
+                vault_info.recovery_rotation = None;
+                rotated = true;
+            }
+        }
+
+        require!(rotated, CustomError::EndSlotWasNotReached);
+        Ok(())
+    }
+
+    pub fn request_allowance_change(
+        ctx: Context<RequestAllowanceChangeCtx>,
+        _vault_id: u64,
+        allowance: u64,
+        allowance_period: u64, // in slots
+    ) -> Result<()> {
         require!(
-            Clock::get()?.slot
-                >= ctx.accounts.vault_info.request_time + ctx.accounts.vault_info.wait_time,
-            CustomError::EndSlotWasNotReached
+            allowance == 0 || allowance_period > 0,
+            CustomError::InvalidAllowancePeriod
         );
 
         let vault_info = &mut ctx.accounts.vault_info;
-        vault_info.state = State::Idle;
+        require!(
+            vault_info.allowance_change.is_none(),
+            CustomError::AllowanceChangePending
+        );
 
-        let receiver = &mut ctx.accounts.receiver;
+        vault_info.allowance_change = Some(AllowanceChange {
+            allowance,
+            allowance_period,
+            request_time: Clock::get()?.slot,
+        });
+        Ok(())
+    }
 
-        // Transfer lamports
-        **receiver.to_account_info().try_borrow_mut_lamports()? += vault_info.amount;
-        **vault_info.to_account_info().try_borrow_mut_lamports()? -= vault_info.amount;
+    pub fn finalize_allowance_change(
+        ctx: Context<FinalizeAllowanceChangeCtx>,
+        _vault_id: u64,
+    ) -> Result<()> {
+        let vault_info = &mut ctx.accounts.vault_info;
+        let change = match vault_info.allowance_change.clone() {
+            Some(change) => change,
+            None => return err!(CustomError::NoAllowanceChangePending),
+        };
+
+        // The allowance is subject to the same wait time as a withdraw request
+        let slot = Clock::get()?.slot;
+        require!(
+            slot >= change.request_time + vault_info.wait_time,
+            CustomError::EndSlotWasNotReached
+        );
 
+        vault_info.allowance = change.allowance;
+        vault_info.allowance_period = change.allowance_period;
+        vault_info.period_start = slot;
+        vault_info.spent_in_period = 0;
+        vault_info.allowance_change = None;
         Ok(())
     }
 
-    pub fn cancel(ctx: Context<CancelCtx>) -> Result<()> {
+    pub fn cancel_allowance_change(
+        ctx: Context<CancelAllowanceChangeCtx>,
+        _vault_id: u64,
+    ) -> Result<()> {
+        let vault_info = &mut ctx.accounts.vault_info;
         require!(
-            ctx.accounts.vault_info.state == State::Req,
-            CustomError::InvalidState
+            vault_info.allowance_change.is_some(),
+            CustomError::NoAllowanceChangePending
         );
-        ctx.accounts.vault_info.state = State::Idle;
+
+        vault_info.allowance_change = None;
+        Ok(())
+    }
+
+    pub fn cancel_rotation(ctx: Context<CancelRotationCtx>, _vault_id: u64) -> Result<()> {
+        // Each key can only withdraw its own proposal: letting the key being replaced
+        // cancel its rotation would allow a compromised key to block it forever
+        let vault_info = &mut ctx.accounts.vault_info;
+        let signer = *ctx.accounts.signer.key;
+
+        if signer == vault_info.owner {
+            require!(
+                vault_info.recovery_rotation.is_some(),
+                CustomError::NoRotationPending
+            );
+            vault_info.recovery_rotation = None;
+        } else {
+            require!(
+                vault_info.owner_rotation.is_some(),
+                CustomError::NoRotationPending
+            );
+            vault_info.owner_rotation = None;
+        }
         Ok(())
     }
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq, Clone, InitSpace)]
-pub enum State {
-    Idle = 0,
-    Req = 1,
+fn init_vault(
+    vault_info: &mut Account<VaultInfo>,
+    owner: Pubkey,
+    recovery: Pubkey,
+    wait_time: u64,
+    rotation_wait_time: u64,
+    mint: Option<Pubkey>,
+) -> Result<()> {
+    require!(wait_time > 0, CustomError::InvalidWaitTime);
+    require!(rotation_wait_time > 0, CustomError::InvalidWaitTime);
+
+    vault_info.owner = owner;
+    vault_info.recovery = recovery;
+    vault_info.wait_time = wait_time;
+    vault_info.rotation_wait_time = rotation_wait_time;
+    vault_info.pending_amount = 0;
+    vault_info.open_requests = 0;
+    vault_info.generation = 0;
+    vault_info.owner_rotation = None;
+    vault_info.recovery_rotation = None;
+    vault_info.mint = mint;
+    vault_info.allowance = 0;
+    vault_info.allowance_period = 0;
+    vault_info.period_start = 0;
+    vault_info.spent_in_period = 0;
+    vault_info.allowance_change = None;
+    Ok(())
+}
+
+// The pending requests together can never exceed the available balance,
+// which for native vaults excludes the rent exemption
+fn record_request(
+    vault_info: &mut Account<VaultInfo>,
+    request: &mut Account<RequestPDA>,
+    receiver: Pubkey,
+    amount: u64,
+    available: u64,
+) -> Result<()> {
+    require!(amount > 0, CustomError::InvalidAmount);
+    let pending_amount = vault_info
+        .pending_amount
+        .checked_add(amount)
+        .ok_or(CustomError::InvalidAmount)?;
+    require!(pending_amount <= available, CustomError::InvalidAmount);
+    vault_info.pending_amount = pending_amount;
+    vault_info.open_requests += 1;
+
+    request.receiver = receiver;
+    request.amount = amount;
+    request.request_time = Clock::get()?.slot;
+    request.generation = vault_info.generation;
+    Ok(())
+}
+
+fn check_finalize(vault_info: &VaultInfo, request: &RequestPDA, available: u64) -> Result<()> {
+    // Requests issued before a recovery or an owner rotation can only be cancelled
+    require!(
+        request.generation == vault_info.generation,
+        CustomError::StaleRequest
+    );
+    require!(
+        Clock::get()?.slot >= request.request_time + vault_info.wait_time,
+        CustomError::EndSlotWasNotReached
+    );
+
+    // The balance may have changed since the request was made
+    require!(request.amount <= available, CustomError::InsufficientFunds);
+    Ok(())
+}
+
+// Stale requests were already dropped from the pending amount
+fn release_request(vault_info: &mut VaultInfo, request: &RequestPDA) {
+    if request.generation == vault_info.generation {
+        vault_info.pending_amount -= request.amount;
+    }
+    vault_info.open_requests -= 1;
+}
+
+// Lamports above the rent exemption
+fn available_lamports(vault_info: &Account<VaultInfo>) -> Result<u64> {
+    let min_rent_lamports = Rent::get()?.minimum_balance(vault_info.to_account_info().data_len());
+    let available = vault_info
+        .to_account_info()
+        .lamports()
+        .checked_sub(min_rent_lamports)
+        .ok_or(CustomError::InsufficientFunds)?;
+    Ok(available)
+}
+
+fn transfer_from_vault<'info>(
+    vault_info: &Account<'info, VaultInfo>,
+    vault_id: u64,
+    token_vault: &Account<'info, TokenAccount>,
+    to: &Account<'info, TokenAccount>,
+    token_program: &Program<'info, Token>,
+    program_id: &Pubkey,
+    amount: u64,
+) -> Result<()> {
+    let vault_id_bytes = vault_id.to_le_bytes();
+    let (vault_info_key, vault_info_bump) =
+        Pubkey::find_program_address(&[b"vault", vault_id_bytes.as_ref()], program_id);
+
+    let vault_info_signer_seeds: &[&[&[u8]]] =
+        &[&[b"vault", vault_id_bytes.as_ref(), &[vault_info_bump]]];
+
+    anchor_lang::solana_program::program::invoke_signed(
+        &spl_token::instruction::transfer(
+            &anchor_spl::token::ID,
+            &token_vault.key(),
+            &to.key(),
+            &vault_info_key, //owner
+            &[&vault_info_key],
+            amount,
+        )?,
+        &[
+            token_vault.to_account_info(),
+            to.to_account_info(),
+            vault_info.to_account_info(),
+            token_program.to_account_info(),
+        ],
+        vault_info_signer_seeds,
+    )?;
+
+    Ok(())
+}
+
+fn close_token_vault<'info>(
+    vault_info: &Account<'info, VaultInfo>,
+    vault_id: u64,
+    token_vault: &Account<'info, TokenAccount>,
+    owner: &Signer<'info>,
+    token_program: &Program<'info, Token>,
+    program_id: &Pubkey,
+) -> Result<()> {
+    let vault_id_bytes = vault_id.to_le_bytes();
+    let (vault_info_key, vault_info_bump) =
+        Pubkey::find_program_address(&[b"vault", vault_id_bytes.as_ref()], program_id);
+
+    let vault_info_signer_seeds: &[&[&[u8]]] =
+        &[&[b"vault", vault_id_bytes.as_ref(), &[vault_info_bump]]];
+
+    anchor_lang::solana_program::program::invoke_signed(
+        &spl_token::instruction::close_account(
+            &anchor_spl::token::ID,
+            &token_vault.key(),
+            &owner.key(),
+            &vault_info_key,
+            &[&vault_info_key],
+        )?,
+        &[
+            token_vault.to_account_info(),
+            owner.to_account_info(),
+            vault_info.to_account_info(),
+            token_program.to_account_info(),
+        ],
+        vault_info_signer_seeds,
+    )?;
+
+    Ok(())
 }
 
 #[account]
 #[derive(InitSpace)]
 pub struct VaultInfo {
-    pub owner: Pubkey,     // 32 bytes
-    pub recovery: Pubkey,  // 32 bytes
-    pub receiver: Pubkey,  // 32 bytes
-    pub wait_time: u64,    // 8 bytes
+    pub owner: Pubkey,                             // 32 bytes
+    pub recovery: Pubkey,                          // 32 bytes
+    pub wait_time: u64,                            // 8 bytes
+    pub rotation_wait_time: u64,                   // 8 bytes
+    pub pending_amount: u64,                       // 8 bytes, sum of the pending requests
+    pub open_requests: u64,                        // 8 bytes, stale ones included
+    pub generation: u64,                           // 8 bytes, invalidates older requests
+    pub owner_rotation: Option<Rotation>,          // 1 + 40 bytes
+    pub recovery_rotation: Option<Rotation>,       // 1 + 40 bytes
+    pub mint: Option<Pubkey>,                      // 1 + 32 bytes, None for native vaults
+    pub allowance: u64,                            // 8 bytes, spendable instantly per period
+    pub allowance_period: u64,                     // 8 bytes, in slots
+    pub period_start: u64,                         // 8 bytes
+    pub spent_in_period: u64,                      // 8 bytes
+    pub allowance_change: Option<AllowanceChange>, // 1 + 24 bytes
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Clone, InitSpace)]
+pub struct Rotation {
+    pub new_key: Pubkey,   // 32 bytes
     pub request_time: u64, // 8 bytes
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Clone, InitSpace)]
+pub struct AllowanceChange {
+    pub allowance: u64,        // 8 bytes
+    pub allowance_period: u64, // 8 bytes
+    pub request_time: u64,     // 8 bytes
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct RequestPDA {
+    pub receiver: Pubkey,  // 32 bytes
     pub amount: u64,       // 8 bytes
-    pub state: State,      // see: https://www.anchor-lang.com/docs/space
+    pub request_time: u64, // 8 bytes
+    pub generation: u64,   // 8 bytes
 }
 
 #[derive(Accounts)]
+#[instruction(vault_id: u64)]
 pub struct InitializeCtx<'info> {
     #[account(mut)]
     pub owner: Signer<'info>,
@@ -122,7 +706,7 @@ pub struct InitializeCtx<'info> {
     #[account(
         init, 
         payer = owner, 
-        seeds = [owner.key().as_ref()],
+        seeds = [b"vault", vault_id.to_le_bytes().as_ref()],
         bump,
         space = 8 + VaultInfo::INIT_SPACE
     )]
@@ -131,60 +715,372 @@ pub struct InitializeCtx<'info> {
 }
 
 #[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct InitializeTokenCtx<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub recovery: SystemAccount<'info>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        constraint = owner_token_account.mint == mint.key() @ CustomError::InvalidMint
+    )]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = owner,
+        seeds = [b"vault", vault_id.to_le_bytes().as_ref()],
+        bump,
+        space = 8 + VaultInfo::INIT_SPACE
+    )]
+    pub vault_info: Account<'info, VaultInfo>,
+    #[account(
+        init,
+        payer = owner,
+        seeds = [b"token_vault", vault_id.to_le_bytes().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = vault_info
+    )]
+    pub token_vault: Account<'info, TokenAccount>,
+    // Programs and other
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct DepositCtx<'info> {
+    #[account(mut)]
+    pub sender: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = vault_info.mint.is_none() @ CustomError::InvalidMint,
+    )]
+    pub vault_info: Account<'info, VaultInfo>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct DepositTokenCtx<'info> {
+    pub sender: Signer<'info>,
+    #[account(
+        mut,
+        constraint = Some(sender_token_account.mint) == vault_info.mint @ CustomError::InvalidMint
+    )]
+    pub sender_token_account: Account<'info, TokenAccount>,
+    #[account(seeds = [b"vault", vault_id.to_le_bytes().as_ref()], bump)]
+    pub vault_info: Account<'info, VaultInfo>,
+    #[account(mut, seeds = [b"token_vault", vault_id.to_le_bytes().as_ref()], bump)]
+    pub token_vault: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64, request_id: u64)]
 pub struct WithdrawCtx<'info> {
     #[account(mut)]
     pub owner: Signer<'info>,
     pub receiver: SystemAccount<'info>,
     #[account(
         mut,
-        seeds = [owner.key().as_ref()],
+        seeds = [b"vault", vault_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = vault_info.owner == *owner.key @ CustomError::InvalidOwner,
+        constraint = vault_info.mint.is_none() @ CustomError::InvalidMint,
+    )]
+    pub vault_info: Account<'info, VaultInfo>,
+    #[account(
+        init,
+        payer = owner,
+        seeds = [b"request", vault_info.key().as_ref(), request_id.to_le_bytes().as_ref()],
+        bump,
+        space = 8 + RequestPDA::INIT_SPACE
+    )]
+    pub request: Account<'info, RequestPDA>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64, request_id: u64)]
+pub struct WithdrawTokenCtx<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub receiver: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault_id.to_le_bytes().as_ref()],
         bump,
         constraint = vault_info.owner == *owner.key @ CustomError::InvalidOwner,
     )]
     pub vault_info: Account<'info, VaultInfo>,
+    #[account(seeds = [b"token_vault", vault_id.to_le_bytes().as_ref()], bump)]
+    pub token_vault: Account<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = owner,
+        seeds = [b"request", vault_info.key().as_ref(), request_id.to_le_bytes().as_ref()],
+        bump,
+        space = 8 + RequestPDA::INIT_SPACE
+    )]
+    pub request: Account<'info, RequestPDA>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct InstantWithdrawCtx<'info> {
+    pub owner: Signer<'info>,
+    #[account(mut)]
+    pub receiver: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = vault_info.owner == *owner.key @ CustomError::InvalidOwner,
+        constraint = vault_info.mint.is_none() @ CustomError::InvalidMint,
+    )]
+    pub vault_info: Account<'info, VaultInfo>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64, request_id: u64)]
 pub struct FinalizeCtx<'info> {
     #[account(mut)]
     pub owner: Signer<'info>,
-    #[account(mut, constraint = vault_info.receiver == *receiver.key @ CustomError::InvalidReceiver)]
+    #[account(mut, constraint = request.receiver == *receiver.key @ CustomError::InvalidReceiver)]
     pub receiver: SystemAccount<'info>,
     #[account(
         mut,
-        seeds = [owner.key().as_ref()],
+        seeds = [b"vault", vault_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = vault_info.owner == *owner.key @ CustomError::InvalidOwner,
+        constraint = vault_info.mint.is_none() @ CustomError::InvalidMint,
+    )]
+    pub vault_info: Account<'info, VaultInfo>,
+    #[account(
+        mut,
+        seeds = [b"request", vault_info.key().as_ref(), request_id.to_le_bytes().as_ref()],
+        bump,
+        close = owner
+    )]
+    pub request: Account<'info, RequestPDA>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64, request_id: u64)]
+pub struct FinalizeTokenCtx<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        constraint = receiver_token_account.owner == request.receiver @ CustomError::InvalidReceiver,
+        constraint = Some(receiver_token_account.mint) == vault_info.mint @ CustomError::InvalidMint
+    )]
+    pub receiver_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault_id.to_le_bytes().as_ref()],
         bump,
         constraint = vault_info.owner == *owner.key @ CustomError::InvalidOwner,
     )]
     pub vault_info: Account<'info, VaultInfo>,
+    #[account(mut, seeds = [b"token_vault", vault_id.to_le_bytes().as_ref()], bump)]
+    pub token_vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"request", vault_info.key().as_ref(), request_id.to_le_bytes().as_ref()],
+        bump,
+        close = owner
+    )]
+    pub request: Account<'info, RequestPDA>,
+    pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
+#[instruction(vault_id: u64, request_id: u64)]
 pub struct CancelCtx<'info> {
     #[account(mut)]
     pub recovery: Signer<'info>,
+    #[account(mut)]
     pub owner: SystemAccount<'info>,
     #[account(
         mut,
-        seeds = [owner.key().as_ref()],
+        seeds = [b"vault", vault_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = vault_info.owner == *owner.key @ CustomError::InvalidOwner,
+        constraint = vault_info.recovery == *recovery.key @ CustomError::InvalidRecovery,
+    )]
+    pub vault_info: Account<'info, VaultInfo>,
+    #[account(
+        mut,
+        seeds = [b"request", vault_info.key().as_ref(), request_id.to_le_bytes().as_ref()],
+        bump,
+        close = owner
+    )]
+    pub request: Account<'info, RequestPDA>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct RecoverCtx<'info> {
+    pub recovery: Signer<'info>,
+    #[account(mut)]
+    pub destination: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = vault_info.recovery == *recovery.key @ CustomError::InvalidRecovery,
+        constraint = vault_info.mint.is_none() @ CustomError::InvalidMint,
+    )]
+    pub vault_info: Account<'info, VaultInfo>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct RecoverTokenCtx<'info> {
+    pub recovery: Signer<'info>,
+    #[account(
+        mut,
+        constraint = Some(destination_token_account.mint) == vault_info.mint @ CustomError::InvalidMint
+    )]
+    pub destination_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = vault_info.recovery == *recovery.key @ CustomError::InvalidRecovery,
+    )]
+    pub vault_info: Account<'info, VaultInfo>,
+    #[account(mut, seeds = [b"token_vault", vault_id.to_le_bytes().as_ref()], bump)]
+    pub token_vault: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct CloseVaultCtx<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault_id.to_le_bytes().as_ref()],
         bump,
         constraint = vault_info.owner == *owner.key @ CustomError::InvalidOwner,
+        close = owner
+    )]
+    pub vault_info: Account<'info, VaultInfo>,
+    // only for token vaults
+    #[account(mut, seeds = [b"token_vault", vault_id.to_le_bytes().as_ref()], bump)]
+    pub token_vault: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub owner_token_account: Option<Account<'info, TokenAccount>>,
+    pub token_program: Option<Program<'info, Token>>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct RotateOwnerCtx<'info> {
+    pub recovery: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault_id.to_le_bytes().as_ref()],
+        bump,
         constraint = vault_info.recovery == *recovery.key @ CustomError::InvalidRecovery,
     )]
     pub vault_info: Account<'info, VaultInfo>,
 }
 
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct RotateRecoveryCtx<'info> {
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = vault_info.owner == *owner.key @ CustomError::InvalidOwner,
+    )]
+    pub vault_info: Account<'info, VaultInfo>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct FinalizeRotationCtx<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub vault_info: Account<'info, VaultInfo>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct RequestAllowanceChangeCtx<'info> {
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = vault_info.owner == *owner.key @ CustomError::InvalidOwner,
+    )]
+    pub vault_info: Account<'info, VaultInfo>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct FinalizeAllowanceChangeCtx<'info> {
+    pub owner: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = vault_info.owner == *owner.key @ CustomError::InvalidOwner,
+    )]
+    pub vault_info: Account<'info, VaultInfo>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct CancelAllowanceChangeCtx<'info> {
+    pub recovery: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = vault_info.recovery == *recovery.key @ CustomError::InvalidRecovery,
+    )]
+    pub vault_info: Account<'info, VaultInfo>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct CancelRotationCtx<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = vault_info.owner == *signer.key || vault_info.recovery == *signer.key @ CustomError::InvalidSigner,
+    )]
+    pub vault_info: Account<'info, VaultInfo>,
+}
+
 #[error_code]
 pub enum CustomError {
     #[msg("Invalid wait time, must be greater than 0")]
     InvalidWaitTime,
 
-    #[msg("Invalid amount, must be greater than 0 and preserve the rent exemption")]
+    #[msg("Invalid amount, must be greater than 0 and, with the pending requests, preserve the rent exemption")]
     InvalidAmount,
 
-    #[msg("Invalid state")]
-    InvalidState,
-
     #[msg("Invalid owner")]
     InvalidOwner,
 
@@ -196,4 +1092,37 @@ pub enum CustomError {
 
     #[msg("The end slot (request time + wait time) was not reached")]
     EndSlotWasNotReached,
+
+    #[msg("The request was issued before a recovery or an owner rotation")]
+    StaleRequest,
+
+    #[msg("Invalid signer, must be the owner or the recovery")]
+    InvalidSigner,
+
+    #[msg("A rotation of this key is already pending")]
+    RotationPending,
+
+    #[msg("No rotation pending")]
+    NoRotationPending,
+
+    #[msg("The mint does not match the vault")]
+    InvalidMint,
+
+    #[msg("The amount exceeds the allowance left in the current period")]
+    AllowanceExceeded,
+
+    #[msg("Invalid allowance period, must be greater than 0")]
+    InvalidAllowancePeriod,
+
+    #[msg("An allowance change is already pending")]
+    AllowanceChangePending,
+
+    #[msg("No allowance change pending")]
+    NoAllowanceChangePending,
+
+    #[msg("The vault balance does not cover the amount")]
+    InsufficientFunds,
+
+    #[msg("A withdraw request is still pending")]
+    RequestPending,
 }
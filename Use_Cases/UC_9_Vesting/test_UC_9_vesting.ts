@@ -1,13 +1,43 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { PublicKey, Keypair, SystemProgram, LAMPORTS_PER_SOL } from "@solana/web3.js";
+import {
+  TOKEN_PROGRAM_ID,
+  createMint,
+  mintTo,
+  getOrCreateAssociatedTokenAccount,
+  getAccount,
+} from "@solana/spl-token";
 import { assert, expect } from "chai";
 import { BN } from "bn.js";
 
 interface VestingProgram extends Program {
   methods: {
-    initialize: (startSlot: BN, duration: BN, lamportsAmount: BN) => any;
-    release: () => any;
+    initialize: (
+      grantId: BN,
+      startSlot: BN,
+      duration: BN,
+      lamportsAmount: BN,
+      cliff: BN,
+      schedule: any,
+      revocable: boolean,
+      transferApproval: boolean
+    ) => any;
+    initializeToken: (
+      grantId: BN,
+      startSlot: BN,
+      duration: BN,
+      tokenAmount: BN,
+      cliff: BN,
+      schedule: any,
+      revocable: boolean,
+      transferApproval: boolean
+    ) => any;
+    release: (grantId: BN) => any;
+    releaseToken: (grantId: BN) => any;
+    revoke: (grantId: BN) => any;
+    revokeToken: (grantId: BN) => any;
+    transferBeneficiary: (grantId: BN) => any;
   };
   account: {
     vestingInfo: {
@@ -17,11 +47,19 @@ interface VestingProgram extends Program {
 }
 
 interface VestingInfo {
+  grantId: BN;
+  totalAmount: BN;
   released: BN;
   funder: PublicKey;
   beneficiary: PublicKey;
   startSlot: BN;
   duration: BN;
+  cliff: BN;
+  schedule: any;
+  revocable: boolean;
+  revoked: boolean;
+  mint: PublicKey | null;
+  transferApproval: boolean;
 }
 
 describe("Vesting Program", () => {
@@ -34,6 +72,9 @@ describe("Vesting Program", () => {
   
   const VESTING_AMOUNT = new BN(10 * LAMPORTS_PER_SOL);
   const DURATION_SLOTS = new BN(1000);
+  const NO_CLIFF = new BN(0);
+  const GRANT_ID = new BN(0);
+  const LINEAR = { linear: {} };
 
   before(async () => {
     provider = anchor.AnchorProvider.env();
@@ -52,7 +93,7 @@ describe("Vesting Program", () => {
 
     // Derive PDA
     [vestingInfoPda, vestingInfoBump] = PublicKey.findProgramAddressSync(
-      [beneficiary.publicKey.toBuffer()],
+      [funder.publicKey.toBuffer(), GRANT_ID.toArrayLike(Buffer, "le", 8)],
       program.programId
     );
   });
@@ -83,7 +124,7 @@ describe("Vesting Program", () => {
       const funderBalanceBefore = await getAccountBalance(funder.publicKey);
       
       await program.methods
-        .initialize(futureStartSlot, DURATION_SLOTS, VESTING_AMOUNT)
+        .initialize(GRANT_ID, futureStartSlot, DURATION_SLOTS, VESTING_AMOUNT, NO_CLIFF, LINEAR, false, false)
         .accounts({
           funder: funder.publicKey,
           beneficiary: beneficiary.publicKey,
@@ -101,6 +142,9 @@ describe("Vesting Program", () => {
       expect(vestingInfo.startSlot.toString()).to.equal(futureStartSlot.toString());
       expect(vestingInfo.duration.toString()).to.equal(DURATION_SLOTS.toString());
       expect(vestingInfo.released.toString()).to.equal("0");
+      expect(vestingInfo.totalAmount.toString()).to.equal(VESTING_AMOUNT.toString());
+      expect(vestingInfo.cliff.toString()).to.equal("0");
+      expect(vestingInfo.schedule).to.deep.equal(LINEAR);
 
       // Verify lamports were transferred
       const funderBalanceAfter = await getAccountBalance(funder.publicKey);
@@ -112,8 +156,9 @@ describe("Vesting Program", () => {
 
     it("rejects past start slot", async () => {
       const newBeneficiary = Keypair.generate();
+      const newGrantId = new BN(1);
       const [newVestingPda] = PublicKey.findProgramAddressSync(
-        [newBeneficiary.publicKey.toBuffer()],
+        [funder.publicKey.toBuffer(), newGrantId.toArrayLike(Buffer, "le", 8)],
         program.programId
       );
       
@@ -122,7 +167,7 @@ describe("Vesting Program", () => {
 
       await expectTransactionToFail(
         program.methods
-          .initialize(pastStartSlot, DURATION_SLOTS, VESTING_AMOUNT)
+          .initialize(newGrantId, pastStartSlot, DURATION_SLOTS, VESTING_AMOUNT, NO_CLIFF, LINEAR, false, false)
           .accounts({
             funder: funder.publicKey,
             beneficiary: newBeneficiary.publicKey,
@@ -136,8 +181,9 @@ describe("Vesting Program", () => {
 
     it("rejects zero duration", async () => {
       const newBeneficiary = Keypair.generate();
+      const newGrantId = new BN(1);
       const [newVestingPda] = PublicKey.findProgramAddressSync(
-        [newBeneficiary.publicKey.toBuffer()],
+        [funder.publicKey.toBuffer(), newGrantId.toArrayLike(Buffer, "le", 8)],
         program.programId
       );
       
@@ -146,7 +192,7 @@ describe("Vesting Program", () => {
 
       await expectTransactionToFail(
         program.methods
-          .initialize(futureStartSlot, new BN(0), VESTING_AMOUNT)
+          .initialize(newGrantId, futureStartSlot, new BN(0), VESTING_AMOUNT, NO_CLIFF, LINEAR, false, false)
           .accounts({
             funder: funder.publicKey,
             beneficiary: newBeneficiary.publicKey,
@@ -167,7 +213,7 @@ describe("Vesting Program", () => {
       await new Promise(resolve => setTimeout(resolve, 1000));
       
       const [newVestingPda] = PublicKey.findProgramAddressSync(
-        [newBeneficiary.publicKey.toBuffer()],
+        [newFunder.publicKey.toBuffer(), GRANT_ID.toArrayLike(Buffer, "le", 8)],
         program.programId
       );
       
@@ -178,7 +224,7 @@ describe("Vesting Program", () => {
       const testAmount = new BN(5 * LAMPORTS_PER_SOL);
 
       await program.methods
-        .initialize(futureStartSlot, DURATION_SLOTS, testAmount)
+        .initialize(GRANT_ID, futureStartSlot, DURATION_SLOTS, testAmount, NO_CLIFF, LINEAR, false, false)
         .accounts({
           funder: newFunder.publicKey,
           beneficiary: newBeneficiary.publicKey,
@@ -212,7 +258,7 @@ describe("Vesting Program", () => {
       await new Promise(resolve => setTimeout(resolve, 1000));
       
       [testVestingPda] = PublicKey.findProgramAddressSync(
-        [testBeneficiary.publicKey.toBuffer()],
+        [testFunder.publicKey.toBuffer(), GRANT_ID.toArrayLike(Buffer, "le", 8)],
         program.programId
       );
     });
@@ -222,7 +268,7 @@ describe("Vesting Program", () => {
       startSlot = currentSlot + 10; // Start in near future
       
       await program.methods
-        .initialize(new BN(startSlot), DURATION_SLOTS, VESTING_AMOUNT)
+        .initialize(GRANT_ID, new BN(startSlot), DURATION_SLOTS, VESTING_AMOUNT, NO_CLIFF, LINEAR, false, false)
         .accounts({
           funder: testFunder.publicKey,
           beneficiary: testBeneficiary.publicKey,
@@ -238,8 +284,9 @@ describe("Vesting Program", () => {
       const beneficiaryBalanceBefore = await getAccountBalance(testBeneficiary.publicKey);
 
       await program.methods
-        .release()
+        .release(GRANT_ID)
         .accountsPartial({
+          caller: testBeneficiary.publicKey,
           beneficiary: testBeneficiary.publicKey,
           funder: testFunder.publicKey,
           vestingInfo: testVestingPda,
@@ -253,12 +300,63 @@ describe("Vesting Program", () => {
       expect(beneficiaryBalanceAfter).to.be.greaterThan(beneficiaryBalanceBefore);
     });
 
+    it("allows anyone to release to the beneficiary", async () => {
+      const crank = Keypair.generate();
+      await provider.connection.requestAirdrop(crank.publicKey, LAMPORTS_PER_SOL);
+
+      const currentSlot = await getCurrentSlot();
+      startSlot = currentSlot + 10;
+
+      await program.methods
+        .initialize(GRANT_ID, new BN(startSlot), DURATION_SLOTS, VESTING_AMOUNT, NO_CLIFF, LINEAR, false, false)
+        .accounts({
+          funder: testFunder.publicKey,
+          beneficiary: testBeneficiary.publicKey,
+          vestingInfo: testVestingPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([testFunder])
+        .rpc();
+
+      await new Promise(resolve => setTimeout(resolve, 5000));
+
+      const beneficiaryBalanceBefore = await getAccountBalance(testBeneficiary.publicKey);
+
+      await program.methods
+        .release(GRANT_ID)
+        .accountsPartial({
+          caller: crank.publicKey,
+          beneficiary: testBeneficiary.publicKey,
+          funder: testFunder.publicKey,
+          vestingInfo: testVestingPda,
+        })
+        .signers([crank])
+        .rpc();
+
+      const beneficiaryBalanceAfter = await getAccountBalance(testBeneficiary.publicKey);
+      expect(beneficiaryBalanceAfter).to.be.greaterThan(beneficiaryBalanceBefore);
+
+      // The funds cannot be redirected to the caller
+      await expectTransactionToFail(
+        program.methods
+          .release(GRANT_ID)
+          .accountsPartial({
+            caller: crank.publicKey,
+            beneficiary: crank.publicKey,
+            funder: testFunder.publicKey,
+            vestingInfo: testVestingPda,
+          })
+          .signers([crank])
+          .rpc()
+      );
+    });
+
     it("rejects release before start slot", async () => {
       const currentSlot = await getCurrentSlot();
       const futureStartSlot = currentSlot + 1000; // Far in the future
       
       await program.methods
-        .initialize(new BN(futureStartSlot), DURATION_SLOTS, VESTING_AMOUNT)
+        .initialize(GRANT_ID, new BN(futureStartSlot), DURATION_SLOTS, VESTING_AMOUNT, NO_CLIFF, LINEAR, false, false)
         .accounts({
           funder: testFunder.publicKey,
           beneficiary: testBeneficiary.publicKey,
@@ -270,8 +368,9 @@ describe("Vesting Program", () => {
 
       await expectTransactionToFail(
         program.methods
-          .release()
+          .release(GRANT_ID)
           .accountsPartial({
+            caller: testBeneficiary.publicKey,
             beneficiary: testBeneficiary.publicKey,
             funder: testFunder.publicKey,
             vestingInfo: testVestingPda,
@@ -287,7 +386,7 @@ describe("Vesting Program", () => {
       const shortDuration = 100; // Longer duration for better testing
       
       await program.methods
-        .initialize(new BN(startSlot), new BN(shortDuration), VESTING_AMOUNT)
+        .initialize(GRANT_ID, new BN(startSlot), new BN(shortDuration), VESTING_AMOUNT, NO_CLIFF, LINEAR, false, false)
         .accounts({
           funder: testFunder.publicKey,
           beneficiary: testBeneficiary.publicKey,
@@ -303,8 +402,9 @@ describe("Vesting Program", () => {
       const beneficiaryBalanceBefore = await getAccountBalance(testBeneficiary.publicKey);
 
       await program.methods
-        .release()
+        .release(GRANT_ID)
         .accountsPartial({
+          caller: testBeneficiary.publicKey,
           beneficiary: testBeneficiary.publicKey,
           funder: testFunder.publicKey,
           vestingInfo: testVestingPda,
@@ -326,7 +426,7 @@ describe("Vesting Program", () => {
       const shortDuration = 20; // Short duration that will complete quickly
       
       await program.methods
-        .initialize(new BN(startSlot), new BN(shortDuration), VESTING_AMOUNT)
+        .initialize(GRANT_ID, new BN(startSlot), new BN(shortDuration), VESTING_AMOUNT, NO_CLIFF, LINEAR, false, false)
         .accounts({
           funder: testFunder.publicKey,
           beneficiary: testBeneficiary.publicKey,
@@ -351,8 +451,9 @@ describe("Vesting Program", () => {
       console.log(`Duration: ${vestingInfoBefore.duration.toString()}`);
 
       await program.methods
-        .release()
+        .release(GRANT_ID)
         .accountsPartial({
+          caller: testBeneficiary.publicKey,
           beneficiary: testBeneficiary.publicKey,
           funder: testFunder.publicKey,
           vestingInfo: testVestingPda,
@@ -392,7 +493,7 @@ describe("Vesting Program", () => {
       const testDuration = 100;
       
       await program.methods
-        .initialize(new BN(startSlot), new BN(testDuration), VESTING_AMOUNT)
+        .initialize(GRANT_ID, new BN(startSlot), new BN(testDuration), VESTING_AMOUNT, NO_CLIFF, LINEAR, false, false)
         .accounts({
           funder: testFunder.publicKey,
           beneficiary: testBeneficiary.publicKey,
@@ -409,8 +510,9 @@ describe("Vesting Program", () => {
       const vestingInfoBefore: VestingInfo = await program.account.vestingInfo.fetch(testVestingPda);
 
       await program.methods
-        .release()
+        .release(GRANT_ID)
         .accountsPartial({
+          caller: testBeneficiary.publicKey,
           beneficiary: testBeneficiary.publicKey,
           funder: testFunder.publicKey,
           vestingInfo: testVestingPda,
@@ -434,7 +536,7 @@ describe("Vesting Program", () => {
       const completedDuration = 20; // Short duration
       
       await program.methods
-        .initialize(new BN(startSlot), new BN(completedDuration), VESTING_AMOUNT)
+        .initialize(GRANT_ID, new BN(startSlot), new BN(completedDuration), VESTING_AMOUNT, NO_CLIFF, LINEAR, false, false)
         .accounts({
           funder: testFunder.publicKey,
           beneficiary: testBeneficiary.publicKey,
@@ -454,8 +556,9 @@ describe("Vesting Program", () => {
       console.log(`Vesting balance before: ${vestingBalanceBefore}`);
 
       await program.methods
-        .release()
+        .release(GRANT_ID)
         .accountsPartial({
+          caller: testBeneficiary.publicKey,
           beneficiary: testBeneficiary.publicKey,
           funder: testFunder.publicKey,
           vestingInfo: testVestingPda,
@@ -485,6 +588,587 @@ describe("Vesting Program", () => {
     });
   });
 
+  describe("vesting schedules", () => {
+    let testFunder: Keypair;
+    let testBeneficiary: Keypair;
+    let testVestingPda: PublicKey;
+
+    beforeEach(async () => {
+      testFunder = Keypair.generate();
+      testBeneficiary = Keypair.generate();
+
+      await provider.connection.requestAirdrop(testFunder.publicKey, 20 * LAMPORTS_PER_SOL);
+      await provider.connection.requestAirdrop(testBeneficiary.publicKey, LAMPORTS_PER_SOL);
+      await new Promise(resolve => setTimeout(resolve, 1000));
+
+      [testVestingPda] = PublicKey.findProgramAddressSync(
+        [testFunder.publicKey.toBuffer(), GRANT_ID.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+    });
+
+    const initializeWith = (startSlot: number, duration: number | BN, cliff: number, schedule: any) =>
+      program.methods
+        .initialize(GRANT_ID, new BN(startSlot), new BN(duration), VESTING_AMOUNT, new BN(cliff), schedule, false, false)
+        .accounts({
+          funder: testFunder.publicKey,
+          beneficiary: testBeneficiary.publicKey,
+          vestingInfo: testVestingPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([testFunder])
+        .rpc();
+
+    const release = () =>
+      program.methods
+        .release(GRANT_ID)
+        .accountsPartial({
+          caller: testBeneficiary.publicKey,
+          beneficiary: testBeneficiary.publicKey,
+          funder: testFunder.publicKey,
+          vestingInfo: testVestingPda,
+        })
+        .signers([testBeneficiary])
+        .rpc();
+
+    it("releases nothing before the cliff", async () => {
+      const startSlot = (await getCurrentSlot()) + 10;
+      await initializeWith(startSlot, 1000, 500, LINEAR);
+
+      // Wait past the start slot but well before the cliff
+      await new Promise(resolve => setTimeout(resolve, 5000));
+      await release();
+
+      const vestingInfo: VestingInfo = await program.account.vestingInfo.fetch(testVestingPda);
+      expect(vestingInfo.released.toNumber()).to.equal(0);
+    });
+
+    it("rejects a cliff longer than the duration", async () => {
+      const startSlot = (await getCurrentSlot()) + 10;
+      await expectTransactionToFail(initializeWith(startSlot, 100, 101, LINEAR));
+    });
+
+    it("rejects a duration ending past the last slot", async () => {
+      const startSlot = (await getCurrentSlot()) + 10;
+      const maxDuration = new BN("18446744073709551615"); // u64::MAX
+      await expectTransactionToFail(initializeWith(startSlot, maxDuration, 0, LINEAR));
+    });
+
+    it("releases whole steps only", async () => {
+      const startSlot = (await getCurrentSlot()) + 10;
+      const duration = 100;
+      const interval = 20;
+      await initializeWith(startSlot, duration, 0, { stepwise: { interval: new BN(interval) } });
+
+      await new Promise(resolve => setTimeout(resolve, 5000));
+      await release();
+
+      // The released amount is a multiple of the per-step amount
+      const stepAmount = VESTING_AMOUNT.muln(interval).divn(duration);
+      const vestingInfo: VestingInfo = await program.account.vestingInfo.fetch(testVestingPda);
+      expect(vestingInfo.released.mod(stepAmount).toNumber()).to.equal(0);
+      expect(vestingInfo.released.lt(VESTING_AMOUNT)).to.be.true;
+    });
+
+    it("rejects a zero step interval", async () => {
+      const startSlot = (await getCurrentSlot()) + 10;
+      await expectTransactionToFail(
+        initializeWith(startSlot, 100, 0, { stepwise: { interval: new BN(0) } })
+      );
+    });
+
+    it("follows a piecewise schedule", async () => {
+      const startSlot = (await getCurrentSlot()) + 10;
+      const duration = 100;
+      const points = [
+        // Half of the amount unlocks right after the start
+        { slot: new BN(startSlot + 1), amount: VESTING_AMOUNT.divn(2) },
+        { slot: new BN(startSlot + duration), amount: VESTING_AMOUNT },
+      ];
+      await initializeWith(startSlot, duration, 0, { piecewise: { points } });
+
+      await new Promise(resolve => setTimeout(resolve, 5000));
+      await release();
+
+      const vestingInfo: VestingInfo = await program.account.vestingInfo.fetch(testVestingPda);
+      expect(vestingInfo.released.gte(VESTING_AMOUNT.divn(2))).to.be.true;
+      expect(vestingInfo.released.lt(VESTING_AMOUNT)).to.be.true;
+    });
+
+    it("rejects a piecewise schedule not ending with the whole amount", async () => {
+      const startSlot = (await getCurrentSlot()) + 10;
+      const duration = 100;
+      const points = [{ slot: new BN(startSlot + duration), amount: VESTING_AMOUNT.divn(2) }];
+      await expectTransactionToFail(initializeWith(startSlot, duration, 0, { piecewise: { points } }));
+    });
+
+    it("rejects non-increasing piecewise slots", async () => {
+      const startSlot = (await getCurrentSlot()) + 10;
+      const duration = 100;
+      const points = [
+        { slot: new BN(startSlot + 50), amount: VESTING_AMOUNT.divn(2) },
+        { slot: new BN(startSlot + 50), amount: VESTING_AMOUNT.divn(2) },
+        { slot: new BN(startSlot + duration), amount: VESTING_AMOUNT },
+      ];
+      await expectTransactionToFail(initializeWith(startSlot, duration, 0, { piecewise: { points } }));
+    });
+  });
+
+  describe("revoke()", () => {
+    let testFunder: Keypair;
+    let testBeneficiary: Keypair;
+    let testVestingPda: PublicKey;
+
+    beforeEach(async () => {
+      testFunder = Keypair.generate();
+      testBeneficiary = Keypair.generate();
+
+      await provider.connection.requestAirdrop(testFunder.publicKey, 20 * LAMPORTS_PER_SOL);
+      await provider.connection.requestAirdrop(testBeneficiary.publicKey, LAMPORTS_PER_SOL);
+      await new Promise(resolve => setTimeout(resolve, 1000));
+
+      [testVestingPda] = PublicKey.findProgramAddressSync(
+        [testFunder.publicKey.toBuffer(), GRANT_ID.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+    });
+
+    const initializeWith = async (revocable: boolean) => {
+      const startSlot = (await getCurrentSlot()) + 10;
+      await program.methods
+        .initialize(GRANT_ID, new BN(startSlot), new BN(1000), VESTING_AMOUNT, NO_CLIFF, LINEAR, revocable, false)
+        .accounts({
+          funder: testFunder.publicKey,
+          beneficiary: testBeneficiary.publicKey,
+          vestingInfo: testVestingPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([testFunder])
+        .rpc();
+    };
+
+    const revoke = (signer: Keypair) =>
+      program.methods
+        .revoke(GRANT_ID)
+        .accountsPartial({
+          funder: signer.publicKey,
+          beneficiary: testBeneficiary.publicKey,
+          vestingInfo: testVestingPda,
+        })
+        .signers([signer])
+        .rpc();
+
+    it("splits the balance between beneficiary and funder", async () => {
+      await initializeWith(true);
+      await new Promise(resolve => setTimeout(resolve, 5000));
+
+      const beneficiaryBalanceBefore = await getAccountBalance(testBeneficiary.publicKey);
+      const funderBalanceBefore = await getAccountBalance(testFunder.publicKey);
+
+      await revoke(testFunder);
+
+      const beneficiaryBalanceAfter = await getAccountBalance(testBeneficiary.publicKey);
+      const funderBalanceAfter = await getAccountBalance(testFunder.publicKey);
+      const vested = beneficiaryBalanceAfter - beneficiaryBalanceBefore;
+      const unvested = funderBalanceAfter - funderBalanceBefore;
+
+      expect(vested).to.be.greaterThan(0);
+      expect(unvested).to.be.greaterThan(0);
+      // The funder pays the transaction fee
+      expect(vested + unvested).to.be.closeTo(VESTING_AMOUNT.toNumber(), 10_000);
+
+      const vestingInfo: VestingInfo = await program.account.vestingInfo.fetch(testVestingPda);
+      expect(vestingInfo.revoked).to.be.true;
+      expect(vestingInfo.released.toNumber()).to.equal(vested);
+    });
+
+    it("rejects release after revocation", async () => {
+      await initializeWith(true);
+      await revoke(testFunder);
+
+      await expectTransactionToFail(
+        program.methods
+          .release(GRANT_ID)
+          .accountsPartial({
+            caller: testBeneficiary.publicKey,
+            beneficiary: testBeneficiary.publicKey,
+            funder: testFunder.publicKey,
+            vestingInfo: testVestingPda,
+          })
+          .signers([testBeneficiary])
+          .rpc()
+      );
+    });
+
+    it("rejects revoking a non-revocable vesting", async () => {
+      await initializeWith(false);
+      await expectTransactionToFail(revoke(testFunder));
+    });
+
+    it("rejects revocation by someone other than the funder", async () => {
+      await initializeWith(true);
+      await expectTransactionToFail(revoke(testBeneficiary));
+    });
+  });
+
+  describe("token vesting", () => {
+    const TOKEN_AMOUNT = new BN(1_000_000_000);
+
+    let testFunder: Keypair;
+    let testBeneficiary: Keypair;
+    let testVestingPda: PublicKey;
+    let vault: PublicKey;
+    let mint: PublicKey;
+    let funderTokenAccount: PublicKey;
+    let beneficiaryTokenAccount: PublicKey;
+
+    beforeEach(async () => {
+      testFunder = Keypair.generate();
+      testBeneficiary = Keypair.generate();
+
+      await provider.connection.requestAirdrop(testFunder.publicKey, 20 * LAMPORTS_PER_SOL);
+      await provider.connection.requestAirdrop(testBeneficiary.publicKey, LAMPORTS_PER_SOL);
+      await new Promise(resolve => setTimeout(resolve, 1000));
+
+      [testVestingPda] = PublicKey.findProgramAddressSync(
+        [testFunder.publicKey.toBuffer(), GRANT_ID.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [vault] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), testFunder.publicKey.toBuffer(), GRANT_ID.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
+      mint = await createMint(provider.connection, testFunder, testFunder.publicKey, null, 6);
+      funderTokenAccount = (
+        await getOrCreateAssociatedTokenAccount(provider.connection, testFunder, mint, testFunder.publicKey)
+      ).address;
+      beneficiaryTokenAccount = (
+        await getOrCreateAssociatedTokenAccount(provider.connection, testFunder, mint, testBeneficiary.publicKey)
+      ).address;
+      await mintTo(provider.connection, testFunder, mint, funderTokenAccount, testFunder, TOKEN_AMOUNT.toNumber());
+    });
+
+    const initializeToken = async (duration: number, revocable: boolean) => {
+      const startSlot = (await getCurrentSlot()) + 10;
+      await program.methods
+        .initializeToken(GRANT_ID, new BN(startSlot), new BN(duration), TOKEN_AMOUNT, NO_CLIFF, LINEAR, revocable, false)
+        .accounts({
+          funder: testFunder.publicKey,
+          beneficiary: testBeneficiary.publicKey,
+          mint,
+          funderTokenAccount,
+          vestingInfo: testVestingPda,
+          vault,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([testFunder])
+        .rpc();
+    };
+
+    const releaseToken = () =>
+      program.methods
+        .releaseToken(GRANT_ID)
+        .accountsPartial({
+          caller: testBeneficiary.publicKey,
+          beneficiary: testBeneficiary.publicKey,
+          funder: testFunder.publicKey,
+          mint,
+          vestingInfo: testVestingPda,
+          vault,
+          beneficiaryTokenAccount,
+          funderTokenAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([testBeneficiary])
+        .rpc();
+
+    it("deposits the tokens into a vault owned by the vesting account", async () => {
+      await initializeToken(1000, false);
+
+      const vaultAccount = await getAccount(provider.connection, vault);
+      expect(vaultAccount.amount.toString()).to.equal(TOKEN_AMOUNT.toString());
+      expect(vaultAccount.owner.toString()).to.equal(testVestingPda.toString());
+
+      const vestingInfo: VestingInfo = await program.account.vestingInfo.fetch(testVestingPda);
+      expect(vestingInfo.mint.toString()).to.equal(mint.toString());
+    });
+
+    it("releases vested tokens to the beneficiary's token account", async () => {
+      await initializeToken(100, false);
+      await new Promise(resolve => setTimeout(resolve, 5000));
+
+      await releaseToken();
+
+      const beneficiaryAccount = await getAccount(provider.connection, beneficiaryTokenAccount);
+      const vestingInfo: VestingInfo = await program.account.vestingInfo.fetch(testVestingPda);
+      expect(Number(beneficiaryAccount.amount)).to.be.greaterThan(0);
+      expect(beneficiaryAccount.amount.toString()).to.equal(vestingInfo.released.toString());
+    });
+
+    it("closes the vault once everything is released", async () => {
+      await initializeToken(20, false);
+      await new Promise(resolve => setTimeout(resolve, 15000));
+
+      await releaseToken();
+
+      const beneficiaryAccount = await getAccount(provider.connection, beneficiaryTokenAccount);
+      expect(beneficiaryAccount.amount.toString()).to.equal(TOKEN_AMOUNT.toString());
+      expect(await provider.connection.getAccountInfo(vault)).to.be.null;
+    });
+
+    it("closes the vault even if tokens were sent to it directly", async () => {
+      await initializeToken(20, false);
+      // Tokens sent to the vault outside of the schedule
+      await mintTo(provider.connection, testFunder, mint, vault, testFunder, 5);
+      await new Promise(resolve => setTimeout(resolve, 15000));
+
+      await releaseToken();
+
+      // The beneficiary gets the schedule, the funder gets back the stray tokens
+      const beneficiaryAccount = await getAccount(provider.connection, beneficiaryTokenAccount);
+      const funderAccount = await getAccount(provider.connection, funderTokenAccount);
+      expect(beneficiaryAccount.amount.toString()).to.equal(TOKEN_AMOUNT.toString());
+      expect(funderAccount.amount.toString()).to.equal("5");
+      expect(await provider.connection.getAccountInfo(vault)).to.be.null;
+      expect(await provider.connection.getAccountInfo(testVestingPda)).to.be.null;
+    });
+
+    it("rejects the native release on a token vesting", async () => {
+      await initializeToken(100, false);
+      await new Promise(resolve => setTimeout(resolve, 5000));
+
+      await expectTransactionToFail(
+        program.methods
+          .release(GRANT_ID)
+          .accountsPartial({
+            caller: testBeneficiary.publicKey,
+            beneficiary: testBeneficiary.publicKey,
+            funder: testFunder.publicKey,
+            vestingInfo: testVestingPda,
+          })
+          .signers([testBeneficiary])
+          .rpc()
+      );
+    });
+
+    it("returns the unvested tokens on revocation and closes the vesting accounts", async () => {
+      await initializeToken(1000, true);
+      // Tokens sent to the vault outside of the schedule
+      await mintTo(provider.connection, testFunder, mint, vault, testFunder, 5);
+      await new Promise(resolve => setTimeout(resolve, 5000));
+
+      await program.methods
+        .revokeToken(GRANT_ID)
+        .accountsPartial({
+          funder: testFunder.publicKey,
+          beneficiary: testBeneficiary.publicKey,
+          mint,
+          vestingInfo: testVestingPda,
+          vault,
+          beneficiaryTokenAccount,
+          funderTokenAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([testFunder])
+        .rpc();
+
+      const beneficiaryAccount = await getAccount(provider.connection, beneficiaryTokenAccount);
+      const funderAccount = await getAccount(provider.connection, funderTokenAccount);
+      expect((beneficiaryAccount.amount + funderAccount.amount).toString()).to.equal(TOKEN_AMOUNT.addn(5).toString());
+      expect(Number(funderAccount.amount)).to.be.greaterThan(5);
+      expect(await provider.connection.getAccountInfo(vault)).to.be.null;
+      expect(await provider.connection.getAccountInfo(testVestingPda)).to.be.null;
+    });
+  });
+
+  describe("grants", () => {
+    let testFunder: Keypair;
+    let testBeneficiary: Keypair;
+
+    const deriveGrant = (grantId: BN): PublicKey =>
+      PublicKey.findProgramAddressSync(
+        [testFunder.publicKey.toBuffer(), grantId.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      )[0];
+
+    const initializeGrant = async (grantId: BN, duration: number) => {
+      const startSlot = (await getCurrentSlot()) + 10;
+      await program.methods
+        .initialize(grantId, new BN(startSlot), new BN(duration), VESTING_AMOUNT, NO_CLIFF, LINEAR, false, false)
+        .accounts({
+          funder: testFunder.publicKey,
+          beneficiary: testBeneficiary.publicKey,
+          vestingInfo: deriveGrant(grantId),
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([testFunder])
+        .rpc();
+    };
+
+    beforeEach(async () => {
+      testFunder = Keypair.generate();
+      testBeneficiary = Keypair.generate();
+
+      await provider.connection.requestAirdrop(testFunder.publicKey, 30 * LAMPORTS_PER_SOL);
+      await provider.connection.requestAirdrop(testBeneficiary.publicKey, LAMPORTS_PER_SOL);
+      await new Promise(resolve => setTimeout(resolve, 1000));
+    });
+
+    it("allows multiple grants for the same beneficiary", async () => {
+      const firstGrant = new BN(1);
+      const secondGrant = new BN(2);
+      await initializeGrant(firstGrant, 1000);
+      await initializeGrant(secondGrant, 2000);
+
+      const first: VestingInfo = await program.account.vestingInfo.fetch(deriveGrant(firstGrant));
+      const second: VestingInfo = await program.account.vestingInfo.fetch(deriveGrant(secondGrant));
+      expect(first.grantId.toString()).to.equal(firstGrant.toString());
+      expect(second.grantId.toString()).to.equal(secondGrant.toString());
+      expect(second.duration.toNumber()).to.equal(2000);
+    });
+
+    it("ignores lamports sent to the vesting account after initialization", async () => {
+      const grantId = new BN(3);
+      const vestingPda = deriveGrant(grantId);
+      await initializeGrant(grantId, 100);
+
+      // Donate to the vesting account: the schedule must not change
+      await provider.sendAndConfirm(
+        new anchor.web3.Transaction().add(
+          SystemProgram.transfer({
+            fromPubkey: testFunder.publicKey,
+            toPubkey: vestingPda,
+            lamports: VESTING_AMOUNT.toNumber(),
+          })
+        ),
+        [testFunder]
+      );
+
+      await new Promise(resolve => setTimeout(resolve, 5000));
+      await program.methods
+        .release(grantId)
+        .accountsPartial({
+          caller: testBeneficiary.publicKey,
+          beneficiary: testBeneficiary.publicKey,
+          funder: testFunder.publicKey,
+          vestingInfo: vestingPda,
+        })
+        .signers([testBeneficiary])
+        .rpc();
+
+      const vestingInfo: VestingInfo = await program.account.vestingInfo.fetch(vestingPda);
+      expect(vestingInfo.totalAmount.toString()).to.equal(VESTING_AMOUNT.toString());
+      expect(vestingInfo.released.lt(VESTING_AMOUNT)).to.be.true;
+    });
+  });
+
+  describe("transfer_beneficiary()", () => {
+    let testFunder: Keypair;
+    let testBeneficiary: Keypair;
+    let newBeneficiary: Keypair;
+    let testVestingPda: PublicKey;
+
+    beforeEach(async () => {
+      testFunder = Keypair.generate();
+      testBeneficiary = Keypair.generate();
+      newBeneficiary = Keypair.generate();
+
+      await provider.connection.requestAirdrop(testFunder.publicKey, 20 * LAMPORTS_PER_SOL);
+      await provider.connection.requestAirdrop(testBeneficiary.publicKey, LAMPORTS_PER_SOL);
+      await provider.connection.requestAirdrop(newBeneficiary.publicKey, LAMPORTS_PER_SOL);
+      await new Promise(resolve => setTimeout(resolve, 1000));
+
+      [testVestingPda] = PublicKey.findProgramAddressSync(
+        [testFunder.publicKey.toBuffer(), GRANT_ID.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+    });
+
+    const initializeVesting = async (transferApproval: boolean) => {
+      const startSlot = (await getCurrentSlot()) + 10;
+      await program.methods
+        .initialize(GRANT_ID, new BN(startSlot), new BN(1000), VESTING_AMOUNT, NO_CLIFF, LINEAR, false, transferApproval)
+        .accounts({
+          funder: testFunder.publicKey,
+          beneficiary: testBeneficiary.publicKey,
+          vestingInfo: testVestingPda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([testFunder])
+        .rpc();
+    };
+
+    const transferBeneficiary = (signers: Keypair[]) =>
+      program.methods
+        .transferBeneficiary(GRANT_ID)
+        .accountsPartial({
+          beneficiary: testBeneficiary.publicKey,
+          newBeneficiary: newBeneficiary.publicKey,
+          funder: testFunder.publicKey,
+          vestingInfo: testVestingPda,
+        })
+        .signers(signers)
+        .rpc();
+
+    const release = (beneficiary: PublicKey) =>
+      program.methods
+        .release(GRANT_ID)
+        .accountsPartial({
+          caller: beneficiary,
+          beneficiary,
+          funder: testFunder.publicKey,
+          vestingInfo: testVestingPda,
+        })
+        .signers([beneficiary.equals(testBeneficiary.publicKey) ? testBeneficiary : newBeneficiary])
+        .rpc();
+
+    it("moves future releases to the new beneficiary", async () => {
+      await initializeVesting(false);
+      await transferBeneficiary([testBeneficiary]);
+
+      const vestingInfo: VestingInfo = await program.account.vestingInfo.fetch(testVestingPda);
+      expect(vestingInfo.beneficiary.toString()).to.equal(newBeneficiary.publicKey.toString());
+
+      await new Promise(resolve => setTimeout(resolve, 5000));
+      await expectTransactionToFail(release(testBeneficiary.publicKey));
+
+      const balanceBefore = await getAccountBalance(newBeneficiary.publicKey);
+      await release(newBeneficiary.publicKey);
+      const balanceAfter = await getAccountBalance(newBeneficiary.publicKey);
+      expect(balanceAfter).to.be.greaterThan(balanceBefore);
+    });
+
+    it("requires the funder's signature when approval is enabled", async () => {
+      await initializeVesting(true);
+      await expectTransactionToFail(transferBeneficiary([testBeneficiary]));
+
+      await transferBeneficiary([testBeneficiary, testFunder]);
+
+      const vestingInfo: VestingInfo = await program.account.vestingInfo.fetch(testVestingPda);
+      expect(vestingInfo.beneficiary.toString()).to.equal(newBeneficiary.publicKey.toString());
+    });
+
+    it("rejects a transfer signed by someone other than the beneficiary", async () => {
+      await initializeVesting(false);
+
+      await expectTransactionToFail(
+        program.methods
+          .transferBeneficiary(GRANT_ID)
+          .accountsPartial({
+            beneficiary: newBeneficiary.publicKey,
+            newBeneficiary: newBeneficiary.publicKey,
+            funder: testFunder.publicKey,
+            vestingInfo: testVestingPda,
+          })
+          .signers([newBeneficiary])
+          .rpc()
+      );
+    });
+  });
+
   describe("time validation", () => {
     let testFunder: Keypair;
     let testBeneficiary: Keypair;
@@ -499,7 +1183,7 @@ describe("Vesting Program", () => {
       await new Promise(resolve => setTimeout(resolve, 1000));
       
       [testVestingPda] = PublicKey.findProgramAddressSync(
-        [testBeneficiary.publicKey.toBuffer()],
+        [testFunder.publicKey.toBuffer(), GRANT_ID.toArrayLike(Buffer, "le", 8)],
         program.programId
       );
     });
@@ -510,7 +1194,7 @@ describe("Vesting Program", () => {
       const duration = 1000;
       
       await program.methods
-        .initialize(new BN(startSlot), new BN(duration), VESTING_AMOUNT)
+        .initialize(GRANT_ID, new BN(startSlot), new BN(duration), VESTING_AMOUNT, NO_CLIFF, LINEAR, false, false)
         .accounts({
           funder: testFunder.publicKey,
           beneficiary: testBeneficiary.publicKey,
@@ -535,8 +1219,9 @@ describe("Vesting Program", () => {
         
         try {
           await program.methods
-            .release()
+            .release(GRANT_ID)
             .accountsPartial({
+              caller: testBeneficiary.publicKey,
               beneficiary: testBeneficiary.publicKey,
               funder: testFunder.publicKey,
               vestingInfo: testVestingPda,
@@ -562,7 +1247,7 @@ describe("Vesting Program", () => {
       const duration = 100;
       
       await program.methods
-        .initialize(new BN(startSlot), new BN(duration), VESTING_AMOUNT)
+        .initialize(GRANT_ID, new BN(startSlot), new BN(duration), VESTING_AMOUNT, NO_CLIFF, LINEAR, false, false)
         .accounts({
           funder: testFunder.publicKey,
           beneficiary: testBeneficiary.publicKey,
@@ -578,8 +1263,9 @@ describe("Vesting Program", () => {
       const vestingInfoBefore: VestingInfo = await program.account.vestingInfo.fetch(testVestingPda);
       
       await program.methods
-        .release()
+        .release(GRANT_ID)
         .accountsPartial({
+          caller: testBeneficiary.publicKey,
           beneficiary: testBeneficiary.publicKey,
           funder: testFunder.publicKey,
           vestingInfo: testVestingPda,
@@ -1,29 +1,45 @@
 use anchor_lang::prelude::*;
+use anchor_spl::token::spl_token;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
 
 declare_id!("G3ZkAxvJtZnq7E9bSiMczRaPxR9X2csuY3dvrwnELG9M");
 
+const MAX_SCHEDULE_POINTS: usize = 10;
+
 #[program]
 pub mod vesting {
     use super::*;
 
+    #[allow(clippy::too_many_arguments)]
     pub fn initialize(
         ctx: Context<InitializeCtx>,
+        grant_id: u64,
         start_slot: u64,
         duration: u64,
         lamports_amount: u64,
+        cliff: u64,
+        schedule: VestingSchedule,
+        revocable: bool,
+        transfer_approval: bool,
     ) -> Result<()> {
-        require!(
-            start_slot > Clock::get()?.slot,
-            CustomError::InvalidStartSlot
-        );
-        require!(duration > 0, CustomError::InvalidDuration);
-
         let vesting_info = &mut ctx.accounts.vesting_info;
-        vesting_info.funder = *ctx.accounts.funder.key;
-        vesting_info.beneficiary = *ctx.accounts.beneficiary.key;
-        vesting_info.start_slot = start_slot;
-        vesting_info.duration = duration;
-        vesting_info.released = 0;
+        init_vesting(
+            vesting_info,
+            ctx.accounts.funder.key(),
+            ctx.accounts.beneficiary.key(),
+            grant_id,
+            None,
+            transfer_approval,
+        );
+        set_vesting_terms(
+            vesting_info,
+            start_slot,
+            duration,
+            lamports_amount,
+            cliff,
+            schedule,
+            revocable,
+        )?;
 
         msg!("Transfering lamports to the vesting account");
         let transfer_instruction = anchor_lang::solana_program::system_instruction::transfer(
@@ -44,25 +60,66 @@ pub mod vesting {
         Ok(())
     }
 
-    pub fn release(ctx: Context<ReleaseCtx>) -> Result<()> {
-        let beneficiary = &mut ctx.accounts.beneficiary;
+    #[allow(clippy::too_many_arguments)]
+    pub fn initialize_token(
+        ctx: Context<InitializeTokenCtx>,
+        grant_id: u64,
+        start_slot: u64,
+        duration: u64,
+        token_amount: u64, // in base units of the mint
+        cliff: u64,
+        schedule: VestingSchedule,
+        revocable: bool,
+        transfer_approval: bool,
+    ) -> Result<()> {
         let vesting_info = &mut ctx.accounts.vesting_info;
+        init_vesting(
+            vesting_info,
+            ctx.accounts.funder.key(),
+            ctx.accounts.beneficiary.key(),
+            grant_id,
+            Some(ctx.accounts.mint.key()),
+            transfer_approval,
+        );
+        set_vesting_terms(
+            vesting_info,
+            start_slot,
+            duration,
+            token_amount,
+            cliff,
+            schedule,
+            revocable,
+        )?;
 
-        let rent_lamports = Rent::get()?.minimum_balance(vesting_info.to_account_info().data_len());
-        let balance = **vesting_info.to_account_info().try_borrow_lamports()? - rent_lamports;
-        let amount = releasable(
-            vesting_info.released,
-            balance,
-            vesting_info.start_slot,
-            vesting_info.duration,
+        msg!("Transfering tokens to the vault");
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.funder_token_account.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                    authority: ctx.accounts.funder.to_account_info(),
+                },
+            ),
+            token_amount,
         )?;
 
+        Ok(())
+    }
+
+    pub fn release(ctx: Context<ReleaseCtx>, _grant_id: u64) -> Result<()> {
+        let beneficiary = &mut ctx.accounts.beneficiary;
+        let vesting_info = &mut ctx.accounts.vesting_info;
+        require!(!vesting_info.revoked, CustomError::VestingRevoked);
+
+        let amount = releasable(vesting_info)?;
+
         msg!("Releasing {} lamports to {}", amount, beneficiary.key());
         vesting_info.released += amount;
         **beneficiary.to_account_info().try_borrow_mut_lamports()? += amount;
         **vesting_info.to_account_info().try_borrow_mut_lamports()? -= amount;
 
-        if **vesting_info.to_account_info().lamports.borrow() <= rent_lamports {
+        if vesting_info.released == vesting_info.total_amount {
             msg!("Closing the vesting account and returning the rent fees to the funder");
             let funder = &mut ctx.accounts.funder;
             **funder.to_account_info().try_borrow_mut_lamports()? +=
@@ -77,19 +134,229 @@ pub mod vesting {
 
         Ok(())
     }
+
+    pub fn revoke(ctx: Context<RevokeCtx>, _grant_id: u64) -> Result<()> {
+        let beneficiary = &mut ctx.accounts.beneficiary;
+        let funder = &mut ctx.accounts.funder;
+        let vesting_info = &mut ctx.accounts.vesting_info;
+        require!(vesting_info.revocable, CustomError::NotRevocable);
+        require!(!vesting_info.revoked, CustomError::VestingRevoked);
+
+        let vested = releasable(vesting_info)?;
+        let unvested = vesting_info.total_amount - vesting_info.released - vested;
+
+        // The beneficiary keeps what has vested so far, the rest goes back to the funder
+        msg!("Releasing {} lamports to {}", vested, beneficiary.key());
+        vesting_info.released += vested;
+        vesting_info.revoked = true;
+        **beneficiary.to_account_info().try_borrow_mut_lamports()? += vested;
+        **funder.to_account_info().try_borrow_mut_lamports()? += unvested;
+        **vesting_info.to_account_info().try_borrow_mut_lamports()? -= vested + unvested;
+
+        emit!(EtherReleased {
+            amount: vested,
+            beneficiary: *beneficiary.key,
+        });
+        emit!(UnvestedReturned {
+            unvested,
+            funder: *funder.key,
+        });
+
+        Ok(())
+    }
+
+    pub fn release_token(ctx: Context<ReleaseTokenCtx>, _grant_id: u64) -> Result<()> {
+        let vesting_info = &mut ctx.accounts.vesting_info;
+        require!(!vesting_info.revoked, CustomError::VestingRevoked);
+
+        let balance = ctx.accounts.vault.amount;
+        let amount = releasable(vesting_info)?;
+
+        msg!(
+            "Releasing {} tokens to {}",
+            amount,
+            vesting_info.beneficiary
+        );
+        vesting_info.released += amount;
+        transfer_from_vault(
+            vesting_info,
+            &ctx.accounts.vault,
+            &ctx.accounts.beneficiary_token_account,
+            &ctx.accounts.token_program,
+            ctx.program_id,
+            amount,
+        )?;
+
+        if vesting_info.released == vesting_info.total_amount {
+            // Tokens sent directly to the vault are not part of the schedule, like stray
+            // lamports they go back to the funder so that the vault can be closed
+            let leftover = balance - amount;
+            if leftover > 0 {
+                msg!("Sweeping {} leftover tokens to the funder", leftover);
+                transfer_from_vault(
+                    vesting_info,
+                    &ctx.accounts.vault,
+                    &ctx.accounts.funder_token_account,
+                    &ctx.accounts.token_program,
+                    ctx.program_id,
+                    leftover,
+                )?;
+            }
+
+            msg!("Closing the vesting accounts and returning the rent fees to the funder");
+            let funder = &ctx.accounts.funder;
+            close_vault(
+                vesting_info,
+                &ctx.accounts.vault,
+                &funder.to_account_info(),
+                &ctx.accounts.token_program,
+                ctx.program_id,
+            )?;
+            **funder.to_account_info().try_borrow_mut_lamports()? +=
+                **vesting_info.to_account_info().try_borrow_mut_lamports()?;
+            **vesting_info.to_account_info().try_borrow_mut_lamports()? = 0;
+        }
+
+        emit!(TokensReleased {
+            amount,
+            beneficiary: ctx.accounts.beneficiary.key(),
+            mint: ctx.accounts.mint.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn revoke_token(ctx: Context<RevokeTokenCtx>, _grant_id: u64) -> Result<()> {
+        let vesting_info = &mut ctx.accounts.vesting_info;
+        require!(vesting_info.revocable, CustomError::NotRevocable);
+        require!(!vesting_info.revoked, CustomError::VestingRevoked);
+
+        let vested = releasable(vesting_info)?;
+        let unvested = vesting_info.total_amount - vesting_info.released - vested;
+        // Tokens sent directly to the vault are not part of the schedule
+        let leftover = ctx.accounts.vault.amount - vested - unvested;
+
+        // The beneficiary keeps what has vested so far, the rest goes back to the funder
+        msg!(
+            "Releasing {} tokens to {}",
+            vested,
+            vesting_info.beneficiary
+        );
+        vesting_info.released += vested;
+        vesting_info.revoked = true;
+        transfer_from_vault(
+            vesting_info,
+            &ctx.accounts.vault,
+            &ctx.accounts.beneficiary_token_account,
+            &ctx.accounts.token_program,
+            ctx.program_id,
+            vested,
+        )?;
+        transfer_from_vault(
+            vesting_info,
+            &ctx.accounts.vault,
+            &ctx.accounts.funder_token_account,
+            &ctx.accounts.token_program,
+            ctx.program_id,
+            unvested + leftover,
+        )?;
+
+        msg!("Closing the vesting accounts and returning the rent fees to the funder");
+        let funder = &ctx.accounts.funder;
+        close_vault(
+            vesting_info,
+            &ctx.accounts.vault,
+            &funder.to_account_info(),
+            &ctx.accounts.token_program,
+            ctx.program_id,
+        )?;
+        **funder.to_account_info().try_borrow_mut_lamports()? +=
+            **vesting_info.to_account_info().try_borrow_mut_lamports()?;
+        **vesting_info.to_account_info().try_borrow_mut_lamports()? = 0;
+
+        emit!(TokensReleased {
+            amount: vested,
+            beneficiary: ctx.accounts.beneficiary.key(),
+            mint: ctx.accounts.mint.key(),
+        });
+        emit!(UnvestedReturned {
+            unvested,
+            funder: ctx.accounts.funder.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn transfer_beneficiary(
+        ctx: Context<TransferBeneficiaryCtx>,
+        _grant_id: u64,
+    ) -> Result<()> {
+        let vesting_info = &mut ctx.accounts.vesting_info;
+        require!(!vesting_info.revoked, CustomError::VestingRevoked);
+        if vesting_info.transfer_approval {
+            require!(
+                ctx.accounts.funder.to_account_info().is_signer,
+                CustomError::TransferNotApproved
+            );
+        }
+
+        let new_beneficiary = ctx.accounts.new_beneficiary.key();
+        msg!(
+            "Transfering the vesting from {} to {}",
+            vesting_info.beneficiary,
+            new_beneficiary
+        );
+        vesting_info.beneficiary = new_beneficiary;
+
+        emit!(BeneficiaryTransferred {
+            previous: ctx.accounts.beneficiary.key(),
+            beneficiary: new_beneficiary,
+        });
+
+        Ok(())
+    }
 }
 
 #[account]
 #[derive(InitSpace)]
 pub struct VestingInfo {
-    pub released: u64,       // 8 bytes
-    pub funder: Pubkey,      // 32 bytes
-    pub beneficiary: Pubkey, // 32 bytes
-    pub start_slot: u64,     // 8 bytes
-    pub duration: u64,       // 8 bytes
+    pub grant_id: u64,             // 8 bytes
+    pub total_amount: u64,         // 8 bytes, fixed at initialization
+    pub released: u64,             // 8 bytes
+    pub funder: Pubkey,            // 32 bytes
+    pub beneficiary: Pubkey,       // 32 bytes
+    pub start_slot: u64,           // 8 bytes
+    pub duration: u64,             // 8 bytes
+    pub cliff: u64,                // 8 bytes, in slots after start_slot
+    pub schedule: VestingSchedule, // 1 + 4 + 10 * 16 bytes at most
+    pub revocable: bool,           // 1 byte
+    pub revoked: bool,             // 1 byte
+    pub mint: Option<Pubkey>,      // 1 + 32 bytes, None for native vestings
+    pub transfer_approval: bool,   // 1 byte, funder must sign beneficiary transfers
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Clone, InitSpace)]
+pub enum VestingSchedule {
+    Linear,
+    // unlocks every `interval` slots
+    Stepwise {
+        interval: u64,
+    },
+    // (slot, cumulative amount) points, linearly interpolated
+    Piecewise {
+        #[max_len(MAX_SCHEDULE_POINTS)]
+        points: Vec<SchedulePoint>,
+    },
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Clone, InitSpace)]
+pub struct SchedulePoint {
+    pub slot: u64,   // 8 bytes
+    pub amount: u64, // 8 bytes
 }
 
 #[derive(Accounts)]
+#[instruction(grant_id: u64)]
 pub struct InitializeCtx<'info> {
     #[account(mut)]
     pub funder: Signer<'info>,
@@ -97,7 +364,7 @@ pub struct InitializeCtx<'info> {
     #[account(
         init, 
         payer = funder, 
-        seeds = [beneficiary.key().as_ref()],
+        seeds = [funder.key().as_ref(), grant_id.to_le_bytes().as_ref()],
         bump,
         space = 8 + VestingInfo::INIT_SPACE
     )]
@@ -106,68 +373,454 @@ pub struct InitializeCtx<'info> {
 }
 
 #[derive(Accounts)]
+#[instruction(grant_id: u64)]
 pub struct ReleaseCtx<'info> {
+    pub caller: Signer<'info>, // anyone can release, funds only go to the beneficiary
     #[account(mut)]
-    pub beneficiary: Signer<'info>,
+    pub beneficiary: SystemAccount<'info>,
     #[account(mut)] // mutable to return the rent fees back
     pub funder: SystemAccount<'info>,
     #[account(
         mut,
-        seeds = [beneficiary.key().as_ref()],
+        seeds = [funder.key().as_ref(), grant_id.to_le_bytes().as_ref()],
         bump,
         constraint = vesting_info.beneficiary == *beneficiary.key @ CustomError::InvalidBeneficiary,
         constraint = vesting_info.funder == *funder.key @ CustomError::InvalidFunder,
+        constraint = vesting_info.mint.is_none() @ CustomError::InvalidMint,
     )]
     pub vesting_info: Account<'info, VestingInfo>,
 }
 
-fn releasable(released: u64, balance: u64, start_slot: u64, duration: u64) -> Result<u64> {
-    let current_slot = Clock::get()?.slot;
-    Ok(vested_amount(current_slot, released, balance, start_slot, duration)? - released)
+#[derive(Accounts)]
+#[instruction(grant_id: u64)]
+pub struct RevokeCtx<'info> {
+    #[account(mut)]
+    pub funder: Signer<'info>,
+    #[account(mut)]
+    pub beneficiary: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [funder.key().as_ref(), grant_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = vesting_info.beneficiary == *beneficiary.key @ CustomError::InvalidBeneficiary,
+        constraint = vesting_info.funder == *funder.key @ CustomError::InvalidFunder,
+        constraint = vesting_info.mint.is_none() @ CustomError::InvalidMint,
+    )]
+    pub vesting_info: Account<'info, VestingInfo>,
 }
 
-fn vested_amount(
-    timestamp: u64,
-    released: u64,
-    balance: u64,
+#[derive(Accounts)]
+#[instruction(grant_id: u64)]
+pub struct InitializeTokenCtx<'info> {
+    #[account(mut)]
+    pub funder: Signer<'info>,
+    pub beneficiary: SystemAccount<'info>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        constraint = funder_token_account.mint == mint.key() @ CustomError::InvalidMint
+    )]
+    pub funder_token_account: Account<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = funder,
+        seeds = [funder.key().as_ref(), grant_id.to_le_bytes().as_ref()],
+        bump,
+        space = 8 + VestingInfo::INIT_SPACE
+    )]
+    pub vesting_info: Account<'info, VestingInfo>,
+    #[account(
+        init,
+        payer = funder,
+        seeds = ["vault".as_ref(), funder.key().as_ref(), grant_id.to_le_bytes().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = vesting_info
+    )]
+    pub vault: Account<'info, TokenAccount>,
+    // Programs and other
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(grant_id: u64)]
+pub struct ReleaseTokenCtx<'info> {
+    pub caller: Signer<'info>, // anyone can release, funds only go to the beneficiary
+    pub beneficiary: SystemAccount<'info>,
+    #[account(mut)] // mutable to return the rent fees back
+    pub funder: SystemAccount<'info>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [funder.key().as_ref(), grant_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = vesting_info.beneficiary == *beneficiary.key @ CustomError::InvalidBeneficiary,
+        constraint = vesting_info.funder == *funder.key @ CustomError::InvalidFunder,
+        constraint = vesting_info.mint == Some(mint.key()) @ CustomError::InvalidMint,
+    )]
+    pub vesting_info: Account<'info, VestingInfo>,
+    #[account(
+        mut,
+        seeds = ["vault".as_ref(), funder.key().as_ref(), grant_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = beneficiary
+    )]
+    pub beneficiary_token_account: Account<'info, TokenAccount>,
+    // receives the tokens sent directly to the vault, on the last release
+    #[account(
+        mut,
+        constraint = funder_token_account.mint == mint.key() @ CustomError::InvalidMint,
+        constraint = funder_token_account.owner == *funder.key @ CustomError::InvalidFunder,
+    )]
+    pub funder_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(grant_id: u64)]
+pub struct RevokeTokenCtx<'info> {
+    #[account(mut)] // mutable to return the rent fees back
+    pub funder: Signer<'info>,
+    pub beneficiary: SystemAccount<'info>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [funder.key().as_ref(), grant_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = vesting_info.beneficiary == *beneficiary.key @ CustomError::InvalidBeneficiary,
+        constraint = vesting_info.funder == *funder.key @ CustomError::InvalidFunder,
+        constraint = vesting_info.mint == Some(mint.key()) @ CustomError::InvalidMint,
+    )]
+    pub vesting_info: Account<'info, VestingInfo>,
+    #[account(
+        mut,
+        seeds = ["vault".as_ref(), funder.key().as_ref(), grant_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = beneficiary
+    )]
+    pub beneficiary_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = funder_token_account.mint == mint.key() @ CustomError::InvalidMint
+    )]
+    pub funder_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(grant_id: u64)]
+pub struct TransferBeneficiaryCtx<'info> {
+    pub beneficiary: Signer<'info>,
+    pub new_beneficiary: SystemAccount<'info>,
+    pub funder: SystemAccount<'info>, // must also sign when transfer_approval is set
+    #[account(
+        mut,
+        seeds = [funder.key().as_ref(), grant_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = vesting_info.beneficiary == *beneficiary.key @ CustomError::InvalidBeneficiary,
+        constraint = vesting_info.funder == *funder.key @ CustomError::InvalidFunder,
+    )]
+    pub vesting_info: Account<'info, VestingInfo>,
+}
+
+fn init_vesting(
+    vesting_info: &mut Account<VestingInfo>,
+    funder: Pubkey,
+    beneficiary: Pubkey,
+    grant_id: u64,
+    mint: Option<Pubkey>,
+    transfer_approval: bool,
+) {
+    vesting_info.grant_id = grant_id;
+    vesting_info.funder = funder;
+    vesting_info.beneficiary = beneficiary;
+    vesting_info.released = 0;
+    vesting_info.revoked = false;
+    vesting_info.mint = mint;
+    vesting_info.transfer_approval = transfer_approval;
+}
+
+fn set_vesting_terms(
+    vesting_info: &mut Account<VestingInfo>,
+    start_slot: u64,
+    duration: u64,
+    amount: u64,
+    cliff: u64,
+    schedule: VestingSchedule,
+    revocable: bool,
+) -> Result<()> {
+    require!(
+        start_slot > Clock::get()?.slot,
+        CustomError::InvalidStartSlot
+    );
+    // The end slot must fit in a u64, so does the end of the cliff
+    require!(
+        duration > 0 && start_slot.checked_add(duration).is_some(),
+        CustomError::InvalidDuration
+    );
+    require!(cliff <= duration, CustomError::InvalidCliff);
+    validate_schedule(&schedule, start_slot, duration, amount)?;
+
+    vesting_info.total_amount = amount;
+    vesting_info.start_slot = start_slot;
+    vesting_info.duration = duration;
+    vesting_info.cliff = cliff;
+    vesting_info.schedule = schedule;
+    vesting_info.revocable = revocable;
+    Ok(())
+}
+
+// The last piecewise point closes the vesting and unlocks the whole amount
+fn validate_schedule(
+    schedule: &VestingSchedule,
     start_slot: u64,
     duration: u64,
-) -> Result<u64> {
+    lamports_amount: u64,
+) -> Result<()> {
+    match schedule {
+        VestingSchedule::Linear => {}
+        VestingSchedule::Stepwise { interval } => {
+            require!(
+                *interval > 0 && *interval <= duration,
+                CustomError::InvalidSchedule
+            );
+        }
+        VestingSchedule::Piecewise { points } => {
+            require!(
+                !points.is_empty() && points.len() <= MAX_SCHEDULE_POINTS,
+                CustomError::InvalidSchedule
+            );
+            let mut previous = SchedulePoint {
+                slot: start_slot,
+                amount: 0,
+            };
+            for point in points {
+                require!(
+                    point.slot > previous.slot && point.amount >= previous.amount,
+                    CustomError::InvalidSchedule
+                );
+                previous = point.clone();
+            }
+            require!(
+                previous.slot == start_slot + duration && previous.amount == lamports_amount,
+                CustomError::InvalidSchedule
+            );
+        }
+    }
+    Ok(())
+}
+
+fn transfer_from_vault<'info>(
+    vesting_info: &Account<'info, VestingInfo>,
+    vault: &Account<'info, TokenAccount>,
+    to: &Account<'info, TokenAccount>,
+    token_program: &Program<'info, Token>,
+    program_id: &Pubkey,
+    amount: u64,
+) -> Result<()> {
+    let grant_id = vesting_info.grant_id.to_le_bytes();
+    let (vesting_info_key, vesting_info_bump) = Pubkey::find_program_address(
+        &[vesting_info.funder.as_ref(), grant_id.as_ref()],
+        program_id,
+    );
+
+    let vesting_info_signer_seeds: &[&[&[u8]]] = &[&[
+        vesting_info.funder.as_ref(),
+        grant_id.as_ref(),
+        &[vesting_info_bump],
+    ]];
+
+    anchor_lang::solana_program::program::invoke_signed(
+        &spl_token::instruction::transfer(
+            &anchor_spl::token::ID,
+            &vault.key(),
+            &to.key(),
+            &vesting_info_key, //owner
+            &[&vesting_info_key],
+            amount,
+        )?,
+        &[
+            vault.to_account_info(),
+            to.to_account_info(),
+            vesting_info.to_account_info(),
+            token_program.to_account_info(),
+        ],
+        vesting_info_signer_seeds,
+    )?;
+
+    Ok(())
+}
+
+fn close_vault<'info>(
+    vesting_info: &Account<'info, VestingInfo>,
+    vault: &Account<'info, TokenAccount>,
+    funder: &AccountInfo<'info>,
+    token_program: &Program<'info, Token>,
+    program_id: &Pubkey,
+) -> Result<()> {
+    let grant_id = vesting_info.grant_id.to_le_bytes();
+    let (vesting_info_key, vesting_info_bump) = Pubkey::find_program_address(
+        &[vesting_info.funder.as_ref(), grant_id.as_ref()],
+        program_id,
+    );
+
+    let vesting_info_signer_seeds: &[&[&[u8]]] = &[&[
+        vesting_info.funder.as_ref(),
+        grant_id.as_ref(),
+        &[vesting_info_bump],
+    ]];
+
+    anchor_lang::solana_program::program::invoke_signed(
+        &spl_token::instruction::close_account(
+            &anchor_spl::token::ID,
+            &vault.key(),
+            funder.key,
+            &vesting_info_key,
+            &[&vesting_info_key],
+        )?,
+        &[
+            vault.to_account_info(),
+            funder.clone(),
+            vesting_info.to_account_info(),
+            token_program.to_account_info(),
+        ],
+        vesting_info_signer_seeds,
+    )?;
+
+    Ok(())
+}
+
+fn releasable(vesting_info: &VestingInfo) -> Result<u64> {
+    let current_slot = Clock::get()?.slot;
+    Ok(vested_amount(vesting_info, current_slot)? - vesting_info.released)
+}
+
+// Funds sent to the accounts after initialization do not alter the schedule
+fn vested_amount(vesting_info: &VestingInfo, timestamp: u64) -> Result<u64> {
+    // Nothing vests before the cliff
+    if timestamp < vesting_info.start_slot + vesting_info.cliff {
+        return Ok(0);
+    }
     Ok(vesting_schedule(
-        balance + released,
+        &vesting_info.schedule,
+        vesting_info.total_amount,
         timestamp,
-        start_slot,
-        duration,
+        vesting_info.start_slot,
+        vesting_info.duration,
     ))
 }
 
-fn vesting_schedule(total_allocation: u64, timestamp: u64, start_slot: u64, duration: u64) -> u64 {
+fn vesting_schedule(
+    schedule: &VestingSchedule,
+    total_allocation: u64,
+    timestamp: u64,
+    start_slot: u64,
+    duration: u64,
+) -> u64 {
     if timestamp < start_slot {
         return 0;
     } else if timestamp > start_slot + duration {
         return total_allocation;
-    } else {
-        return (total_allocation * (timestamp - start_slot)) / duration;
+    }
+
+    let elapsed = timestamp - start_slot;
+    match schedule {
+        VestingSchedule::Linear => mul_div(total_allocation, elapsed, duration),
+        VestingSchedule::Stepwise { interval } => {
+            mul_div(total_allocation, elapsed - elapsed % interval, duration)
+        }
+        VestingSchedule::Piecewise { points } => {
+            let mut previous = SchedulePoint {
+                slot: start_slot,
+                amount: 0,
+            };
+            for point in points {
+                if timestamp < point.slot {
+                    return previous.amount
+                        + mul_div(
+                            point.amount - previous.amount,
+                            timestamp - previous.slot,
+                            point.slot - previous.slot,
+                        );
+                }
+                previous = point.clone();
+            }
+            previous.amount
+        }
     }
 }
 
+// a * b / c with a u128 intermediate product, the result never exceeds a since b <= c
+fn mul_div(a: u64, b: u64, c: u64) -> u64 {
+    (u128::from(a) * u128::from(b) / u128::from(c)) as u64
+}
+
 #[error_code]
 pub enum CustomError {
     #[msg("Invalid start slot, must be in the future")]
     InvalidStartSlot,
 
-    #[msg("Invalid duration, must be greater than 0")]
+    #[msg("Invalid duration, must be greater than 0 and end within the slot range")]
     InvalidDuration,
 
+    #[msg("Invalid cliff, must not exceed the duration")]
+    InvalidCliff,
+
+    #[msg("Invalid schedule")]
+    InvalidSchedule,
+
     #[msg("Invalid beneficiary")]
     InvalidBeneficiary,
 
     #[msg("Invalid funder")]
     InvalidFunder,
+
+    #[msg("The vesting is not revocable")]
+    NotRevocable,
+
+    #[msg("The vesting was revoked")]
+    VestingRevoked,
+
+    #[msg("The mint does not match the vesting")]
+    InvalidMint,
+
+    #[msg("The funder must approve the transfer")]
+    TransferNotApproved,
 }
 
 #[event]
 pub struct EtherReleased {
     amount: u64,
     beneficiary: Pubkey,
-}
\ No newline at end of file
+}
+
+#[event]
+pub struct TokensReleased {
+    amount: u64,
+    beneficiary: Pubkey,
+    mint: Pubkey,
+}
+
+#[event]
+pub struct UnvestedReturned {
+    unvested: u64,
+    funder: Pubkey,
+}
+
+#[event]
+pub struct BeneficiaryTransferred {
+    previous: Pubkey,
+    beneficiary: Pubkey,
+}